@@ -1,43 +1,104 @@
 //! Codegen
 
+macro_rules! cstr {
+    ($s:expr) => {
+        std::ffi::CString::new($s).unwrap().as_ptr()
+    };
+}
+
+pub mod choice;
+pub mod edit;
+pub mod expr;
+pub mod figurative;
+pub mod fixed;
 pub mod func;
+pub mod list;
+pub mod record;
+pub mod sort;
+pub mod string;
+pub mod table;
+pub mod text;
 pub mod var;
 
-use std::{collections::HashMap, hash::Hash, iter::Peekable, ptr::null_mut};
+use std::{collections::HashMap, iter::Peekable, ptr::null_mut};
 
 use llvm_sys::{
-    analysis::{LLVMVerifierFailureAction, LLVMVerifyFunction, LLVMVerifyModule},
+    analysis::{LLVMVerifierFailureAction, LLVMVerifyModule},
     core::{
-        LLVMAddFunction, LLVMAppendBasicBlock, LLVMArrayType, LLVMBuildAlloca, LLVMBuildBitCast,
-        LLVMBuildCall2, LLVMBuildCondBr, LLVMBuildFCmp, LLVMBuildGlobalStringPtr, LLVMBuildICmp,
-        LLVMBuildLoad2, LLVMBuildPtrDiff2, LLVMBuildRet, LLVMBuildRetVoid, LLVMBuildStore,
-        LLVMConstInt, LLVMConstString, LLVMContextCreate, LLVMCreateBuilder, LLVMCreatePassManager,
-        LLVMDisposeBuilder, LLVMDumpModule, LLVMDumpValue, LLVMFunctionType, LLVMGetArrayLength,
-        LLVMGetPointerAddressSpace, LLVMInt16Type, LLVMInt1Type, LLVMInt32Type, LLVMInt8Type,
-        LLVMModuleCreateWithName, LLVMPointerType, LLVMPositionBuilderAtEnd, LLVMPrintModuleToFile,
-        LLVMRunPassManager, LLVMVectorType, LLVMVoidType,
+        LLVMAddFunction, LLVMAddGlobal, LLVMAppendBasicBlock, LLVMArrayType, LLVMBuildAlloca,
+        LLVMBuildBr, LLVMBuildCall2, LLVMBuildCondBr, LLVMBuildRet, LLVMBuildRetVoid,
+        LLVMBuildStore, LLVMConstInt, LLVMConstSIToFP, LLVMContextCreate, LLVMCreateBuilder,
+        LLVMCreatePassManager, LLVMDisposeBuilder, LLVMDisposePassManager, LLVMDoubleType,
+        LLVMFunctionType, LLVMGetBasicBlockParent, LLVMGetBasicBlockTerminator,
+        LLVMGetEntryBasicBlock, LLVMGetFirstFunction, LLVMGetFirstGlobal, LLVMGetFirstInstruction,
+        LLVMGetGlobalParent, LLVMGetInsertBlock, LLVMGetNamedFunction, LLVMGetNamedGlobal,
+        LLVMGetNextFunction, LLVMGetNextGlobal, LLVMGetParam, LLVMGetReturnType, LLVMGetValueName2,
+        LLVMInt1Type, LLVMInt32Type, LLVMInt64Type, LLVMInt8Type, LLVMIsAGlobalVariable,
+        LLVMIsDeclaration, LLVMModuleCreateWithName, LLVMPointerType, LLVMPositionBuilderAtEnd,
+        LLVMPositionBuilderBefore, LLVMPrintModuleToFile, LLVMRunPassManager,
+        LLVMSetGlobalConstant, LLVMSetInitializer, LLVMSetLinkage, LLVMStructType, LLVMVoidType,
     },
+    linker::LLVMLinkModules2,
     prelude::{
-        LLVMBasicBlockRef, LLVMBuilderRef, LLVMContextRef, LLVMDIBuilderRef, LLVMModuleRef,
-        LLVMValueRef,
+        LLVMBasicBlockRef, LLVMBuilderRef, LLVMContextRef, LLVMModuleRef, LLVMTypeRef, LLVMValueRef,
     },
-    target_machine::LLVMCodeGenOptLevel,
+    target::{LLVMABISizeOfType, LLVMGetModuleDataLayout, LLVMTargetDataRef},
     transforms::pass_manager_builder::{
         LLVMPassManagerBuilderCreate, LLVMPassManagerBuilderDispose,
         LLVMPassManagerBuilderPopulateFunctionPassManager,
+        LLVMPassManagerBuilderPopulateLTOPassManager,
         LLVMPassManagerBuilderPopulateModulePassManager, LLVMPassManagerBuilderSetOptLevel,
     },
-    LLVMContext, LLVMIntPredicate, LLVMModule, LLVMRealPredicate, LLVMType,
+    LLVMLinkage,
 };
 
-use crate::node::{BinaryOperators, Expr, LiteralExpr, Stmt, VariableType};
+use crate::node::{Constant, DefId, Expr, LiteralExpr, Stmt, VariableType, Visibility};
 
 use self::{func::Function, var::Variable};
 
-macro_rules! cstr {
-    ($s:expr) => {
-        std::ffi::CString::new($s).unwrap().as_ptr()
-    };
+/// Strings are stored as a pointer to their characters, numbers and booleans are stored as they
+/// are. A `NUMBER` is stored as a whole number of its smallest decimal and a `TEXT` as its
+/// characters, padded with spaces. A record is a struct of its fields and a table is the header
+/// its runtime keeps.
+pub unsafe fn llvm_type(ty: &VariableType) -> LLVMTypeRef {
+    match ty {
+        VariableType::String => LLVMPointerType(LLVMInt8Type(), 0),
+        VariableType::Integer => LLVMInt64Type(),
+        VariableType::Decimal => LLVMDoubleType(),
+        VariableType::Boolean => LLVMInt1Type(),
+        VariableType::Choice { .. } => LLVMInt8Type(),
+        VariableType::Fixed { .. } => LLVMInt64Type(),
+        VariableType::Text { length } => LLVMArrayType(LLVMInt8Type(), *length),
+        // Packed, so fields follow each other without padding and overlays line up with them.
+        VariableType::Record(fields) => {
+            let mut members = fields
+                .iter()
+                .filter(|field| field.redefines.is_none())
+                .map(|field| llvm_type(&field.ty))
+                .collect::<Vec<_>>();
+            LLVMStructType(members.as_mut_ptr(), members.len() as u32, 1)
+        }
+        VariableType::List {
+            item,
+            length: Some(length),
+            ..
+        } => LLVMArrayType(llvm_type(item), *length),
+        VariableType::List {
+            item, length: None, ..
+        } => list::growing_list_type(item),
+        VariableType::Table { .. } => table::table_type(),
+        VariableType::Function { parameters } => {
+            LLVMPointerType(func::function_type(parameters), 0)
+        }
+    }
+}
+
+/// Private members are only reachable from their own module, so they don't need to be exported.
+pub fn linkage(visibility: Visibility) -> LLVMLinkage {
+    match visibility {
+        Visibility::Public => LLVMLinkage::LLVMExternalLinkage,
+        Visibility::Private => LLVMLinkage::LLVMInternalLinkage,
+    }
 }
 
 pub struct CodeGen<T>
@@ -47,12 +108,25 @@ where
     pub context: LLVMContextRef,
     pub builder: LLVMBuilderRef,
     pub cur_module: Option<LLVMModuleRef>,
-    pub modules: Vec<LLVMModuleRef>,
+    /// Every Cobalt module is compiled into its own LLVM module, keyed by its name. They are
+    /// linked into `main` once everything has been compiled.
+    pub modules: HashMap<String, LLVMModuleRef>,
     pub stmts: Peekable<T>,
     // Hashmap key is `{module}-{func-name}`
     pub functions: HashMap<String, Function>,
-    pub variables: HashMap<String, Variable>,
+    /// Locals and module-level globals, keyed by the definition the resolver gave them.
+    pub variables: HashMap<DefId, Variable>,
+    /// The Cobalt module whose contents are being compiled. Top-level code lives in `main`.
+    pub cur_module_name: String,
     pub idx: usize,
+    /// 0 to 3, like `-O`. From 2 upwards the linked modules are also optimized as a whole.
+    pub opt_level: u32,
+    /// An `i1` slot that is set instead of stopping the program when a result can't be worked
+    /// out, while the values of a `COMPUTE` with an `ON SIZE ERROR` are lowered.
+    pub size_error: Option<LLVMValueRef>,
+    /// Set while the value of a `NUMBER` or `ROUNDED` target is lowered, so dividing whole
+    /// numbers keeps the decimals the target needs instead of truncating.
+    pub exact_division: bool,
 }
 
 impl<T> CodeGen<T>
@@ -63,343 +137,690 @@ where
         Self {
             builder: LLVMCreateBuilder(),
             context: LLVMContextCreate(),
-            modules: Vec::new(),
+            modules: HashMap::new(),
             cur_module: None,
             stmts,
             functions: HashMap::new(),
             variables: HashMap::new(),
+            cur_module_name: String::from("main"),
             idx: 0,
+            opt_level: 3,
+            size_error: None,
+            exact_division: false,
         }
     }
 
+    /// Creates the `main` module and declares the C library functions the generated code uses.
     pub unsafe fn setup_main_module(&mut self) {
         let main_module = LLVMModuleCreateWithName(cstr!("main"));
 
-        let printf_ty = LLVMFunctionType(LLVMInt32Type(), [].as_mut_ptr(), 0, 1);
-        let printf = LLVMAddFunction(main_module, cstr!("printf"), printf_ty);
-
-        self.functions.insert(
-            "main-printf".to_string(),
-            Function {
-                entry: None,
-                ret: None,
-                ty: printf_ty,
-                func: printf,
-                blocks: Vec::new(),
-            },
-        );
-
-        let strcmp_arg_tys = LLVMPointerType(LLVMInt8Type(), 0);
-        let strcmp_ty = LLVMFunctionType(
-            LLVMInt1Type(),
-            [strcmp_arg_tys, strcmp_arg_tys].as_mut_ptr(),
-            2,
-            0,
-        );
-        let strcmp = LLVMAddFunction(main_module, cstr!("strcmp"), strcmp_ty);
-
-        self.functions.insert(
-            "main-strcmp".to_string(),
-            Function {
-                entry: None,
-                ret: None,
-                ty: strcmp_ty,
-                func: strcmp,
-                blocks: Vec::new(),
-            },
-        );
+        let string = LLVMPointerType(LLVMInt8Type(), 0);
+        let mut checked = [LLVMInt64Type(), LLVMInt1Type()];
+        let checked = LLVMStructType(checked.as_mut_ptr(), 2, 0);
+        let i64_pair = || vec![LLVMInt64Type(), LLVMInt64Type()];
+        let c_functions = [
+            ("printf", LLVMInt32Type(), vec![], true),
+            ("strcmp", LLVMInt32Type(), vec![string, string], false),
+            ("strstr", string, vec![string, string], false),
+            ("malloc", string, vec![LLVMInt64Type()], false),
+            ("strlen", LLVMInt64Type(), vec![string], false),
+            ("strdup", string, vec![string], false),
+            (
+                "snprintf",
+                LLVMInt32Type(),
+                vec![string, LLVMInt64Type()],
+                true,
+            ),
+            (
+                "memset",
+                string,
+                vec![string, LLVMInt32Type(), LLVMInt64Type()],
+                false,
+            ),
+            (
+                "memmove",
+                string,
+                vec![string, string, LLVMInt64Type()],
+                false,
+            ),
+            (
+                "memcmp",
+                LLVMInt32Type(),
+                vec![string, string, LLVMInt64Type()],
+                false,
+            ),
+            ("realloc", string, vec![string, LLVMInt64Type()], false),
+            ("exit", LLVMVoidType(), vec![LLVMInt32Type()], false),
+            ("free", LLVMVoidType(), vec![string], false),
+            (
+                "qsort",
+                LLVMVoidType(),
+                vec![string, LLVMInt64Type(), LLVMInt64Type(), string],
+                false,
+            ),
+            // Not C, but declared the same way: the result and whether it overflowed.
+            ("llvm.sadd.with.overflow.i64", checked, i64_pair(), false),
+            ("llvm.ssub.with.overflow.i64", checked, i64_pair(), false),
+            ("llvm.smul.with.overflow.i64", checked, i64_pair(), false),
+        ];
+        for (name, ret, mut params, variadic) in c_functions {
+            let ty = LLVMFunctionType(
+                ret,
+                params.as_mut_ptr(),
+                params.len() as u32,
+                variadic as i32,
+            );
+            let func = LLVMAddFunction(main_module, cstr!(name), ty);
+            self.functions.insert(
+                format!("main-{name}"),
+                Function {
+                    entry: None,
+                    ret: None,
+                    ty,
+                    func,
+                    blocks: Vec::new(),
+                    parameters: Vec::new(),
+                },
+            );
+        }
 
+        self.modules.insert(String::from("main"), main_module);
         self.cur_module = Some(main_module);
     }
 
-    pub unsafe fn visit_block(
-        &mut self,
-        func: &Function,
-        specific_bb: Option<LLVMBasicBlockRef>,
-        block: Vec<Box<Stmt>>,
-    ) {
+    /// Switches to the LLVM module of the Cobalt module `name`, creating it the first time.
+    pub unsafe fn enter_module(&mut self, name: &str) {
+        let module = *self
+            .modules
+            .entry(name.to_string())
+            .or_insert_with(|| LLVMModuleCreateWithName(cstr!(name.as_bytes())));
+        self.cur_module = Some(module);
+        self.cur_module_name = name.to_string();
+    }
+
+    /// The name of the symbol `name` gets in LLVM. Everything outside of `main` is prefixed with
+    /// its module so modules can reuse names.
+    pub fn symbol_name(&self, name: &str) -> String {
+        if self.cur_module_name == "main" {
+            return name.to_string();
+        }
+        format!("{}.{}", self.cur_module_name, name)
+    }
+
+    /// Lowers every statement in `block` at the builder's current position. Statements that
+    /// branch leave the builder at the end of the block where control continues.
+    pub unsafe fn visit_block(&mut self, func: &Function, block: Vec<Box<Stmt>>) {
         let mut peekable = block.into_iter().map(|e| *e).peekable();
         while let Some(stmt) = peekable.peek() {
             match stmt {
                 Stmt::Condition(cond) => {
-                    let Expr::BinaryOp(inner_cond) = *cond.clone().condition else {
-                        panic!("Incorrect expr type");
-                    };
+                    let condition = self.visit_condition(*cond.condition.clone());
 
-                    // TODO: handle other expression types here.
-                    let (Expr::Literal(LiteralExpr::String(left)), Expr::Literal(LiteralExpr::String(right))) =
-                        (*inner_cond.l, *inner_cond.r) else {
-                            panic!("Incorrect left and right operands.");
-                        };
-
-                    // I AM VERY AWARE THIS IS BAD ... it will be fixed soon:tm:
-                    let left = if let Some(var) = self.variables.get(&left) {
-                        var.ptr
-                    } else {
-                        let mut value = left.to_owned();
-                        value.push('\0');
-
-                        let alloc = LLVMBuildAlloca(
-                            self.builder,
-                            LLVMArrayType(
-                                LLVMInt8Type(),
-                                std::mem::size_of_val(value.as_bytes()) as u32,
-                            ),
-                            cstr!(""),
-                        );
-                        let val = LLVMConstString(
-                            value.as_bytes().as_ptr() as *const i8,
-                            std::mem::size_of_val(value.as_bytes()) as u32,
-                            1,
-                        );
-                        LLVMBuildStore(self.builder, val, alloc);
-                        alloc
-                    };
-
-                    let right = if let Some(var) = self.variables.get(&right) {
-                        var.ptr
-                    } else {
-                        let mut value = right.to_owned();
-                        value.push('\0');
-                        let ty = LLVMArrayType(
-                            LLVMInt8Type(),
-                            std::mem::size_of_val(value.as_bytes()) as u32,
-                        );
-                        let alloc = LLVMBuildAlloca(self.builder, ty, cstr!(""));
-                        let val = LLVMConstString(
-                            value.as_bytes().as_ptr() as *const i8,
-                            std::mem::size_of_val(value.as_bytes()) as u32,
-                            1,
-                        );
-                        LLVMBuildStore(self.builder, val, alloc);
-                        alloc
-                    };
+                    let then = LLVMAppendBasicBlock(func.func, cstr!("condition"));
+                    let merge = LLVMAppendBasicBlock(func.func, cstr!("merge"));
+                    LLVMBuildCondBr(self.builder, condition, then, merge);
 
-                    //let icmp = LLVMBuildICmp(self.builder, op_ty, left, right, cstr!("if-cond"));
-                    if let Some(strcmp) = self.functions.get("main-strcmp") {
-                        let mut arguments = vec![
-                            LLVMBuildBitCast(
-                                self.builder,
-                                left,
-                                LLVMPointerType(LLVMInt8Type(), 0),
-                                cstr!(""),
-                            ),
-                            LLVMBuildBitCast(
-                                self.builder,
-                                right,
-                                LLVMPointerType(LLVMInt8Type(), 0),
-                                cstr!(""),
-                            ),
-                        ];
-
-                        let strcmp_call = LLVMBuildCall2(
-                            self.builder,
-                            strcmp.ty,
-                            strcmp.func,
-                            arguments.as_mut_ptr(),
-                            arguments.len() as u32,
-                            cstr!("".as_bytes()),
-                        );
-
-                        let zero = LLVMBuildAlloca(self.builder, LLVMInt1Type(), cstr!(""));
-                        LLVMBuildStore(self.builder, LLVMConstInt(LLVMInt1Type(), 0, 1), zero);
-
-                        let zero = LLVMBuildLoad2(self.builder, LLVMInt1Type(), zero, cstr!(""));
-
-                        let icmp = LLVMBuildICmp(
-                            self.builder,
-                            LLVMIntPredicate::LLVMIntEQ,
-                            strcmp_call,
-                            zero,
-                            cstr!(""),
-                        );
-
-                        let then = LLVMAppendBasicBlock(func.func, cstr!("condition"));
-                        let r#else = LLVMAppendBasicBlock(func.func, cstr!("else"));
-
-                        LLVMPositionBuilderAtEnd(self.builder, r#else);
-
-                        LLVMBuildRetVoid(self.builder);
+                    LLVMPositionBuilderAtEnd(self.builder, then);
+                    if let Stmt::Block(block) = *cond.clone().then {
+                        self.visit_block(func, block)
+                    }
+                    self.build_br_if_open(merge);
+                    LLVMPositionBuilderAtEnd(self.builder, merge);
+                }
+                Stmt::Expr(Expr::Call(call)) => self.visit_call(call.clone()),
+                Stmt::Variable { .. } => self.visit_var(stmt.to_owned()),
+                Stmt::Constant(constant) => self.visit_constant(constant.clone()),
+                Stmt::Compute(compute) => {
+                    let handler = compute.on_size_error.clone();
+                    let size_error = self.visit_compute(compute.clone());
+                    if let (Some(size_error), Some(handler)) = (size_error, handler) {
+                        let then = LLVMAppendBasicBlock(func.func, cstr!("size.error"));
+                        let merge = LLVMAppendBasicBlock(func.func, cstr!("merge"));
+                        LLVMBuildCondBr(self.builder, size_error, then, merge);
 
                         LLVMPositionBuilderAtEnd(self.builder, then);
-
-                        if let Stmt::Block(block) = *cond.clone().then {
-                            self.visit_block(func, Some(then), block)
+                        if let Stmt::Block(block) = *handler {
+                            self.visit_block(func, block)
                         }
-                        LLVMBuildRetVoid(self.builder);
-                        LLVMPositionBuilderAtEnd(
-                            self.builder,
-                            specific_bb.unwrap_or(func.entry.unwrap()),
-                        );
-                        LLVMBuildCondBr(self.builder, icmp, then, r#else);
+                        self.build_br_if_open(merge);
+                        LLVMPositionBuilderAtEnd(self.builder, merge);
                     }
                 }
-                Stmt::Expr(expr) => {
-                    match expr {
-                        Expr::Call(func_call) => {
-                            let Expr::Literal(LiteralExpr::String(name)) = *func_call.func.clone() else {
-                                panic!("Expected string literal");
-                            };
-                            let Some(function) = self.functions.get(&format!("main-{name}")) else {
-                            panic!("Function {name} not defined.");
-                        };
-                            LLVMPositionBuilderAtEnd(
-                                self.builder,
-                                specific_bb.unwrap_or(func.entry.unwrap()),
-                            );
-
-                            let mut arguments: Vec<LLVMValueRef> =
-                                if let Some(args) = &func_call.args {
-                                    args.clone()
-                                .into_iter()
-                                .map(|e| match *e {
-                                    Expr::Literal(lit) => {
-                                        let LiteralExpr::String(name) = lit;
-                                        let Some(variable_ptr) = self.variables.get(&name) else {
-                                            panic!("Undefined var {:?}.", name);
-                                        };
-                                        variable_ptr.ptr
-                                        // doesn't like to load stuff?
-                                        // LLVMBuildLoad2(self.builder, variable_ptr.ty, variable_ptr.ptr, cstr!("".as_bytes()))
-                                    }
-                                    _ => unimplemented!(),
-                                })
-                                .collect::<Vec<LLVMValueRef>>()
-                                } else {
-                                    Vec::new()
-                                };
-
-                            LLVMBuildCall2(
-                                self.builder,
-                                function.ty,
-                                function.func,
-                                arguments.as_mut_ptr(),
-                                arguments.len() as u32,
-                                cstr!("".as_bytes()),
-                            );
-                        }
-                        _ => {}
-                    }
+                Stmt::Move(store) => self.visit_move(store.clone()),
+                Stmt::Append(store) => self.visit_append(store.clone()),
+                Stmt::ForEach(each) => self.visit_for_each(func, each.clone()),
+                Stmt::Sort(sort) => self.visit_sort(sort.clone()),
+                Stmt::Search(search) => self.visit_search(search.clone()),
+                Stmt::Evaluate(evaluate) => self.visit_evaluate(func, evaluate.clone()),
+                Stmt::Display { exprs, .. } => {
+                    self.visit_display(exprs.clone());
+                }
+                // The values of a choice are part of the types that use it.
+                Stmt::Choice(_) => {}
+                Stmt::Function { .. } | Stmt::Module { .. } => {
+                    unreachable!("Definitions inside a block are rejected by the parser.")
+                }
+                Stmt::Block(_) | Stmt::Expr(_) => {
+                    unreachable!("The parser only makes blocks of bodies and statements of calls.")
                 }
-                Stmt::Variable { name, ty, value } => self.visit_var(
-                    func,
-                    Some(specific_bb.unwrap_or(func.entry.unwrap())),
-                    stmt.to_owned(),
-                ),
-                _ => {}
             };
             peekable.next();
         }
     }
 
-    pub unsafe fn visit_var(
-        &mut self,
-        func: &Function,
-        specific_bb: Option<LLVMBasicBlockRef>,
-        variable: Stmt,
-    ) {
-        if let Stmt::Variable { name, ty, value } = variable {
-            let value = match value {
-                Some(inner) => match inner {
-                    Expr::Literal(literal) => match literal {
-                        LiteralExpr::String(value) => value,
-                    },
-                    _ => todo!(),
-                },
-                None => todo!(),
-            };
-
-            let mut value = value.to_owned();
-            value.push('\0');
+    /// `SET` assigns to the variable the resolver found for it, or defines a new local if this
+    /// is its first `SET`.
+    pub unsafe fn visit_var(&mut self, variable: Stmt) {
+        if let Stmt::Variable {
+            name,
+            ty,
+            value,
+            id: Some(id),
+            ..
+        } = variable
+        {
+            if let Some((ptr, kind)) = self
+                .variables
+                .get(&id)
+                .map(|var| (var.ptr, var.kind.clone()))
+            {
+                let Some(value) = value else {
+                    panic!("{name:?} is already defined.");
+                };
+                let value = self.const_stored(value, &kind);
+                LLVMBuildStore(self.builder, value, ptr);
+                return;
+            }
 
-            let var_type = match ty {
-                VariableType::String => LLVMArrayType(
-                    LLVMInt8Type(),
-                    std::mem::size_of_val(value.as_bytes()) as u32,
-                ),
+            let var_type = llvm_type(&ty);
+            let alloc = self.entry_alloca(var_type, &name);
+            let value = match value {
+                Some(value) => self.const_stored(value, &ty),
+                None => self.const_default(&ty),
             };
-
-            LLVMPositionBuilderAtEnd(self.builder, specific_bb.unwrap_or(func.entry.unwrap()));
-            let alloc = LLVMBuildAlloca(self.builder, var_type, cstr!(name.as_bytes()));
-            let val = LLVMConstString(
-                value.as_bytes().as_ptr() as *const i8,
-                std::mem::size_of_val(value.as_bytes()) as u32,
-                1,
-            );
-            LLVMBuildStore(self.builder, val, alloc);
+            LLVMBuildStore(self.builder, value, alloc);
             self.variables.insert(
-                name.to_string(),
+                id,
                 Variable {
-                    size: std::mem::size_of_val(value.as_bytes()) as u32,
+                    size: LLVMABISizeOfType(self.target_data(), var_type) as u32,
                     ptr: alloc,
                     ty: var_type,
+                    kind: ty,
                 },
             );
         }
     }
 
-    pub unsafe fn visit_fn(&mut self, func: Stmt) {
+    /// The literal value of a `SET` as it is stored in a variable of type `ty`, without emitting
+    /// any instructions so it can also initialize a global.
+    pub unsafe fn const_stored(&mut self, value: Expr, ty: &VariableType) -> LLVMValueRef {
+        if let Expr::Literal(LiteralExpr::Figurative(figurative)) = value {
+            return self.const_figurative(figurative, ty);
+        }
+        if let Expr::Function(function) = value {
+            return self.function_value(function).0;
+        }
+        match ty {
+            VariableType::Fixed { scale, .. } => self.const_fixed(&value, *scale),
+            VariableType::Text { length } => self.const_text(&value, *length),
+            VariableType::Choice { .. } => self.const_choice(&value, ty),
+            _ => match self.const_value(value) {
+                (value, VariableType::Integer) if *ty == VariableType::Decimal => {
+                    LLVMConstSIToFP(value, LLVMDoubleType())
+                }
+                (value, value_ty) if value_ty == *ty => value,
+                (_, value_ty) => panic!("Cannot store a {value_ty:?} in a {ty:?}."),
+            },
+        }
+    }
+
+    /// A `SET` directly inside of a module becomes a global that every function in the module
+    /// can read and update.
+    pub unsafe fn visit_global_var(&mut self, variable: Stmt) {
+        let (
+            Some(current_module),
+            Stmt::Variable {
+                name,
+                ty,
+                value,
+                visibility,
+                id: Some(id),
+                ..
+            },
+        ) = (self.cur_module, variable)
+        else {
+            return;
+        };
+        let var_type = llvm_type(&ty);
+        let global = LLVMAddGlobal(
+            current_module,
+            var_type,
+            cstr!(self.symbol_name(&name).as_bytes()),
+        );
+        let value = match value {
+            Some(value) => self.const_stored(value, &ty),
+            None => self.const_default(&ty),
+        };
+        LLVMSetInitializer(global, value);
+        LLVMSetLinkage(global, linkage(visibility));
+        self.variables.insert(
+            id,
+            Variable {
+                size: LLVMABISizeOfType(self.target_data(), var_type) as u32,
+                ptr: global,
+                ty: var_type,
+                kind: ty,
+            },
+        );
+    }
+
+    /// A constant becomes a read-only global of the module it was defined in. Its uses were
+    /// already replaced by its value, so nothing else can see it.
+    pub unsafe fn visit_constant(&mut self, constant: Constant) {
+        let (Some(current_module), Some(id)) = (self.cur_module, constant.id) else {
+            return;
+        };
+        let (value, kind) = match constant.value {
+            Expr::Literal(LiteralExpr::Figurative(figurative)) => {
+                let kind = figurative.default_type();
+                (self.const_figurative(figurative, &kind), kind)
+            }
+            value => self.const_value(value),
+        };
+        let var_type = llvm_type(&kind);
+        let global = LLVMAddGlobal(
+            current_module,
+            var_type,
+            cstr!(self.symbol_name(&constant.name).as_bytes()),
+        );
+        LLVMSetInitializer(global, value);
+        LLVMSetGlobalConstant(global, 1);
+        LLVMSetLinkage(global, LLVMLinkage::LLVMPrivateLinkage);
+        self.variables.insert(
+            id,
+            Variable {
+                size: LLVMABISizeOfType(self.target_data(), var_type) as u32,
+                ptr: global,
+                ty: var_type,
+                kind,
+            },
+        );
+    }
+
+    /// Functions are looked up in the module being compiled first, then in `main`, which also
+    /// holds the C library functions. Returns the function's type and a value that can be called
+    /// from the current LLVM module.
+    pub unsafe fn lookup_fn(&mut self, name: &str) -> Option<(LLVMTypeRef, LLVMValueRef)> {
+        let function = self
+            .functions
+            .get(&format!("{}-{}", self.cur_module_name, name))
+            .or_else(|| self.functions.get(&format!("main-{name}")))?;
+        let (ty, func) = (function.ty, function.func);
+        Some((ty, self.import_fn(ty, func)))
+    }
+
+    /// Looks up a function of a specific module, for `CALL FUNCTION "total" OF MODULE "billing"`.
+    pub unsafe fn lookup_module_fn(
+        &mut self,
+        module: &str,
+        name: &str,
+    ) -> Option<(LLVMTypeRef, LLVMValueRef)> {
+        let function = self.functions.get(&format!("{module}-{name}"))?;
+        let (ty, func) = (function.ty, function.func);
+        Some((ty, self.import_fn(ty, func)))
+    }
+
+    /// Looks up one of the C library functions declared by `setup_main_module`, skipping over
+    /// functions of the current module with the same name.
+    pub unsafe fn lookup_c_fn(&mut self, name: &str) -> Option<(LLVMTypeRef, LLVMValueRef)> {
+        self.lookup_module_fn("main", name)
+    }
+
+    /// Calls one of the C library functions declared by `setup_main_module`.
+    pub unsafe fn call_c_fn(
+        &mut self,
+        name: &str,
+        mut arguments: Vec<LLVMValueRef>,
+    ) -> LLVMValueRef {
+        let Some((ty, func)) = self.lookup_c_fn(name) else {
+            panic!("{name} is not defined.");
+        };
+        LLVMBuildCall2(
+            self.builder,
+            ty,
+            func,
+            arguments.as_mut_ptr(),
+            arguments.len() as u32,
+            cstr!(""),
+        )
+    }
+
+    /// Functions from other LLVM modules have to be declared in the current one before they can
+    /// be called. The declaration is resolved when the modules are linked.
+    pub unsafe fn import_fn(&mut self, ty: LLVMTypeRef, func: LLVMValueRef) -> LLVMValueRef {
+        let module = self.cur_module.unwrap();
+        if LLVMGetGlobalParent(func) == module {
+            return func;
+        }
+        let mut len = 0;
+        let name = LLVMGetValueName2(func, &mut len);
+        let existing = LLVMGetNamedFunction(module, name);
+        if !existing.is_null() {
+            return existing;
+        }
+        LLVMAddFunction(module, name, ty)
+    }
+
+    /// Where the variable `id` is stored, and its type. A module-level variable of another
+    /// module is declared in the current one first, like a function would be.
+    pub unsafe fn variable_ptr(&mut self, id: DefId) -> Option<(LLVMValueRef, VariableType)> {
+        let var = self.variables.get(&id)?;
+        let (ptr, ty, kind) = (var.ptr, var.ty, var.kind.clone());
+        let module = self.cur_module.unwrap();
+        if LLVMIsAGlobalVariable(ptr).is_null() || LLVMGetGlobalParent(ptr) == module {
+            return Some((ptr, kind));
+        }
+        let mut len = 0;
+        let name = LLVMGetValueName2(ptr, &mut len);
+        let existing = LLVMGetNamedGlobal(module, name);
+        if !existing.is_null() {
+            return Some((existing, kind));
+        }
+        Some((LLVMAddGlobal(module, ty, name), kind))
+    }
+
+    pub unsafe fn target_data(&self) -> LLVMTargetDataRef {
+        LLVMGetModuleDataLayout(self.cur_module.unwrap())
+    }
+
+    /// Compiles every top-level item. All functions are declared up front so they can be called
+    /// before their definition, and any statements outside of a function become the body of an
+    /// implicit `main`.
+    pub unsafe fn visit_program(&mut self) {
+        let mut items = Vec::new();
+        while let Some(stmt) = self.advance() {
+            items.push(stmt);
+        }
+
+        // Everything is paired with the module it belongs to, top-level definitions belong to `main`.
+        let mut functions = Vec::new();
+        let mut globals = Vec::new();
+        let mut top_level = Vec::new();
+        for item in items {
+            match item {
+                Stmt::Function { .. } => functions.push((String::from("main"), item)),
+                Stmt::Constant(_) => globals.push((String::from("main"), item)),
+                // The values of a choice are part of the types that use it.
+                Stmt::Choice(_) => {}
+                Stmt::Module {
+                    name: LiteralExpr::String(module),
+                    nodes,
+                    ..
+                } => {
+                    let Stmt::Block(contents) = *nodes else {
+                        panic!("Expected a block?");
+                    };
+                    for content in contents {
+                        match *content {
+                            Stmt::Function { .. } => functions.push((module.clone(), *content)),
+                            Stmt::Variable { .. } | Stmt::Constant(_) => {
+                                globals.push((module.clone(), *content))
+                            }
+                            Stmt::Choice(_) => {}
+                            other => panic!("Only definitions can be in a module, not {other:?}."),
+                        }
+                    }
+                }
+                stmt => top_level.push(Box::new(stmt)),
+            }
+        }
+
+        for (module, function) in &functions {
+            if let Stmt::Function {
+                name: LiteralExpr::String(name),
+                parameters,
+                visibility,
+                ..
+            } = function
+            {
+                let parameters = parameters.iter().map(|p| p.ty.clone()).collect::<Vec<_>>();
+                self.enter_module(module);
+                self.declare_fn(name, &parameters, *visibility);
+            }
+        }
+        for (module, global) in globals {
+            self.enter_module(&module);
+            match global {
+                Stmt::Constant(constant) => self.visit_constant(constant),
+                global => self.visit_global_var(global),
+            }
+        }
+        for (module, function) in functions {
+            self.enter_module(&module);
+            self.visit_fn(function);
+        }
+        self.enter_module("main");
+
+        if !top_level.is_empty() {
+            self.declare_fn("main", &[], Visibility::Public);
+            self.visit_fn(Stmt::Function {
+                name: LiteralExpr::String("main".to_string()),
+                parameters: Vec::new(),
+                nodes: Box::new(Stmt::Block(top_level)),
+                visibility: Visibility::Public,
+                id: None,
+                span: 0..0,
+            });
+        }
+    }
+
+    /// Adds the function to the module without a body. `main` returns an `i32` exit code, every
+    /// other function returns nothing.
+    pub unsafe fn declare_fn(
+        &mut self,
+        name: &str,
+        parameters: &[VariableType],
+        visibility: Visibility,
+    ) {
         if let Some(current_module) = self.cur_module {
-            let Stmt::Function { name, nodes } = func else {
-                panic!("Not a function");
+            let ty = if name == "main" && self.cur_module_name == "main" {
+                LLVMFunctionType(LLVMInt32Type(), [].as_mut_ptr(), 0, 0)
+            } else {
+                func::function_type(parameters)
             };
-            let LiteralExpr::String(name) = name;
-            let main_ty = LLVMFunctionType(LLVMVoidType(), [].as_mut_ptr(), 0, 0);
-            let main_func = LLVMAddFunction(current_module, cstr!(name.as_bytes()), main_ty);
-
-            let entry = LLVMAppendBasicBlock(main_func, cstr!("entry"));
-            // let ret = LLVMAppendBasicBlock(main_func, cstr!("return"));
-
-            let mut function = Function {
-                entry: Some(entry),
-                ret: None,
-                ty: main_ty,
-                func: main_func,
-                blocks: Vec::new(),
+            let func =
+                LLVMAddFunction(current_module, cstr!(self.symbol_name(name).as_bytes()), ty);
+            LLVMSetLinkage(func, linkage(visibility));
+            self.functions.insert(
+                format!("{}-{name}", self.cur_module_name),
+                Function {
+                    entry: None,
+                    ret: None,
+                    ty,
+                    func,
+                    blocks: Vec::new(),
+                    parameters: parameters.to_vec(),
+                },
+            );
+        }
+    }
+
+    pub unsafe fn visit_fn(&mut self, func: Stmt) {
+        let Stmt::Function {
+            name,
+            parameters,
+            nodes,
+            visibility,
+            ..
+        } = func
+        else {
+            panic!("Not a function");
+        };
+        let LiteralExpr::String(name) = name else {
+            panic!("Expected a function name, not {name:?}.");
+        };
+        let key = format!("{}-{name}", self.cur_module_name);
+        if !self.functions.contains_key(&key) {
+            let parameters = parameters.iter().map(|p| p.ty.clone()).collect::<Vec<_>>();
+            self.declare_fn(&name, &parameters, visibility);
+        }
+        let Some(declared) = self.functions.get(&key) else {
+            return;
+        };
+
+        let entry = LLVMAppendBasicBlock(declared.func, cstr!("entry"));
+        // let ret = LLVMAppendBasicBlock(main_func, cstr!("return"));
+        LLVMPositionBuilderAtEnd(self.builder, entry);
+
+        let function = Function {
+            entry: Some(entry),
+            ret: None,
+            ty: declared.ty,
+            func: declared.func,
+            blocks: Vec::new(),
+            parameters: declared.parameters.clone(),
+        };
+
+        // Each argument is a local of the function, so it can be changed like any other.
+        for (index, parameter) in parameters.into_iter().enumerate() {
+            let Some(id) = parameter.id else {
+                continue;
             };
+            let var_type = llvm_type(&parameter.ty);
+            let alloc = self.entry_alloca(var_type, &parameter.name);
+            LLVMBuildStore(
+                self.builder,
+                LLVMGetParam(function.func, index as u32),
+                alloc,
+            );
+            self.variables.insert(
+                id,
+                Variable {
+                    size: LLVMABISizeOfType(self.target_data(), var_type) as u32,
+                    ptr: alloc,
+                    ty: var_type,
+                    kind: parameter.ty,
+                },
+            );
+        }
 
-            match *nodes {
-                Stmt::Block(stmts) => self.visit_block(&function, None, stmts),
-                _ => panic!("Expected a block?"),
-            }
+        match *nodes {
+            Stmt::Block(stmts) => self.visit_block(&function, stmts),
+            _ => panic!("Expected a block?"),
+        }
 
-            LLVMPositionBuilderAtEnd(self.builder, function.entry.unwrap());
-            if name == "main" {
+        if LLVMGetBasicBlockTerminator(LLVMGetInsertBlock(self.builder)).is_null() {
+            if LLVMGetReturnType(function.ty) == LLVMInt32Type() {
+                LLVMBuildRet(self.builder, LLVMConstInt(LLVMInt32Type(), 0, 0));
+            } else {
                 LLVMBuildRetVoid(self.builder);
             }
+        }
 
-            self.functions.insert(format!("main-{}", name), function);
+        self.functions.insert(key, function);
+    }
+
+    /// Branches to `to` unless the block the builder is in already ends in a terminator.
+    pub unsafe fn build_br_if_open(&mut self, to: LLVMBasicBlockRef) {
+        if LLVMGetBasicBlockTerminator(LLVMGetInsertBlock(self.builder)).is_null() {
+            LLVMBuildBr(self.builder, to);
         }
     }
 
+    /// Allocates stack space in the entry block of the current function, so a local defined in
+    /// a loop is only allocated once.
+    pub unsafe fn entry_alloca(&mut self, ty: LLVMTypeRef, name: &str) -> LLVMValueRef {
+        let func = LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.builder));
+        let entry = LLVMGetEntryBasicBlock(func);
+        let builder = LLVMCreateBuilder();
+        let first = LLVMGetFirstInstruction(entry);
+        if first.is_null() {
+            LLVMPositionBuilderAtEnd(builder, entry);
+        } else {
+            LLVMPositionBuilderBefore(builder, first);
+        }
+        let alloca = LLVMBuildAlloca(builder, ty, cstr!(name));
+        LLVMDisposeBuilder(builder);
+        alloca
+    }
+
     pub fn advance(&mut self) -> Option<Stmt> {
         self.idx += 1;
         self.stmts.next()
     }
 
-    pub unsafe fn verify_and_dump(&self) {
-        if let Some(current_module) = self.cur_module {
-            let pm = LLVMCreatePassManager();
-            let pmb = LLVMPassManagerBuilderCreate();
-            LLVMPassManagerBuilderSetOptLevel(
-                pmb,
-                LLVMCodeGenOptLevel::LLVMCodeGenLevelAggressive as u32,
-            );
-            LLVMPassManagerBuilderPopulateFunctionPassManager(pmb, pm);
-            LLVMPassManagerBuilderPopulateModulePassManager(pmb, pm);
-
+    /// Verifies and optimizes every module on its own, links them into `main` and writes the
+    /// result to `cbt.ll`. From `-O2` upwards the linked program also goes through the LTO
+    /// pipeline, with everything but `main` internalized so it can be inlined across modules.
+    pub unsafe fn verify_and_dump(&mut self) {
+        let Some(main_module) = self.modules.remove("main") else {
+            return;
+        };
+        let pmb = LLVMPassManagerBuilderCreate();
+        LLVMPassManagerBuilderSetOptLevel(pmb, self.opt_level);
+
+        let mut modules = self.modules.drain().collect::<Vec<_>>();
+        modules.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let compiled = std::iter::once(main_module)
+            .chain(modules.iter().map(|(_, m)| *m))
+            .collect::<Vec<_>>();
+        if let Some(runtime) = table::runtime(&compiled) {
+            modules.push((String::from("cobalt.table"), runtime));
+        }
+        for module in std::iter::once(main_module).chain(modules.iter().map(|(_, m)| *m)) {
             LLVMVerifyModule(
-                current_module,
+                module,
                 LLVMVerifierFailureAction::LLVMAbortProcessAction,
                 std::ptr::null_mut(),
             );
+            if self.opt_level > 0 {
+                let pm = LLVMCreatePassManager();
+                LLVMPassManagerBuilderPopulateFunctionPassManager(pmb, pm);
+                LLVMPassManagerBuilderPopulateModulePassManager(pmb, pm);
+                LLVMRunPassManager(pm, module);
+                LLVMDisposePassManager(pm);
+            }
+        }
 
-            LLVMRunPassManager(pm, current_module);
+        for (name, module) in modules {
+            if LLVMLinkModules2(main_module, module) != 0 {
+                panic!("Failed to link the module {name:?} into main.");
+            }
+        }
 
-            LLVMPrintModuleToFile(current_module, cstr!("cbt.ll"), null_mut());
+        if self.opt_level >= 2 {
+            let mut func = LLVMGetFirstFunction(main_module);
+            while !func.is_null() {
+                let mut len = 0;
+                let name =
+                    std::slice::from_raw_parts(LLVMGetValueName2(func, &mut len) as *const u8, len);
+                if LLVMIsDeclaration(func) == 0 && name != b"main" {
+                    LLVMSetLinkage(func, LLVMLinkage::LLVMInternalLinkage);
+                }
+                func = LLVMGetNextFunction(func);
+            }
+            let mut global = LLVMGetFirstGlobal(main_module);
+            while !global.is_null() {
+                if LLVMIsDeclaration(global) == 0 {
+                    LLVMSetLinkage(global, LLVMLinkage::LLVMInternalLinkage);
+                }
+                global = LLVMGetNextGlobal(global);
+            }
 
-            LLVMPassManagerBuilderDispose(pmb);
-            LLVMDisposeBuilder(self.builder);
+            let pm = LLVMCreatePassManager();
+            LLVMPassManagerBuilderPopulateLTOPassManager(pmb, pm, 1, 1);
+            LLVMRunPassManager(pm, main_module);
+            LLVMDisposePassManager(pm);
         }
+
+        LLVMVerifyModule(
+            main_module,
+            LLVMVerifierFailureAction::LLVMAbortProcessAction,
+            std::ptr::null_mut(),
+        );
+        LLVMPrintModuleToFile(main_module, cstr!("cbt.ll"), null_mut());
+
+        LLVMPassManagerBuilderDispose(pmb);
+        LLVMDisposeBuilder(self.builder);
     }
 }
@@ -0,0 +1,116 @@
+//! Choices, stored as the number of their value in an `i8`. `DISPLAY` looks the name up in a
+//! constant array of the values, one per choice in every module that displays it.
+
+use llvm_sys::{
+    core::{
+        LLVMAddGlobal, LLVMAppendBasicBlock, LLVMArrayType, LLVMBuildCondBr, LLVMBuildInBoundsGEP2,
+        LLVMBuildLoad2, LLVMBuildOr, LLVMBuildZExt, LLVMConstArray, LLVMConstInt,
+        LLVMGetNamedGlobal, LLVMInt64Type, LLVMInt8Type, LLVMPointerType, LLVMPositionBuilderAtEnd,
+        LLVMSetGlobalConstant, LLVMSetInitializer, LLVMSetLinkage,
+    },
+    prelude::LLVMValueRef,
+    LLVMLinkage,
+};
+
+use crate::node::{BinaryOperators, Evaluate, Expr, LiteralExpr, Stmt, VariableType};
+
+use super::{func::Function, CodeGen};
+
+impl<T> CodeGen<T>
+where
+    T: Iterator<Item = Stmt>,
+{
+    /// The value of `choice` written as the string `value`, which the type checker made sure
+    /// is one of its values.
+    pub unsafe fn const_choice(&mut self, value: &Expr, choice: &VariableType) -> LLVMValueRef {
+        let (Expr::Literal(LiteralExpr::String(value)), VariableType::Choice { values, .. }) =
+            (value, choice)
+        else {
+            panic!("Expected a value of a choice, not {value:?}.");
+        };
+        let Some(number) = values.iter().position(|other| other == value) else {
+            panic!("{value:?} is not a value of {choice:?}.");
+        };
+        LLVMConstInt(LLVMInt8Type(), number as u64, 0)
+    }
+
+    /// The name of the value of `choice` that `value` is the number of, as a string.
+    pub unsafe fn choice_name(
+        &mut self,
+        value: LLVMValueRef,
+        choice: &VariableType,
+    ) -> LLVMValueRef {
+        let VariableType::Choice { name, values } = choice else {
+            panic!("Expected a choice, not {choice:?}.");
+        };
+        let string = LLVMPointerType(LLVMInt8Type(), 0);
+        let names_ty = LLVMArrayType(string, values.len() as u32);
+        let module = self.cur_module.unwrap();
+        let global_name = format!("cobalt.choice.{name}");
+        let mut names = LLVMGetNamedGlobal(module, cstr!(global_name.as_bytes()));
+        if names.is_null() {
+            let mut strings = values
+                .iter()
+                .map(|value| self.const_string(value.as_bytes()))
+                .collect::<Vec<_>>();
+            names = LLVMAddGlobal(module, names_ty, cstr!(global_name.as_bytes()));
+            LLVMSetInitializer(
+                names,
+                LLVMConstArray(string, strings.as_mut_ptr(), strings.len() as u32),
+            );
+            LLVMSetGlobalConstant(names, 1);
+            LLVMSetLinkage(names, LLVMLinkage::LLVMPrivateLinkage);
+        }
+
+        let number = LLVMBuildZExt(self.builder, value, LLVMInt64Type(), cstr!(""));
+        let mut indices = [LLVMConstInt(LLVMInt64Type(), 0, 0), number];
+        let ptr = LLVMBuildInBoundsGEP2(
+            self.builder,
+            names_ty,
+            names,
+            indices.as_mut_ptr(),
+            2,
+            cstr!(""),
+        );
+        LLVMBuildLoad2(self.builder, string, ptr, cstr!(""))
+    }
+
+    /// The subject is evaluated once and compared with the values of each `WHEN` in turn. The
+    /// first that matches runs and the others are skipped, like `IF` blocks they all meet after
+    /// the `EVALUATE`.
+    pub unsafe fn visit_evaluate(&mut self, func: &Function, evaluate: Evaluate) {
+        let subject = self.visit_expr(evaluate.subject);
+        let end = LLVMAppendBasicBlock(func.func, cstr!("evaluate.end"));
+        for when in evaluate.branches {
+            let mut matched = None;
+            for value in when.values {
+                let value = self.visit_operand(value, &subject.1);
+                let equal = self.compare(BinaryOperators::EqualTo, subject.clone(), value);
+                matched = Some(match matched {
+                    Some(any) => LLVMBuildOr(self.builder, any, equal, cstr!("")),
+                    None => equal,
+                });
+            }
+            let Some(matched) = matched else {
+                panic!("A WHEN has at least one value.");
+            };
+            let then = LLVMAppendBasicBlock(func.func, cstr!("when"));
+            let next = LLVMAppendBasicBlock(func.func, cstr!("when.next"));
+            LLVMBuildCondBr(self.builder, matched, then, next);
+
+            LLVMPositionBuilderAtEnd(self.builder, then);
+            if let Stmt::Block(block) = *when.body {
+                self.visit_block(func, block);
+            }
+            self.build_br_if_open(end);
+            LLVMPositionBuilderAtEnd(self.builder, next);
+        }
+        if let Some(other) = evaluate.other {
+            if let Stmt::Block(block) = *other {
+                self.visit_block(func, block);
+            }
+        }
+        self.build_br_if_open(end);
+        LLVMPositionBuilderAtEnd(self.builder, end);
+    }
+}
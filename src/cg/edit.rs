@@ -0,0 +1,235 @@
+use llvm_sys::{
+    core::{
+        LLVMBuildAdd, LLVMBuildCall2, LLVMBuildGEP2, LLVMBuildICmp, LLVMBuildNeg, LLVMBuildSDiv,
+        LLVMBuildSRem, LLVMBuildSelect, LLVMBuildStore, LLVMBuildTrunc, LLVMConstInt, LLVMInt1Type,
+        LLVMInt64Type, LLVMInt8Type,
+    },
+    prelude::LLVMValueRef,
+    LLVMIntPredicate,
+};
+
+use crate::{
+    node::{Edited, Stmt},
+    picture::Symbol,
+};
+
+use super::CodeGen;
+
+/// A character of the edited string, before floating symbols have found their place.
+struct Position {
+    /// What is shown when the position isn't among the suppressed leading zeros.
+    content: LLVMValueRef,
+    /// Set while only zeros are to the left of the position, `None` if it is never suppressed.
+    blank: Option<LLVMValueRef>,
+    /// What replaces a suppressed position, unless a floating symbol lands on it.
+    fill: u8,
+    /// The symbol a floating run puts in front of the first significant digit.
+    floating: Option<LLVMValueRef>,
+}
+
+impl<T> CodeGen<T>
+where
+    T: Iterator<Item = Stmt>,
+{
+    /// Edits a number into a new string as its picture says. The code is generated for the
+    /// picture at hand, one character at a time, rather than interpreting the mask at runtime.
+    /// Digits that don't fit in the picture are dropped from the front, like COBOL does.
+    pub unsafe fn visit_edited(&mut self, edited: Edited) -> LLVMValueRef {
+        let Edited { value, picture, .. } = edited;
+        let (value, ty) = self.visit_expr(*value);
+        let value = self.to_fixed(value, &ty, picture.decimals);
+
+        let zero = self.const_i128(0);
+        let negative = LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntSLT,
+            value,
+            zero,
+            cstr!(""),
+        );
+        let magnitude = LLVMBuildSelect(
+            self.builder,
+            negative,
+            LLVMBuildNeg(self.builder, value, cstr!("")),
+            value,
+            cstr!(""),
+        );
+        // Only needed when every digit is suppressed, as then those after the point are too.
+        let zero_fill = picture.zero_fill();
+        let is_zero = zero_fill.map(|_| {
+            LLVMBuildICmp(
+                self.builder,
+                LLVMIntPredicate::LLVMIntEQ,
+                magnitude,
+                zero,
+                cstr!(""),
+            )
+        });
+        let char = |c: u8| LLVMConstInt(LLVMInt8Type(), c as u64, 0);
+        let pick = |builder, condition, then: u8, otherwise: u8| {
+            LLVMBuildSelect(builder, condition, char(then), char(otherwise), cstr!(""))
+        };
+
+        let mut positions: Vec<Position> = Vec::new();
+        // The blank flag and fill of the last digit that can be suppressed, insertions right of
+        // it are suppressed along with it.
+        let mut zone: Option<(LLVMValueRef, u8, Option<LLVMValueRef>)> = None;
+        let mut after_point = false;
+        for symbol in &picture.symbols {
+            let position = match *symbol {
+                Symbol::Digit { place, fill } => {
+                    let content = self.digit(magnitude, place);
+                    let blank = fill.map(|_| match is_zero {
+                        Some(is_zero) if after_point => is_zero,
+                        _ => self.leading_zero(magnitude, place),
+                    });
+                    zone = blank.zip(fill).map(|(blank, fill)| (blank, fill, None));
+                    Position {
+                        content,
+                        blank,
+                        fill: fill.unwrap_or(b' '),
+                        floating: None,
+                    }
+                }
+                Symbol::Floating { place, symbol } => {
+                    let (content, blank) = match place {
+                        Some(place) => (
+                            self.digit(magnitude, place),
+                            self.leading_zero(magnitude, place),
+                        ),
+                        None => (char(b' '), LLVMConstInt(LLVMInt1Type(), 1, 0)),
+                    };
+                    let symbol = match symbol {
+                        b'+' => pick(self.builder, negative, b'-', b'+'),
+                        b'-' => pick(self.builder, negative, b'-', b' '),
+                        symbol => char(symbol),
+                    };
+                    zone = Some((blank, b' ', Some(symbol)));
+                    Position {
+                        content,
+                        blank: Some(blank),
+                        fill: b' ',
+                        floating: Some(symbol),
+                    }
+                }
+                Symbol::Insert(c) => match zone {
+                    Some((blank, fill, floating)) => Position {
+                        content: char(c),
+                        blank: Some(blank),
+                        fill,
+                        floating,
+                    },
+                    None => fixed(char(c)),
+                },
+                Symbol::Point => {
+                    zone = None;
+                    after_point = true;
+                    fixed(char(b'.'))
+                }
+                Symbol::Currency => fixed(char(b'$')),
+                Symbol::Sign(b'+') => fixed(pick(self.builder, negative, b'-', b'+')),
+                Symbol::Sign(_) => fixed(pick(self.builder, negative, b'-', b' ')),
+                Symbol::Credit(credit) => {
+                    for c in credit.bytes() {
+                        positions.push(fixed(pick(self.builder, negative, c, b' ')));
+                    }
+                    continue;
+                }
+            };
+            positions.push(position);
+        }
+
+        let Some(malloc) = self.lookup_c_fn("malloc") else {
+            panic!("malloc is not defined.");
+        };
+        let mut size = [LLVMConstInt(LLVMInt64Type(), positions.len() as u64 + 1, 0)];
+        let buffer = LLVMBuildCall2(
+            self.builder,
+            malloc.0,
+            malloc.1,
+            size.as_mut_ptr(),
+            1,
+            cstr!("edited"),
+        );
+        for (i, position) in positions.iter().enumerate() {
+            let shown = match (position.blank, position.floating) {
+                (None, _) => position.content,
+                (Some(blank), None) => {
+                    let fill = char(position.fill);
+                    LLVMBuildSelect(self.builder, blank, fill, position.content, cstr!(""))
+                }
+                // A floating symbol goes on the last suppressed position of its run.
+                (Some(blank), Some(symbol)) => {
+                    let next_blank = positions
+                        .get(i + 1)
+                        .filter(|next| next.floating.is_some())
+                        .and_then(|next| next.blank);
+                    let suppressed = match next_blank {
+                        Some(next_blank) => {
+                            LLVMBuildSelect(self.builder, next_blank, char(b' '), symbol, cstr!(""))
+                        }
+                        None => symbol,
+                    };
+                    LLVMBuildSelect(self.builder, blank, suppressed, position.content, cstr!(""))
+                }
+            };
+            // A zero in a picture of only `Z`s is all spaces, like COBOL's `BLANK WHEN ZERO`.
+            let shown = match (zero_fill, is_zero) {
+                (Some(b' '), Some(is_zero)) => {
+                    LLVMBuildSelect(self.builder, is_zero, char(b' '), shown, cstr!(""))
+                }
+                _ => shown,
+            };
+            self.store_char(buffer, i, shown);
+        }
+        self.store_char(buffer, positions.len(), char(0));
+        buffer
+    }
+
+    /// The digit of `magnitude` with value `10^place`, as a character.
+    unsafe fn digit(&mut self, magnitude: LLVMValueRef, place: u32) -> LLVMValueRef {
+        let digit = LLVMBuildSDiv(self.builder, magnitude, self.pow10(place), cstr!(""));
+        let digit = LLVMBuildSRem(self.builder, digit, self.pow10(1), cstr!(""));
+        let digit = LLVMBuildTrunc(self.builder, digit, LLVMInt8Type(), cstr!(""));
+        LLVMBuildAdd(
+            self.builder,
+            digit,
+            LLVMConstInt(LLVMInt8Type(), b'0' as u64, 0),
+            cstr!(""),
+        )
+    }
+
+    /// Whether the digit with value `10^place` and every digit left of it are zero.
+    unsafe fn leading_zero(&mut self, magnitude: LLVMValueRef, place: u32) -> LLVMValueRef {
+        LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntSLT,
+            magnitude,
+            self.pow10(place),
+            cstr!(""),
+        )
+    }
+
+    unsafe fn store_char(&mut self, buffer: LLVMValueRef, index: usize, c: LLVMValueRef) {
+        let mut indices = [LLVMConstInt(LLVMInt64Type(), index as u64, 0)];
+        let ptr = LLVMBuildGEP2(
+            self.builder,
+            LLVMInt8Type(),
+            buffer,
+            indices.as_mut_ptr(),
+            1,
+            cstr!(""),
+        );
+        LLVMBuildStore(self.builder, c, ptr);
+    }
+}
+
+/// A position that is always shown as it is.
+fn fixed(content: LLVMValueRef) -> Position {
+    Position {
+        content,
+        blank: None,
+        fill: b' ',
+        floating: None,
+    }
+}
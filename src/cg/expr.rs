@@ -0,0 +1,713 @@
+use llvm_sys::{
+    core::{
+        LLVMAddGlobal, LLVMAddIncoming, LLVMAppendBasicBlock, LLVMArrayType, LLVMBuildAnd,
+        LLVMBuildBr, LLVMBuildCall2, LLVMBuildCondBr, LLVMBuildExtractValue, LLVMBuildFAdd,
+        LLVMBuildFCmp, LLVMBuildFDiv, LLVMBuildFMul, LLVMBuildFNeg, LLVMBuildFPToSI, LLVMBuildFRem,
+        LLVMBuildFSub, LLVMBuildGlobalStringPtr, LLVMBuildICmp, LLVMBuildIsNotNull, LLVMBuildLoad2,
+        LLVMBuildNeg, LLVMBuildNot, LLVMBuildOr, LLVMBuildPhi, LLVMBuildSDiv, LLVMBuildSExt,
+        LLVMBuildSIToFP, LLVMBuildSRem, LLVMBuildSelect, LLVMBuildStore, LLVMBuildTrunc,
+        LLVMBuildUnreachable, LLVMConstBitCast, LLVMConstFNeg, LLVMConstInt, LLVMConstNeg,
+        LLVMConstReal, LLVMConstString, LLVMDoubleType, LLVMGetBasicBlockParent,
+        LLVMGetInsertBlock, LLVMInt128Type, LLVMInt1Type, LLVMInt32Type, LLVMInt64Type,
+        LLVMInt8Type, LLVMPointerType, LLVMPositionBuilderAtEnd, LLVMSetGlobalConstant,
+        LLVMSetInitializer, LLVMSetLinkage, LLVMSetUnnamedAddress, LLVMTypeOf,
+    },
+    prelude::LLVMValueRef,
+    LLVMIntPredicate, LLVMLinkage, LLVMRealPredicate, LLVMUnnamedAddr,
+};
+
+use crate::node::{
+    Arithmetic, ArithmeticOperators, Binary, BinaryOperators, Compute, Expr, Index, LiteralExpr,
+    Logical, LogicalOperators, Reference, Rounding, Stmt, VariableType, DIVISION_DECIMALS,
+    MAX_DIGITS,
+};
+
+use super::{llvm_type, CodeGen};
+
+impl<T> CodeGen<T>
+where
+    T: Iterator<Item = Stmt>,
+{
+    /// Lowers an expression at the builder's current position. Strings are returned as an `i8*`,
+    /// integers as an `i64`, decimals as a `double` and `NUMBER`s as an `i128`.
+    pub unsafe fn visit_expr(&mut self, expr: Expr) -> (LLVMValueRef, VariableType) {
+        match expr {
+            Expr::Literal(LiteralExpr::String(value)) => (
+                LLVMBuildGlobalStringPtr(self.builder, cstr!(value.as_bytes()), cstr!("")),
+                VariableType::String,
+            ),
+            Expr::Variable(Reference { name, id }) => {
+                let Some((ptr, kind)) = self.variable_ptr(id) else {
+                    panic!("Variable {name:?} was resolved but never defined.");
+                };
+                self.load(ptr, kind)
+            }
+            expr @ (Expr::Member(_) | Expr::Item(_) | Expr::Entry(_)) => {
+                let (ptr, kind) = self.member_ptr(&expr);
+                self.load(ptr, kind)
+            }
+            Expr::Literal(LiteralExpr::Integer(value)) => (
+                LLVMConstInt(LLVMInt64Type(), value as u64, 1),
+                VariableType::Integer,
+            ),
+            Expr::Literal(LiteralExpr::Decimal(value)) => (
+                LLVMConstReal(LLVMDoubleType(), value.parse::<f64>().unwrap()),
+                VariableType::Decimal,
+            ),
+            Expr::Negate(inner) => match self.visit_expr(*inner) {
+                (value, VariableType::Integer) => (
+                    LLVMBuildNeg(self.builder, value, cstr!("")),
+                    VariableType::Integer,
+                ),
+                (value, VariableType::Decimal) => (
+                    LLVMBuildFNeg(self.builder, value, cstr!("")),
+                    VariableType::Decimal,
+                ),
+                (value, ty @ VariableType::Fixed { .. }) => {
+                    (LLVMBuildNeg(self.builder, value, cstr!("")), ty)
+                }
+                (_, ty) => panic!("Cannot negate a {ty:?}."),
+            },
+            Expr::Arithmetic(arithmetic) => self.visit_arithmetic(arithmetic),
+            Expr::Literal(LiteralExpr::Boolean(_)) => self.const_value(expr),
+            Expr::Literal(LiteralExpr::Figurative(figurative)) => {
+                self.figurative_value(figurative, &figurative.default_type())
+            }
+            Expr::Edited(edited) => (self.visit_edited(edited), VariableType::String),
+            Expr::BinaryOp(_) | Expr::Logical(_) | Expr::Not(_) => {
+                (self.visit_condition(expr), VariableType::Boolean)
+            }
+            Expr::Function(function) => self.function_value(function),
+            Expr::Concat(concat) => (self.visit_concat(concat), VariableType::String),
+            Expr::Characters(characters) => {
+                (self.visit_characters(characters), VariableType::String)
+            }
+            Expr::Length(length) => {
+                let (text, ty) = self.visit_expr(*length.text);
+                (self.text_length(text, &ty), VariableType::Integer)
+            }
+            Expr::Call(_) => unreachable!("A call used as a value is rejected by typeck."),
+            Expr::ModuleVariable(_) => {
+                unreachable!("A module variable is rewritten to a reference by the resolver.")
+            }
+            Expr::Interpolation(_) => {
+                unreachable!("An interpolation is rewritten to a concatenation by the resolver.")
+            }
+        }
+    }
+
+    /// Reads the value of type `kind` stored at `ptr`. A `TEXT` is read as a string, and
+    /// records, lists and tables are used through a pointer to them.
+    pub unsafe fn load(
+        &mut self,
+        ptr: LLVMValueRef,
+        kind: VariableType,
+    ) -> (LLVMValueRef, VariableType) {
+        match kind {
+            VariableType::Text { length } => (self.text_value(ptr, length), kind),
+            VariableType::Record(_) | VariableType::List { .. } | VariableType::Table { .. } => {
+                (ptr, kind)
+            }
+            VariableType::Fixed { .. } => {
+                let value = LLVMBuildLoad2(self.builder, llvm_type(&kind), ptr, cstr!(""));
+                let value = LLVMBuildSExt(self.builder, value, LLVMInt128Type(), cstr!(""));
+                (value, kind)
+            }
+            kind => (
+                LLVMBuildLoad2(self.builder, llvm_type(&kind), ptr, cstr!("")),
+                kind,
+            ),
+        }
+    }
+
+    /// Evaluates a literal, or a negated number, without emitting any instructions so the result
+    /// can also be used to initialize a global.
+    pub unsafe fn const_value(&mut self, expr: Expr) -> (LLVMValueRef, VariableType) {
+        match expr {
+            Expr::Literal(LiteralExpr::String(value)) => {
+                (self.const_string(value.as_bytes()), VariableType::String)
+            }
+            Expr::Literal(LiteralExpr::Integer(value)) => (
+                LLVMConstInt(LLVMInt64Type(), value as u64, 1),
+                VariableType::Integer,
+            ),
+            Expr::Literal(LiteralExpr::Decimal(value)) => (
+                LLVMConstReal(LLVMDoubleType(), value.parse::<f64>().unwrap()),
+                VariableType::Decimal,
+            ),
+            Expr::Negate(inner) => match self.const_value(*inner) {
+                (value, VariableType::Integer) => (LLVMConstNeg(value), VariableType::Integer),
+                (value, VariableType::Decimal) => (LLVMConstFNeg(value), VariableType::Decimal),
+                (_, ty) => panic!("Cannot negate a {ty:?}."),
+            },
+            Expr::Literal(LiteralExpr::Boolean(value)) => (
+                LLVMConstInt(LLVMInt1Type(), value as u64, 0),
+                VariableType::Boolean,
+            ),
+            _ => panic!("Expected a literal, not {expr:?}."),
+        }
+    }
+
+    /// Places the characters of `value` in a private global and returns an `i8*` to them.
+    pub unsafe fn const_string(&mut self, value: &[u8]) -> LLVMValueRef {
+        let mut value = value.to_vec();
+        value.push(0);
+        let ty = LLVMArrayType(LLVMInt8Type(), value.len() as u32);
+        let global = LLVMAddGlobal(self.cur_module.unwrap(), ty, cstr!(".str"));
+        LLVMSetInitializer(
+            global,
+            LLVMConstString(value.as_ptr() as *const i8, value.len() as u32, 1),
+        );
+        LLVMSetGlobalConstant(global, 1);
+        LLVMSetLinkage(global, LLVMLinkage::LLVMPrivateLinkage);
+        LLVMSetUnnamedAddress(global, LLVMUnnamedAddr::LLVMGlobalUnnamedAddr);
+        LLVMConstBitCast(global, LLVMPointerType(LLVMInt8Type(), 0))
+    }
+
+    /// Integer operands stay integers, anything involving a decimal is done in floating point and
+    /// `NUMBER`s are exact, see `VariableType::arithmetic`.
+    pub unsafe fn visit_arithmetic(
+        &mut self,
+        arithmetic: Arithmetic,
+    ) -> (LLVMValueRef, VariableType) {
+        let Arithmetic {
+            l, r, op, location, ..
+        } = arithmetic;
+        let ((l, l_ty), (r, r_ty)) = self.visit_operands(*l, *r);
+        if !l_ty.is_numeric() || !r_ty.is_numeric() {
+            panic!("Cannot do arithmetic on a {l_ty:?} and a {r_ty:?}.");
+        }
+
+        let ty = match VariableType::arithmetic(&l_ty, &r_ty, &op) {
+            VariableType::Integer
+                if self.exact_division && matches!(op, ArithmeticOperators::Divide) =>
+            {
+                VariableType::Fixed {
+                    digits: 2 * MAX_DIGITS,
+                    scale: DIVISION_DECIMALS,
+                }
+            }
+            ty => ty,
+        };
+        if let VariableType::Fixed { scale, .. } = ty {
+            let value = self.visit_fixed_arithmetic((l, l_ty), (r, r_ty), op, scale, &location);
+            return (value, ty);
+        }
+        if ty == VariableType::Integer {
+            let value = self.visit_integer_arithmetic(l, r, op, &location);
+            return (value, VariableType::Integer);
+        }
+
+        let l = self.convert(l, &l_ty, &VariableType::Decimal);
+        let mut r = self.convert(r, &r_ty, &VariableType::Decimal);
+        if matches!(
+            op,
+            ArithmeticOperators::Divide | ArithmeticOperators::Remainder
+        ) {
+            r = self.check_decimal_divisor(r, &location);
+        }
+        let value = match op {
+            ArithmeticOperators::Add => LLVMBuildFAdd(self.builder, l, r, cstr!("")),
+            ArithmeticOperators::Subtract => LLVMBuildFSub(self.builder, l, r, cstr!("")),
+            ArithmeticOperators::Multiply => LLVMBuildFMul(self.builder, l, r, cstr!("")),
+            ArithmeticOperators::Divide => LLVMBuildFDiv(self.builder, l, r, cstr!("")),
+            ArithmeticOperators::Remainder => LLVMBuildFRem(self.builder, l, r, cstr!("")),
+        };
+        (value, VariableType::Decimal)
+    }
+
+    /// Lowers the number of an item or a character, or the key of an entry. Dividing whole
+    /// numbers in it gives a whole number, whatever the result around it is stored in.
+    pub unsafe fn visit_index(&mut self, index: &Index) -> (LLVMValueRef, VariableType) {
+        let exact = std::mem::replace(&mut self.exact_division, false);
+        let value = self.visit_expr((*index.value).clone());
+        self.exact_division = exact;
+        value
+    }
+
+    /// Integer arithmetic that stops at a result an `INTEGER` can't hold, or a division by zero,
+    /// instead of wrapping around.
+    unsafe fn visit_integer_arithmetic(
+        &mut self,
+        l: LLVMValueRef,
+        r: LLVMValueRef,
+        op: ArithmeticOperators,
+        location: &str,
+    ) -> LLVMValueRef {
+        let checked = match op {
+            ArithmeticOperators::Add => "llvm.sadd.with.overflow.i64",
+            ArithmeticOperators::Subtract => "llvm.ssub.with.overflow.i64",
+            ArithmeticOperators::Multiply => "llvm.smul.with.overflow.i64",
+            ArithmeticOperators::Divide | ArithmeticOperators::Remainder => {
+                let divisor = self.check_divisor(r, location);
+                // The one quotient that doesn't fit is `i64::MIN / -1`, its remainder is 0.
+                let min = LLVMConstInt(LLVMInt64Type(), i64::MIN as u64, 1);
+                let minus_one = LLVMConstInt(LLVMInt64Type(), -1i64 as u64, 1);
+                let eq = LLVMIntPredicate::LLVMIntEQ;
+                let overflow = LLVMBuildAnd(
+                    self.builder,
+                    LLVMBuildICmp(self.builder, eq, l, min, cstr!("")),
+                    LLVMBuildICmp(self.builder, eq, divisor, minus_one, cstr!("")),
+                    cstr!(""),
+                );
+                let one = LLVMConstInt(LLVMInt64Type(), 1, 0);
+                let divisor = LLVMBuildSelect(self.builder, overflow, one, divisor, cstr!(""));
+                if matches!(op, ArithmeticOperators::Remainder) {
+                    return LLVMBuildSRem(self.builder, l, divisor, cstr!(""));
+                }
+                self.size_error(overflow, location, "The result doesn't fit in an INTEGER");
+                return LLVMBuildSDiv(self.builder, l, divisor, cstr!(""));
+            }
+        };
+        let result = self.call_c_fn(checked, vec![l, r]);
+        let overflow = LLVMBuildExtractValue(self.builder, result, 1, cstr!(""));
+        self.size_error(overflow, location, "The result doesn't fit in an INTEGER");
+        LLVMBuildExtractValue(self.builder, result, 0, cstr!(""))
+    }
+
+    /// Checks that the divisor `r`, an integer of any width, isn't zero and returns one that can
+    /// be divided by whatever happens next.
+    pub unsafe fn check_divisor(&mut self, r: LLVMValueRef, location: &str) -> LLVMValueRef {
+        let zero = LLVMConstInt(LLVMTypeOf(r), 0, 0);
+        let is_zero = LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntEQ,
+            r,
+            zero,
+            cstr!(""),
+        );
+        self.size_error(is_zero, location, "Cannot divide by zero");
+        let one = LLVMConstInt(LLVMTypeOf(r), 1, 0);
+        LLVMBuildSelect(self.builder, is_zero, one, r, cstr!(""))
+    }
+
+    /// Like [`Self::check_divisor`] for a `DECIMAL` divisor, which would otherwise give an
+    /// infinity or NaN.
+    unsafe fn check_decimal_divisor(&mut self, r: LLVMValueRef, location: &str) -> LLVMValueRef {
+        let is_zero = LLVMBuildFCmp(
+            self.builder,
+            LLVMRealPredicate::LLVMRealOEQ,
+            r,
+            LLVMConstReal(LLVMDoubleType(), 0.0),
+            cstr!(""),
+        );
+        self.size_error(is_zero, location, "Cannot divide by zero");
+        let one = LLVMConstReal(LLVMDoubleType(), 1.0);
+        LLVMBuildSelect(self.builder, is_zero, one, r, cstr!(""))
+    }
+
+    /// A result that couldn't be worked out when `failed` is set. Inside a `COMPUTE` with an
+    /// `ON SIZE ERROR` that runs the handler, anywhere else the program stops with `message`.
+    pub unsafe fn size_error(&mut self, failed: LLVMValueRef, location: &str, message: &str) {
+        if let Some(slot) = self.size_error {
+            let any = LLVMBuildLoad2(self.builder, LLVMInt1Type(), slot, cstr!(""));
+            let any = LLVMBuildOr(self.builder, any, failed, cstr!(""));
+            LLVMBuildStore(self.builder, any, slot);
+            return;
+        }
+        let func = LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.builder));
+        let error = LLVMAppendBasicBlock(func, cstr!("size.error"));
+        let fits = LLVMAppendBasicBlock(func, cstr!("size.ok"));
+        LLVMBuildCondBr(self.builder, failed, error, fits);
+
+        LLVMPositionBuilderAtEnd(self.builder, error);
+        let format = format!("%s: {message}.\n");
+        let format = LLVMBuildGlobalStringPtr(self.builder, cstr!(format.as_bytes()), cstr!(""));
+        let location =
+            LLVMBuildGlobalStringPtr(self.builder, cstr!(location.as_bytes()), cstr!(""));
+        self.call_c_fn("printf", vec![format, location]);
+        self.call_c_fn("exit", vec![LLVMConstInt(LLVMInt32Type(), 1, 0)]);
+        LLVMBuildUnreachable(self.builder);
+
+        LLVMPositionBuilderAtEnd(self.builder, fits);
+    }
+
+    /// Lowers the condition of an `IF` to an `i1`. Anything that isn't a comparison has to be a
+    /// boolean, which the type checker makes sure of.
+    pub unsafe fn visit_condition(&mut self, expr: Expr) -> LLVMValueRef {
+        match expr {
+            Expr::BinaryOp(binary) => self.visit_comparison(binary),
+            Expr::Logical(logical) => self.visit_logical(logical),
+            Expr::Not(inner) => {
+                let inner = self.visit_condition(*inner);
+                LLVMBuildNot(self.builder, inner, cstr!(""))
+            }
+            expr => match self.visit_expr(expr) {
+                (value, VariableType::Boolean) => value,
+                (_, ty) => panic!("Expected a condition, not a {ty:?}."),
+            },
+        }
+    }
+
+    /// `AND` and `OR` short-circuit: the right side gets its own block that is skipped when the
+    /// left side already decides the result, and a phi picks the value where the paths meet.
+    pub unsafe fn visit_logical(&mut self, logical: Logical) -> LLVMValueRef {
+        let Logical { l, r, op, .. } = logical;
+        let l = self.visit_condition(*l);
+        let lhs_end = LLVMGetInsertBlock(self.builder);
+        let func = LLVMGetBasicBlockParent(lhs_end);
+
+        let (rhs, end, short_circuit) = match op {
+            LogicalOperators::And => (
+                LLVMAppendBasicBlock(func, cstr!("and.rhs")),
+                LLVMAppendBasicBlock(func, cstr!("and.end")),
+                0,
+            ),
+            LogicalOperators::Or => (
+                LLVMAppendBasicBlock(func, cstr!("or.rhs")),
+                LLVMAppendBasicBlock(func, cstr!("or.end")),
+                1,
+            ),
+        };
+        match op {
+            LogicalOperators::And => LLVMBuildCondBr(self.builder, l, rhs, end),
+            LogicalOperators::Or => LLVMBuildCondBr(self.builder, l, end, rhs),
+        };
+
+        LLVMPositionBuilderAtEnd(self.builder, rhs);
+        let r = self.visit_condition(*r);
+        let rhs_end = LLVMGetInsertBlock(self.builder);
+        LLVMBuildBr(self.builder, end);
+
+        LLVMPositionBuilderAtEnd(self.builder, end);
+        let phi = LLVMBuildPhi(self.builder, LLVMInt1Type(), cstr!(""));
+        let mut values = [LLVMConstInt(LLVMInt1Type(), short_circuit, 0), r];
+        let mut blocks = [lhs_end, rhs_end];
+        LLVMAddIncoming(phi, values.as_mut_ptr(), blocks.as_mut_ptr(), 2);
+        phi
+    }
+
+    /// Lowers a comparison to an `i1`, `CONTAINS` and `CONTAINS KEY` included.
+    pub unsafe fn visit_comparison(&mut self, binary: Binary) -> LLVMValueRef {
+        let Binary { l, r, op, .. } = binary;
+        if let BinaryOperators::Contains | BinaryOperators::ContainsKey = op {
+            let haystack = self.visit_expr(*l);
+            return self.visit_contains(haystack, *r);
+        }
+        let (l, r) = self.visit_operands(*l, *r);
+        self.compare(op, l, r)
+    }
+
+    /// Numbers are compared with `icmp`/`fcmp`, strings are ordered by `strcmp`. A `TEXT` is
+    /// compared as if the shorter side were padded with spaces.
+    pub unsafe fn compare(
+        &mut self,
+        op: BinaryOperators,
+        (l, l_ty): (LLVMValueRef, VariableType),
+        (r, r_ty): (LLVMValueRef, VariableType),
+    ) -> LLVMValueRef {
+        let int_predicate = match op {
+            BinaryOperators::EqualTo => LLVMIntPredicate::LLVMIntEQ,
+            BinaryOperators::NotEqualTo => LLVMIntPredicate::LLVMIntNE,
+            BinaryOperators::GreaterThan => LLVMIntPredicate::LLVMIntSGT,
+            BinaryOperators::LessThan => LLVMIntPredicate::LLVMIntSLT,
+            BinaryOperators::GreaterThanOrEqualTo => LLVMIntPredicate::LLVMIntSGE,
+            BinaryOperators::LessThanOrEqualTo => LLVMIntPredicate::LLVMIntSLE,
+            BinaryOperators::Contains | BinaryOperators::ContainsKey => unreachable!(),
+        };
+
+        match (&l_ty, &r_ty) {
+            // All of both sides is compared, so characters after a NUL still count.
+            (VariableType::Text { length }, VariableType::Text { length: r_length })
+                if length == r_length =>
+            {
+                let length = LLVMConstInt(LLVMInt64Type(), *length as u64, 0);
+                let ordering = self.call_c_fn("memcmp", vec![l, r, length]);
+                LLVMBuildICmp(
+                    self.builder,
+                    int_predicate,
+                    ordering,
+                    LLVMConstInt(LLVMInt32Type(), 0, 1),
+                    cstr!(""),
+                )
+            }
+            (l_ty, r_ty) if l_ty.is_text() && r_ty.is_text() => {
+                let padded = *l_ty != VariableType::String || *r_ty != VariableType::String;
+                let compare = match self.lookup_c_fn("strcmp") {
+                    _ if padded => self.compare_padded_fn(),
+                    Some(strcmp) => strcmp,
+                    None => panic!("strcmp is not defined."),
+                };
+                let mut arguments = vec![l, r];
+                let ordering = LLVMBuildCall2(
+                    self.builder,
+                    compare.0,
+                    compare.1,
+                    arguments.as_mut_ptr(),
+                    arguments.len() as u32,
+                    cstr!(""),
+                );
+                LLVMBuildICmp(
+                    self.builder,
+                    int_predicate,
+                    ordering,
+                    LLVMConstInt(LLVMInt32Type(), 0, 1),
+                    cstr!(""),
+                )
+            }
+            (VariableType::Boolean, VariableType::Boolean)
+            | (VariableType::Choice { .. }, VariableType::Choice { .. })
+            | (VariableType::Function { .. }, VariableType::Function { .. }) => {
+                LLVMBuildICmp(self.builder, int_predicate, l, r, cstr!(""))
+            }
+            (l_ty, r_ty) if !l_ty.is_numeric() || !r_ty.is_numeric() => {
+                panic!("Cannot compare a {l_ty:?} to a {r_ty:?}.")
+            }
+            (VariableType::Integer, VariableType::Integer) => {
+                LLVMBuildICmp(self.builder, int_predicate, l, r, cstr!(""))
+            }
+            (l_ty, r_ty) if l_ty.scale().is_some() && r_ty.scale().is_some() => {
+                let scale = l_ty.scale().max(r_ty.scale()).unwrap_or_default();
+                let l = self.to_fixed(l, l_ty, scale);
+                let r = self.to_fixed(r, r_ty, scale);
+                LLVMBuildICmp(self.builder, int_predicate, l, r, cstr!(""))
+            }
+            _ => {
+                let real_predicate = match op {
+                    BinaryOperators::EqualTo => LLVMRealPredicate::LLVMRealOEQ,
+                    BinaryOperators::NotEqualTo => LLVMRealPredicate::LLVMRealUNE,
+                    BinaryOperators::GreaterThan => LLVMRealPredicate::LLVMRealOGT,
+                    BinaryOperators::LessThan => LLVMRealPredicate::LLVMRealOLT,
+                    BinaryOperators::GreaterThanOrEqualTo => LLVMRealPredicate::LLVMRealOGE,
+                    BinaryOperators::LessThanOrEqualTo => LLVMRealPredicate::LLVMRealOLE,
+                    BinaryOperators::Contains | BinaryOperators::ContainsKey => unreachable!(),
+                };
+                let l = self.convert(l, &l_ty, &VariableType::Decimal);
+                let r = self.convert(r, &r_ty, &VariableType::Decimal);
+                LLVMBuildFCmp(self.builder, real_predicate, l, r, cstr!(""))
+            }
+        }
+    }
+
+    /// `CONTAINS` on two strings is a substring test, done with `strstr`. A list contains a value
+    /// when one of its items is equal to it, and a table when it has an entry for it as the key.
+    pub unsafe fn visit_contains(
+        &mut self,
+        (haystack, haystack_ty): (LLVMValueRef, VariableType),
+        needle: Expr,
+    ) -> LLVMValueRef {
+        match &haystack_ty {
+            VariableType::List { item, .. } => {
+                let needle = self.visit_operand(needle, item);
+                return self.has_item(haystack, &haystack_ty, needle);
+            }
+            VariableType::Table { key, .. } => {
+                let (key, _) = self.visit_operand(needle, key);
+                let index = self.find_entry(haystack, key);
+                let none = LLVMConstInt(LLVMInt64Type(), 0, 1);
+                return LLVMBuildICmp(
+                    self.builder,
+                    LLVMIntPredicate::LLVMIntSGE,
+                    index,
+                    none,
+                    cstr!(""),
+                );
+            }
+            VariableType::String | VariableType::Text { .. } => {}
+            VariableType::Integer
+            | VariableType::Decimal
+            | VariableType::Boolean
+            | VariableType::Fixed { .. }
+            | VariableType::Record(_)
+            | VariableType::Function { .. }
+            | VariableType::Choice { .. } => {
+                unreachable!("typeck only allows `CONTAINS` on text, lists and tables.")
+            }
+        }
+        let (needle, needle_ty) = self.visit_operand(needle, &haystack_ty);
+        if !haystack_ty.is_text() || !needle_ty.is_text() {
+            panic!("Cannot check whether a {haystack_ty:?} contains a {needle_ty:?}.");
+        }
+        let Some(strstr) = self.lookup_c_fn("strstr") else {
+            panic!("strstr is not defined.");
+        };
+        let mut arguments = vec![haystack, needle];
+        let found = LLVMBuildCall2(
+            self.builder,
+            strstr.0,
+            strstr.1,
+            arguments.as_mut_ptr(),
+            arguments.len() as u32,
+            cstr!(""),
+        );
+        LLVMBuildIsNotNull(self.builder, found, cstr!(""))
+    }
+
+    /// Converts between the numeric types. Decimals are truncated when stored into an integer or
+    /// a `NUMBER` with fewer decimals.
+    pub unsafe fn convert(
+        &mut self,
+        value: LLVMValueRef,
+        from: &VariableType,
+        to: &VariableType,
+    ) -> LLVMValueRef {
+        match (from, to) {
+            (VariableType::Integer, VariableType::Decimal) => {
+                LLVMBuildSIToFP(self.builder, value, LLVMDoubleType(), cstr!(""))
+            }
+            (VariableType::Decimal, VariableType::Integer) => {
+                LLVMBuildFPToSI(self.builder, value, LLVMInt64Type(), cstr!(""))
+            }
+            (from, VariableType::Fixed { scale, .. }) => self.to_fixed(value, from, *scale),
+            (VariableType::Fixed { scale, .. }, VariableType::Integer) => {
+                let value = self.rescale(value, *scale, 0, Rounding::Truncate);
+                LLVMBuildTrunc(self.builder, value, LLVMInt64Type(), cstr!(""))
+            }
+            (VariableType::Fixed { scale, .. }, VariableType::Decimal) => {
+                let value = LLVMBuildSIToFP(self.builder, value, LLVMDoubleType(), cstr!(""));
+                let factor = LLVMConstReal(LLVMDoubleType(), 10f64.powi(*scale as i32));
+                LLVMBuildFDiv(self.builder, value, factor, cstr!(""))
+            }
+            (from, to) if from == to => value,
+            (from, to) => panic!("Cannot store a {from:?} into a {to:?}."),
+        }
+    }
+
+    /// Evaluates every value, then stores them. Returns an `i1` that is set when a result didn't
+    /// fit in its target or couldn't be worked out, if there is an `ON SIZE ERROR` that needs to
+    /// know. The targets of those results are left as they were.
+    pub unsafe fn visit_compute(&mut self, compute: Compute) -> Option<LLVMValueRef> {
+        let handled = compute.on_size_error.is_some();
+        let slot = handled.then(|| self.entry_alloca(LLVMInt1Type(), "size.error"));
+        let enclosing = self.size_error;
+        let mut values = vec![];
+        for store in &compute.stores {
+            self.exact_division = store.rounding != Rounding::Truncate
+                || matches!(self.target_type(store), VariableType::Fixed { .. });
+            let failed = slot.map(|slot| {
+                LLVMBuildStore(self.builder, LLVMConstInt(LLVMInt1Type(), 0, 0), slot);
+                self.size_error = Some(slot);
+                let value = self.visit_expr(store.value.clone());
+                self.size_error = enclosing;
+                let failed = LLVMBuildLoad2(self.builder, LLVMInt1Type(), slot, cstr!(""));
+                (value, failed)
+            });
+            values.push(failed.unwrap_or_else(|| {
+                let value = self.visit_expr(store.value.clone());
+                (value, LLVMConstInt(LLVMInt1Type(), 0, 0))
+            }));
+            self.exact_division = false;
+        }
+
+        let mut size_error = handled.then(|| LLVMConstInt(LLVMInt1Type(), 0, 0));
+        for (store, ((value, value_ty), failed)) in compute.stores.into_iter().zip(values) {
+            let (ptr, kind) = self.target_ptr(&store);
+            if kind.is_text() {
+                panic!(
+                    "Cannot store the result of arithmetic in the string {:?}.",
+                    store.target
+                );
+            }
+            let old = LLVMBuildLoad2(self.builder, llvm_type(&kind), ptr, cstr!(""));
+            let failed = match kind {
+                VariableType::Fixed { digits, scale } => {
+                    let overflow = self.store_fixed(
+                        value,
+                        &value_ty,
+                        (digits, scale),
+                        store.rounding,
+                        ptr,
+                        handled,
+                    );
+                    LLVMBuildOr(self.builder, failed, overflow, cstr!(""))
+                }
+                VariableType::Integer if store.rounding != Rounding::Truncate => {
+                    let value = self.round_to_fixed(value, &value_ty, 0, store.rounding);
+                    let value = LLVMBuildTrunc(self.builder, value, LLVMInt64Type(), cstr!(""));
+                    LLVMBuildStore(self.builder, value, ptr);
+                    failed
+                }
+                _ => {
+                    let value = self.convert(value, &value_ty, &kind);
+                    LLVMBuildStore(self.builder, value, ptr);
+                    failed
+                }
+            };
+            if handled {
+                let new = LLVMBuildLoad2(self.builder, llvm_type(&kind), ptr, cstr!(""));
+                let kept = LLVMBuildSelect(self.builder, failed, old, new, cstr!(""));
+                LLVMBuildStore(self.builder, kept, ptr);
+            }
+            size_error = size_error.map(|any| LLVMBuildOr(self.builder, any, failed, cstr!("")));
+        }
+        size_error
+    }
+
+    /// `DISPLAY` prints every operand next to each other, followed by a newline. Booleans are
+    /// printed as `TRUE` or `FALSE` and `NUMBER`s with all of their decimals.
+    pub unsafe fn visit_display(&mut self, exprs: Vec<Expr>) {
+        let mut format = String::new();
+        let mut arguments = vec![];
+        for expr in exprs {
+            let (value, ty) = self.visit_expr(expr);
+            self.display_value(value, ty, &mut format, &mut arguments);
+        }
+        format.push('\n');
+        arguments.insert(
+            0,
+            LLVMBuildGlobalStringPtr(self.builder, cstr!(format.as_bytes()), cstr!("")),
+        );
+
+        let Some(printf) = self.lookup_c_fn("printf") else {
+            panic!("printf is not defined.");
+        };
+        LLVMBuildCall2(
+            self.builder,
+            printf.0,
+            printf.1,
+            arguments.as_mut_ptr(),
+            arguments.len() as u32,
+            cstr!(""),
+        );
+    }
+
+    /// Adds a value to the `printf` format and arguments of a `DISPLAY`. A record shows its
+    /// fields one after another.
+    pub unsafe fn display_value(
+        &mut self,
+        value: LLVMValueRef,
+        ty: VariableType,
+        format: &mut String,
+        arguments: &mut Vec<LLVMValueRef>,
+    ) {
+        let value = match &ty {
+            VariableType::Fixed { scale, .. } => {
+                let (fixed_format, values) = self.display_fixed(value, *scale);
+                format.push_str(&fixed_format);
+                arguments.extend(values);
+                return;
+            }
+            VariableType::Record(fields) => {
+                for (index, field) in fields.iter().enumerate() {
+                    let ptr = self.field_ptr(value, &ty, index);
+                    let (field_value, field_ty) = self.load(ptr, field.ty.clone());
+                    self.display_value(field_value, field_ty, format, arguments);
+                }
+                return;
+            }
+            VariableType::Choice { .. } => self.choice_name(value, &ty),
+            VariableType::Boolean => LLVMBuildSelect(
+                self.builder,
+                value,
+                LLVMBuildGlobalStringPtr(self.builder, cstr!("TRUE"), cstr!("")),
+                LLVMBuildGlobalStringPtr(self.builder, cstr!("FALSE"), cstr!("")),
+                cstr!(""),
+            ),
+            _ => value,
+        };
+        format.push_str(match ty {
+            VariableType::String
+            | VariableType::Text { .. }
+            | VariableType::Boolean
+            | VariableType::Choice { .. } => "%s",
+            VariableType::Integer => "%lld",
+            VariableType::Decimal => "%.15g",
+            VariableType::Fixed { .. }
+            | VariableType::Record(_)
+            | VariableType::List { .. }
+            | VariableType::Table { .. }
+            | VariableType::Function { .. } => unreachable!(),
+        });
+        arguments.push(value);
+    }
+}
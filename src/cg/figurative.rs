@@ -0,0 +1,55 @@
+//! `SPACES`, `ZEROS`, `HIGH-VALUES` and `LOW-VALUES`, filled in for the type they are used as.
+
+use llvm_sys::{
+    core::{LLVMConstInt, LLVMConstReal, LLVMConstString, LLVMDoubleType, LLVMInt64Type},
+    prelude::LLVMValueRef,
+};
+
+use crate::node::{Figurative, Stmt, VariableType};
+
+use super::CodeGen;
+
+impl<T> CodeGen<T>
+where
+    T: Iterator<Item = Stmt>,
+{
+    /// The constant as it is stored in a variable of type `ty`. A `TEXT` is filled completely,
+    /// a `STRING` has no length of its own so it gets a single character.
+    pub unsafe fn const_figurative(
+        &mut self,
+        figurative: Figurative,
+        ty: &VariableType,
+    ) -> LLVMValueRef {
+        match ty {
+            VariableType::Text { length } => {
+                let bytes = vec![figurative.byte(); *length as usize];
+                LLVMConstString(bytes.as_ptr() as *const i8, *length, 1)
+            }
+            VariableType::Fixed { .. } => LLVMConstInt(LLVMInt64Type(), 0, 0),
+            ty => self.figurative_value(figurative, ty).0,
+        }
+    }
+
+    /// The constant as a value of type `ty` in an expression, or of its default type if it
+    /// can't be one.
+    pub unsafe fn figurative_value(
+        &mut self,
+        figurative: Figurative,
+        ty: &VariableType,
+    ) -> (LLVMValueRef, VariableType) {
+        if !figurative.fits(ty) {
+            let ty = figurative.default_type();
+            return self.figurative_value(figurative, &ty);
+        }
+        let value = match ty {
+            VariableType::Integer => LLVMConstInt(LLVMInt64Type(), 0, 0),
+            VariableType::Decimal => LLVMConstReal(LLVMDoubleType(), 0.0),
+            VariableType::Fixed { .. } => self.const_i128(0),
+            VariableType::Text { length } => {
+                self.const_string(&vec![figurative.byte(); *length as usize])
+            }
+            _ => self.const_string(&[figurative.byte()]),
+        };
+        (value, ty.clone())
+    }
+}
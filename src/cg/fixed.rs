@@ -0,0 +1,348 @@
+//! Exact arithmetic on `NUMBER`s. A `NUMBER` with `scale` decimals is stored as an `i64` that
+//! counts units of `10^-scale`, and widened to an `i128` while it is being computed with so
+//! multiplying two of them can't overflow.
+
+use llvm_sys::{
+    core::{
+        LLVMBuildAdd, LLVMBuildAnd, LLVMBuildFMul, LLVMBuildFPToSI, LLVMBuildGlobalStringPtr,
+        LLVMBuildICmp, LLVMBuildLoad2, LLVMBuildMul, LLVMBuildNeg, LLVMBuildOr, LLVMBuildSDiv,
+        LLVMBuildSExt, LLVMBuildSRem, LLVMBuildSelect, LLVMBuildStore, LLVMBuildSub,
+        LLVMBuildTrunc, LLVMConstInt, LLVMConstIntOfArbitraryPrecision, LLVMConstReal,
+        LLVMDoubleType, LLVMInt128Type, LLVMInt64Type,
+    },
+    prelude::LLVMValueRef,
+    LLVMIntPredicate,
+};
+
+use crate::node::{
+    ArithmeticOperators, Expr, LiteralExpr, Rounding, Stmt, VariableType, MAX_DIGITS,
+};
+
+use super::CodeGen;
+
+impl<T> CodeGen<T>
+where
+    T: Iterator<Item = Stmt>,
+{
+    pub unsafe fn const_i128(&self, value: i128) -> LLVMValueRef {
+        let words = [value as u64, (value >> 64) as u64];
+        LLVMConstIntOfArbitraryPrecision(LLVMInt128Type(), 2, words.as_ptr())
+    }
+
+    pub unsafe fn pow10(&self, exponent: u32) -> LLVMValueRef {
+        self.const_i128(10i128.pow(exponent))
+    }
+
+    /// A literal as it is stored in a `NUMBER` with `scale` decimals, the type checker has made
+    /// sure it fits.
+    pub unsafe fn const_fixed(&self, value: &Expr, scale: u32) -> LLVMValueRef {
+        let Some(value) = value.scaled_literal(scale) else {
+            panic!("Expected a literal number, not {value:?}.");
+        };
+        LLVMConstInt(LLVMInt64Type(), value as u64, 1)
+    }
+
+    /// Visits both sides of an operator. A decimal literal next to a `NUMBER` becomes an exact
+    /// `NUMBER` instead of a `double`, a figurative constant takes the type of the other side and
+    /// a string next to a choice is one of its values. The type checker does the same.
+    pub unsafe fn visit_operands(
+        &mut self,
+        l: Expr,
+        r: Expr,
+    ) -> ((LLVMValueRef, VariableType), (LLVMValueRef, VariableType)) {
+        if let Expr::Literal(LiteralExpr::Figurative(figurative)) = l {
+            let r = self.visit_expr(r);
+            return (self.figurative_value(figurative, &r.1), r);
+        }
+        if let Expr::Literal(LiteralExpr::Figurative(figurative)) = r {
+            let l = self.visit_expr(l);
+            let r = self.figurative_value(figurative, &l.1);
+            return (l, r);
+        }
+        if let Expr::Literal(LiteralExpr::String(_)) = l {
+            let r = self.visit_expr(r);
+            return (self.visit_operand(l, &r.1), r);
+        }
+        if let Expr::Literal(LiteralExpr::String(_)) = r {
+            let l = self.visit_expr(l);
+            let r = self.visit_operand(r, &l.1);
+            return (l, r);
+        }
+        let (l_exact, r_exact) = (l.fixed_literal(), r.fixed_literal());
+        let mut l = self.visit_expr(l);
+        let mut r = self.visit_expr(r);
+        if let (Some((value, scale)), VariableType::Fixed { .. }) = (l_exact, &r.1) {
+            l = (self.const_i128(value), exact(scale));
+        }
+        if let (Some((value, scale)), VariableType::Fixed { .. }) = (r_exact, &l.1) {
+            r = (self.const_i128(value), exact(scale));
+        }
+        (l, r)
+    }
+
+    /// Visits `expr` as one side of an operator whose other side, already lowered, is of type
+    /// `other`, the same way `visit_operands` would.
+    pub unsafe fn visit_operand(
+        &mut self,
+        expr: Expr,
+        other: &VariableType,
+    ) -> (LLVMValueRef, VariableType) {
+        if let Expr::Literal(LiteralExpr::Figurative(figurative)) = expr {
+            return self.figurative_value(figurative, other);
+        }
+        if let (Some((value, scale)), VariableType::Fixed { .. }) = (expr.fixed_literal(), other) {
+            return (self.const_i128(value), exact(scale));
+        }
+        if let (Expr::Literal(LiteralExpr::String(_)), VariableType::Choice { .. }) = (&expr, other)
+        {
+            return (self.const_choice(&expr, other), other.clone());
+        }
+        self.visit_expr(expr)
+    }
+
+    /// Turns any number into an `i128` with `scale` decimals. Decimals past `scale` are cut off.
+    pub unsafe fn to_fixed(
+        &mut self,
+        value: LLVMValueRef,
+        from: &VariableType,
+        scale: u32,
+    ) -> LLVMValueRef {
+        match from {
+            VariableType::Integer => {
+                let value = LLVMBuildSExt(self.builder, value, LLVMInt128Type(), cstr!(""));
+                LLVMBuildMul(self.builder, value, self.pow10(scale), cstr!(""))
+            }
+            VariableType::Fixed { scale: from, .. } => {
+                self.rescale(value, *from, scale, Rounding::Truncate)
+            }
+            VariableType::Decimal => {
+                let factor = LLVMConstReal(LLVMDoubleType(), 10f64.powi(scale as i32));
+                let value = LLVMBuildFMul(self.builder, value, factor, cstr!(""));
+                LLVMBuildFPToSI(self.builder, value, LLVMInt128Type(), cstr!(""))
+            }
+            ty => panic!("Cannot use a {ty:?} as a number."),
+        }
+    }
+
+    /// Like `to_fixed`, but the decimals that don't fit are dropped as `rounding` says. Decimals
+    /// are first made exact with two extra digits, which is as close as a `double` gets anyway.
+    pub unsafe fn round_to_fixed(
+        &mut self,
+        value: LLVMValueRef,
+        from: &VariableType,
+        scale: u32,
+        rounding: Rounding,
+    ) -> LLVMValueRef {
+        match from {
+            VariableType::Fixed { scale: from, .. } => self.rescale(value, *from, scale, rounding),
+            VariableType::Decimal => {
+                let value = self.to_fixed(value, from, scale + 2);
+                self.rescale(value, scale + 2, scale, rounding)
+            }
+            from => self.to_fixed(value, from, scale),
+        }
+    }
+
+    /// Changes the number of decimals of an `i128`.
+    pub unsafe fn rescale(
+        &mut self,
+        value: LLVMValueRef,
+        from: u32,
+        to: u32,
+        rounding: Rounding,
+    ) -> LLVMValueRef {
+        if from <= to {
+            return LLVMBuildMul(self.builder, value, self.pow10(to - from), cstr!(""));
+        }
+        let divisor = self.pow10(from - to);
+        let quotient = LLVMBuildSDiv(self.builder, value, divisor, cstr!(""));
+        if rounding == Rounding::Truncate {
+            return quotient;
+        }
+
+        // The remainder has the sign of `value`, rounding moves the quotient away from zero.
+        let zero = self.const_i128(0);
+        let remainder = LLVMBuildSRem(self.builder, value, divisor, cstr!(""));
+        let negative = LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntSLT,
+            value,
+            zero,
+            cstr!(""),
+        );
+        let step = LLVMBuildSelect(
+            self.builder,
+            negative,
+            self.const_i128(-1),
+            self.const_i128(1),
+            cstr!(""),
+        );
+        let twice = LLVMBuildMul(self.builder, remainder, self.const_i128(2), cstr!(""));
+        let twice = LLVMBuildSelect(
+            self.builder,
+            negative,
+            LLVMBuildNeg(self.builder, twice, cstr!("")),
+            twice,
+            cstr!(""),
+        );
+        let compare = |predicate| LLVMBuildICmp(self.builder, predicate, twice, divisor, cstr!(""));
+        let round_away = match rounding {
+            Rounding::Truncate => unreachable!(),
+            Rounding::Up => LLVMBuildICmp(
+                self.builder,
+                LLVMIntPredicate::LLVMIntNE,
+                remainder,
+                zero,
+                cstr!(""),
+            ),
+            Rounding::Nearest => compare(LLVMIntPredicate::LLVMIntSGE),
+            Rounding::Even => {
+                let above = compare(LLVMIntPredicate::LLVMIntSGT);
+                let half = compare(LLVMIntPredicate::LLVMIntEQ);
+                let odd = LLVMBuildAnd(self.builder, quotient, self.const_i128(1), cstr!(""));
+                let odd = LLVMBuildICmp(
+                    self.builder,
+                    LLVMIntPredicate::LLVMIntNE,
+                    odd,
+                    zero,
+                    cstr!(""),
+                );
+                let tie = LLVMBuildAnd(self.builder, half, odd, cstr!(""));
+                LLVMBuildOr(self.builder, above, tie, cstr!(""))
+            }
+        };
+        let step = LLVMBuildSelect(self.builder, round_away, step, zero, cstr!(""));
+        LLVMBuildAdd(self.builder, quotient, step, cstr!(""))
+    }
+
+    /// `l op r` where the result is a `NUMBER` with `scale` decimals, see
+    /// `VariableType::arithmetic`. Dividing by zero is a size error reported at `location`.
+    pub unsafe fn visit_fixed_arithmetic(
+        &mut self,
+        (l, l_ty): (LLVMValueRef, VariableType),
+        (r, r_ty): (LLVMValueRef, VariableType),
+        op: ArithmeticOperators,
+        scale: u32,
+        location: &str,
+    ) -> LLVMValueRef {
+        let (Some(l_scale), Some(r_scale)) = (l_ty.scale(), r_ty.scale()) else {
+            panic!("Cannot do exact arithmetic on a {l_ty:?} and a {r_ty:?}.");
+        };
+        match op {
+            ArithmeticOperators::Multiply => {
+                let l = self.to_fixed(l, &l_ty, l_scale);
+                let r = self.to_fixed(r, &r_ty, r_scale);
+                LLVMBuildMul(self.builder, l, r, cstr!(""))
+            }
+            // Scaling the dividend up by the divisor's decimals as well leaves `scale` decimals.
+            ArithmeticOperators::Divide => {
+                let l = self.to_fixed(l, &l_ty, scale + r_scale);
+                let r = self.to_fixed(r, &r_ty, r_scale);
+                let r = self.check_divisor(r, location);
+                LLVMBuildSDiv(self.builder, l, r, cstr!(""))
+            }
+            op => {
+                let l = self.to_fixed(l, &l_ty, scale);
+                let r = self.to_fixed(r, &r_ty, scale);
+                match op {
+                    ArithmeticOperators::Add => LLVMBuildAdd(self.builder, l, r, cstr!("")),
+                    ArithmeticOperators::Subtract => LLVMBuildSub(self.builder, l, r, cstr!("")),
+                    _ => {
+                        let r = self.check_divisor(r, location);
+                        LLVMBuildSRem(self.builder, l, r, cstr!(""))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Stores a number into a `NUMBER` variable and returns an `i1` that is set when it had more
+    /// digits than the variable holds. The variable is left as it was in that case if there is
+    /// an `ON SIZE ERROR` to handle it, otherwise the digits that don't fit are dropped from the
+    /// front like COBOL does.
+    pub unsafe fn store_fixed(
+        &mut self,
+        value: LLVMValueRef,
+        value_ty: &VariableType,
+        (digits, scale): (u32, u32),
+        rounding: Rounding,
+        ptr: LLVMValueRef,
+        keep_on_overflow: bool,
+    ) -> LLVMValueRef {
+        let value = self.round_to_fixed(value, value_ty, scale, rounding);
+        let limit = self.pow10(digits);
+        let too_big = LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntSGE,
+            value,
+            limit,
+            cstr!(""),
+        );
+        let too_small = LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntSLE,
+            value,
+            LLVMBuildNeg(self.builder, limit, cstr!("")),
+            cstr!(""),
+        );
+        let overflow = LLVMBuildOr(self.builder, too_big, too_small, cstr!("size.error"));
+
+        let stored = if keep_on_overflow {
+            let old = LLVMBuildLoad2(self.builder, LLVMInt64Type(), ptr, cstr!(""));
+            let new = LLVMBuildTrunc(self.builder, value, LLVMInt64Type(), cstr!(""));
+            LLVMBuildSelect(self.builder, overflow, old, new, cstr!(""))
+        } else {
+            let value = LLVMBuildSRem(self.builder, value, limit, cstr!(""));
+            LLVMBuildTrunc(self.builder, value, LLVMInt64Type(), cstr!(""))
+        };
+        LLVMBuildStore(self.builder, stored, ptr);
+        overflow
+    }
+
+    /// The `printf` format and arguments that print a `NUMBER` with all of its decimals, like
+    /// `-12.50`.
+    pub unsafe fn display_fixed(
+        &mut self,
+        value: LLVMValueRef,
+        scale: u32,
+    ) -> (String, Vec<LLVMValueRef>) {
+        let zero = self.const_i128(0);
+        let negative = LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntSLT,
+            value,
+            zero,
+            cstr!(""),
+        );
+        let sign = LLVMBuildSelect(
+            self.builder,
+            negative,
+            LLVMBuildGlobalStringPtr(self.builder, cstr!("-"), cstr!("")),
+            LLVMBuildGlobalStringPtr(self.builder, cstr!(""), cstr!("")),
+            cstr!(""),
+        );
+        let magnitude = LLVMBuildSelect(
+            self.builder,
+            negative,
+            LLVMBuildNeg(self.builder, value, cstr!("")),
+            value,
+            cstr!(""),
+        );
+        let whole = LLVMBuildSDiv(self.builder, magnitude, self.pow10(scale), cstr!(""));
+        let whole = LLVMBuildTrunc(self.builder, whole, LLVMInt64Type(), cstr!(""));
+        if scale == 0 {
+            return (String::from("%s%lld"), vec![sign, whole]);
+        }
+        let decimals = LLVMBuildSRem(self.builder, magnitude, self.pow10(scale), cstr!(""));
+        let decimals = LLVMBuildTrunc(self.builder, decimals, LLVMInt64Type(), cstr!(""));
+        (format!("%s%lld.%0{scale}lld"), vec![sign, whole, decimals])
+    }
+}
+
+/// The type of a literal with `scale` decimals used as a `NUMBER`.
+fn exact(scale: u32) -> VariableType {
+    VariableType::Fixed {
+        digits: MAX_DIGITS,
+        scale,
+    }
+}
@@ -1,12 +1,143 @@
+//! Functions. Arguments are passed by value, a copy of each made like `MOVE` would. A function
+//! used as a value is a pointer to it, and calling through one that is still null stops the
+//! program.
+
 use llvm_sys::{
-    prelude::{LLVMBasicBlockRef, LLVMValueRef},
+    core::{
+        LLVMAppendBasicBlock, LLVMBuildCall2, LLVMBuildCondBr, LLVMBuildGlobalStringPtr,
+        LLVMBuildIsNull, LLVMBuildLoad2, LLVMBuildUnreachable, LLVMConstInt, LLVMFunctionType,
+        LLVMGetBasicBlockParent, LLVMGetInsertBlock, LLVMInt32Type, LLVMIsFunctionVarArg,
+        LLVMPositionBuilderAtEnd, LLVMVoidType,
+    },
+    prelude::{LLVMBasicBlockRef, LLVMTypeRef, LLVMValueRef},
     LLVMType,
 };
 
+use crate::node::{Expr, FunctionCall, FunctionRef, LiteralExpr, Stmt, VariableType};
+
+use super::{llvm_type, CodeGen};
+
 pub struct Function {
     pub entry: Option<LLVMBasicBlockRef>,
     pub ret: Option<LLVMBasicBlockRef>,
     pub blocks: Vec<LLVMBasicBlockRef>,
     pub ty: *mut LLVMType,
     pub func: LLVMValueRef,
+    /// The types of the arguments it expects, empty for the C library functions.
+    pub parameters: Vec<VariableType>,
+}
+
+/// The LLVM type of a function expecting arguments of these types. Only `main` returns
+/// anything, and it can't be used as a value.
+pub unsafe fn function_type(parameters: &[VariableType]) -> LLVMTypeRef {
+    let mut params = parameters
+        .iter()
+        .map(|ty| llvm_type(ty))
+        .collect::<Vec<_>>();
+    LLVMFunctionType(LLVMVoidType(), params.as_mut_ptr(), params.len() as u32, 0)
+}
+
+impl<T> CodeGen<T>
+where
+    T: Iterator<Item = Stmt>,
+{
+    /// `CALL FUNCTION "name"`, either of the function the resolver found or through a variable
+    /// holding one. The arguments of a C function like `printf` are passed as they are.
+    pub unsafe fn visit_call(&mut self, call: FunctionCall) {
+        let (ty, func, parameters) = match *call.func {
+            Expr::Variable(reference) => {
+                let name = reference.name.clone();
+                let (func, kind) = self.visit_expr(Expr::Variable(reference));
+                let VariableType::Function { parameters } = kind else {
+                    panic!("{name:?} is a {kind:?}, not a function.");
+                };
+                self.check_function(func, &name, &call.location);
+                (function_type(&parameters), func, Some(parameters))
+            }
+            Expr::Literal(LiteralExpr::String(name)) => {
+                let module = match &call.module {
+                    Some(module) => module.clone(),
+                    None => self.cur_module_name.clone(),
+                };
+                let Some(function) = self.functions.get(&format!("{module}-{name}")) else {
+                    panic!("Function {name} not defined.");
+                };
+                let (ty, func) = (function.ty, function.func);
+                let parameters =
+                    (LLVMIsFunctionVarArg(ty) == 0).then(|| function.parameters.clone());
+                (ty, self.import_fn(ty, func), parameters)
+            }
+            func => panic!("Cannot call {func:?}."),
+        };
+
+        let args = call.args.unwrap_or_default();
+        let mut arguments: Vec<LLVMValueRef> = match parameters {
+            Some(parameters) => args
+                .into_iter()
+                .zip(parameters)
+                .map(|(arg, ty)| self.argument(*arg, ty))
+                .collect(),
+            None => args
+                .into_iter()
+                .map(|arg| self.visit_expr(*arg).0)
+                .collect(),
+        };
+        LLVMBuildCall2(
+            self.builder,
+            ty,
+            func,
+            arguments.as_mut_ptr(),
+            arguments.len() as u32,
+            cstr!(""),
+        );
+    }
+
+    /// A copy of `value` as an argument of type `ty`, converted like `MOVE` would.
+    unsafe fn argument(&mut self, value: Expr, ty: VariableType) -> LLVMValueRef {
+        let llvm_ty = llvm_type(&ty);
+        let slot = self.entry_alloca(llvm_ty, "argument");
+        self.move_value(value, slot, ty);
+        LLVMBuildLoad2(self.builder, llvm_ty, slot, cstr!(""))
+    }
+
+    /// Stops the program if the variable `name` doesn't hold a function yet.
+    unsafe fn check_function(&mut self, func: LLVMValueRef, name: &str, location: &str) {
+        let is_null = LLVMBuildIsNull(self.builder, func, cstr!(""));
+        let parent = LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.builder));
+        let missing = LLVMAppendBasicBlock(parent, cstr!("call.missing"));
+        let call = LLVMAppendBasicBlock(parent, cstr!("call"));
+        LLVMBuildCondBr(self.builder, is_null, missing, call);
+
+        LLVMPositionBuilderAtEnd(self.builder, missing);
+        let format = LLVMBuildGlobalStringPtr(
+            self.builder,
+            cstr!("%s: \"%s\" has no FUNCTION to call.\n"),
+            cstr!(""),
+        );
+        let location = LLVMBuildGlobalStringPtr(self.builder, cstr!(location), cstr!(""));
+        let name = LLVMBuildGlobalStringPtr(self.builder, cstr!(name), cstr!(""));
+        self.call_c_fn("printf", vec![format, location, name]);
+        self.call_c_fn("exit", vec![LLVMConstInt(LLVMInt32Type(), 1, 0)]);
+        LLVMBuildUnreachable(self.builder);
+
+        LLVMPositionBuilderAtEnd(self.builder, call);
+    }
+
+    /// `FUNCTION "greet"`, a pointer to the function. It is a constant, so it can also
+    /// initialize a global.
+    pub unsafe fn function_value(&mut self, function: FunctionRef) -> (LLVMValueRef, VariableType) {
+        let name = function.name;
+        let module = function
+            .module
+            .unwrap_or_else(|| self.cur_module_name.clone());
+        let Some(declared) = self.functions.get(&format!("{module}-{name}")) else {
+            panic!("Function {name} not defined.");
+        };
+        let (ty, func) = (declared.ty, declared.func);
+        let parameters = declared.parameters.clone();
+        (
+            self.import_fn(ty, func),
+            VariableType::Function { parameters },
+        )
+    }
 }
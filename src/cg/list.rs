@@ -0,0 +1,298 @@
+//! Lists. One with a length is an LLVM array, one that grows is a count, a capacity and a
+//! pointer to its items on the heap.
+
+use llvm_sys::{
+    core::{
+        LLVMAppendBasicBlock, LLVMArrayType, LLVMBuildAdd, LLVMBuildBitCast, LLVMBuildBr,
+        LLVMBuildCondBr, LLVMBuildGlobalStringPtr, LLVMBuildICmp, LLVMBuildInBoundsGEP2,
+        LLVMBuildLoad2, LLVMBuildMul, LLVMBuildSelect, LLVMBuildStore, LLVMBuildStructGEP2,
+        LLVMBuildSub, LLVMBuildUnreachable, LLVMConstArray, LLVMConstInt, LLVMConstNull,
+        LLVMConstStruct, LLVMGetBasicBlockParent, LLVMGetInsertBlock, LLVMInt1Type, LLVMInt32Type,
+        LLVMInt64Type, LLVMInt8Type, LLVMPointerType, LLVMPositionBuilderAtEnd, LLVMSizeOf,
+        LLVMStructType,
+    },
+    prelude::{LLVMTypeRef, LLVMValueRef},
+    LLVMIntPredicate,
+};
+
+use crate::node::{BinaryOperators, Index, Stmt, Store, VariableType};
+
+use super::{llvm_type, CodeGen};
+
+/// The header of a list that grows: how many items it has, how many fit in the memory it
+/// has, and where that memory is.
+pub unsafe fn growing_list_type(item: &VariableType) -> LLVMTypeRef {
+    let mut members = [
+        LLVMInt64Type(),
+        LLVMInt64Type(),
+        LLVMPointerType(llvm_type(item), 0),
+    ];
+    LLVMStructType(members.as_mut_ptr(), 3, 1)
+}
+
+impl<T> CodeGen<T>
+where
+    T: Iterator<Item = Stmt>,
+{
+    /// A list with a length starts with all of its items blank or zero, one that grows starts
+    /// empty.
+    pub unsafe fn const_list(&mut self, item: &VariableType, length: Option<u32>) -> LLVMValueRef {
+        match length {
+            Some(length) => {
+                let value = self.const_default(item);
+                let mut values = vec![value; length as usize];
+                LLVMConstArray(llvm_type(item), values.as_mut_ptr(), length)
+            }
+            None => {
+                let zero = LLVMConstInt(LLVMInt64Type(), 0, 0);
+                let items = LLVMConstNull(LLVMPointerType(llvm_type(item), 0));
+                let mut members = [zero, zero, items];
+                LLVMConstStruct(members.as_mut_ptr(), 3, 1)
+            }
+        }
+    }
+
+    /// A pointer to the item numbered `index` of the list of type `list` at `ptr`, and the type
+    /// of its items. The program stops with an error when there is no such item.
+    pub unsafe fn item_ptr(
+        &mut self,
+        ptr: LLVMValueRef,
+        list: &VariableType,
+        index: &Index,
+    ) -> (LLVMValueRef, VariableType) {
+        let VariableType::List { item, .. } = list else {
+            panic!("A {list:?} has no items.");
+        };
+        let (number, _) = self.visit_index(index);
+        let count = self.item_count(ptr, list);
+
+        // Items are numbered from 1, so 0 wraps around and is caught with the rest.
+        let one = LLVMConstInt(LLVMInt64Type(), 1, 0);
+        let position = LLVMBuildSub(self.builder, number, one, cstr!(""));
+        let outside = LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntUGE,
+            position,
+            count,
+            cstr!(""),
+        );
+        let func = LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.builder));
+        let out_of_bounds = LLVMAppendBasicBlock(func, cstr!("item.out_of_bounds"));
+        let in_bounds = LLVMAppendBasicBlock(func, cstr!("item.in_bounds"));
+        LLVMBuildCondBr(self.builder, outside, out_of_bounds, in_bounds);
+
+        LLVMPositionBuilderAtEnd(self.builder, out_of_bounds);
+        let format = LLVMBuildGlobalStringPtr(
+            self.builder,
+            cstr!("%s: ITEM %lld is out of bounds, the list has %lld item(s).\n"),
+            cstr!(""),
+        );
+        let location =
+            LLVMBuildGlobalStringPtr(self.builder, cstr!(index.location.as_bytes()), cstr!(""));
+        self.call_c_fn("printf", vec![format, location, number, count]);
+        self.call_c_fn("exit", vec![LLVMConstInt(LLVMInt32Type(), 1, 0)]);
+        LLVMBuildUnreachable(self.builder);
+
+        LLVMPositionBuilderAtEnd(self.builder, in_bounds);
+        (self.item_at(ptr, list, position), (**item).clone())
+    }
+
+    /// How many items the list of type `list` at `ptr` has.
+    pub unsafe fn item_count(&mut self, ptr: LLVMValueRef, list: &VariableType) -> LLVMValueRef {
+        match list {
+            VariableType::List {
+                length: Some(length),
+                ..
+            } => LLVMConstInt(LLVMInt64Type(), *length as u64, 0),
+            VariableType::List {
+                item, length: None, ..
+            } => {
+                let header = growing_list_type(item);
+                let count = LLVMBuildStructGEP2(self.builder, header, ptr, 0, cstr!(""));
+                LLVMBuildLoad2(self.builder, LLVMInt64Type(), count, cstr!(""))
+            }
+            list => panic!("A {list:?} has no items."),
+        }
+    }
+
+    /// A pointer to the item at `position`, counting from 0, of the list of type `list` at
+    /// `ptr`, without checking that it is there.
+    pub unsafe fn item_at(
+        &mut self,
+        ptr: LLVMValueRef,
+        list: &VariableType,
+        position: LLVMValueRef,
+    ) -> LLVMValueRef {
+        let VariableType::List { item, length, .. } = list else {
+            panic!("A {list:?} has no items.");
+        };
+        match length {
+            Some(length) => {
+                let zero = LLVMConstInt(LLVMInt64Type(), 0, 0);
+                let mut indices = [zero, position];
+                LLVMBuildInBoundsGEP2(
+                    self.builder,
+                    LLVMArrayType(llvm_type(item), *length),
+                    ptr,
+                    indices.as_mut_ptr(),
+                    2,
+                    cstr!(""),
+                )
+            }
+            None => {
+                let items = self.growing_items(ptr, item);
+                let mut indices = [position];
+                LLVMBuildInBoundsGEP2(
+                    self.builder,
+                    llvm_type(item),
+                    items,
+                    indices.as_mut_ptr(),
+                    1,
+                    cstr!(""),
+                )
+            }
+        }
+    }
+
+    /// Whether an item of the list of type `list` at `ptr` is equal to `value`. The items are
+    /// compared from the first until one is.
+    pub unsafe fn has_item(
+        &mut self,
+        ptr: LLVMValueRef,
+        list: &VariableType,
+        value: (LLVMValueRef, VariableType),
+    ) -> LLVMValueRef {
+        let VariableType::List { item, .. } = list else {
+            panic!("A {list:?} has no items.");
+        };
+        let count = self.item_count(ptr, list);
+        let found = self.entry_alloca(LLVMInt1Type(), "contains.found");
+        LLVMBuildStore(self.builder, LLVMConstInt(LLVMInt1Type(), 0, 0), found);
+        let position = self.entry_alloca(LLVMInt64Type(), "contains.position");
+        LLVMBuildStore(self.builder, LLVMConstInt(LLVMInt64Type(), 0, 0), position);
+
+        let func = LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.builder));
+        let cond = LLVMAppendBasicBlock(func, cstr!("contains.cond"));
+        let body = LLVMAppendBasicBlock(func, cstr!("contains.body"));
+        let next = LLVMAppendBasicBlock(func, cstr!("contains.next"));
+        let end = LLVMAppendBasicBlock(func, cstr!("contains.end"));
+        LLVMBuildBr(self.builder, cond);
+
+        LLVMPositionBuilderAtEnd(self.builder, cond);
+        let current = LLVMBuildLoad2(self.builder, LLVMInt64Type(), position, cstr!(""));
+        let more = LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntULT,
+            current,
+            count,
+            cstr!(""),
+        );
+        LLVMBuildCondBr(self.builder, more, body, end);
+
+        LLVMPositionBuilderAtEnd(self.builder, body);
+        let at = self.item_at(ptr, list, current);
+        let element = self.load(at, (**item).clone());
+        let equal = self.compare(BinaryOperators::EqualTo, element, value);
+        LLVMBuildStore(self.builder, equal, found);
+        LLVMBuildCondBr(self.builder, equal, end, next);
+
+        LLVMPositionBuilderAtEnd(self.builder, next);
+        let one = LLVMConstInt(LLVMInt64Type(), 1, 0);
+        let following = LLVMBuildAdd(self.builder, current, one, cstr!(""));
+        LLVMBuildStore(self.builder, following, position);
+        LLVMBuildBr(self.builder, cond);
+
+        LLVMPositionBuilderAtEnd(self.builder, end);
+        LLVMBuildLoad2(self.builder, LLVMInt1Type(), found, cstr!(""))
+    }
+
+    /// `APPEND` makes room for one more item, doubling the memory of the list when it is full,
+    /// and moves the value into it. The item only counts once it has been stored, so the value
+    /// can't be the item being added.
+    pub unsafe fn visit_append(&mut self, store: Store) {
+        let (ptr, list) = self.target_ptr(&store);
+        let VariableType::List { item, .. } = list else {
+            panic!("Can't append to a {list:?}.");
+        };
+        let header = growing_list_type(&item);
+        let count_ptr = LLVMBuildStructGEP2(self.builder, header, ptr, 0, cstr!(""));
+        let capacity_ptr = LLVMBuildStructGEP2(self.builder, header, ptr, 1, cstr!(""));
+        let items_ptr = LLVMBuildStructGEP2(self.builder, header, ptr, 2, cstr!(""));
+        let count = LLVMBuildLoad2(self.builder, LLVMInt64Type(), count_ptr, cstr!(""));
+        let capacity = LLVMBuildLoad2(self.builder, LLVMInt64Type(), capacity_ptr, cstr!(""));
+
+        let func = LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.builder));
+        let grow = LLVMAppendBasicBlock(func, cstr!("append.grow"));
+        let add = LLVMAppendBasicBlock(func, cstr!("append.add"));
+        let full = LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntEQ,
+            count,
+            capacity,
+            cstr!(""),
+        );
+        LLVMBuildCondBr(self.builder, full, grow, add);
+
+        LLVMPositionBuilderAtEnd(self.builder, grow);
+        let zero = LLVMConstInt(LLVMInt64Type(), 0, 0);
+        let empty = LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntEQ,
+            capacity,
+            zero,
+            cstr!(""),
+        );
+        let doubled = LLVMBuildMul(
+            self.builder,
+            capacity,
+            LLVMConstInt(LLVMInt64Type(), 2, 0),
+            cstr!(""),
+        );
+        let capacity = LLVMBuildSelect(
+            self.builder,
+            empty,
+            LLVMConstInt(LLVMInt64Type(), 4, 0),
+            doubled,
+            cstr!(""),
+        );
+        let bytes = LLVMBuildMul(
+            self.builder,
+            capacity,
+            LLVMSizeOf(llvm_type(&item)),
+            cstr!(""),
+        );
+        let items = self.growing_items(ptr, &item);
+        let string = LLVMPointerType(LLVMInt8Type(), 0);
+        let items = LLVMBuildBitCast(self.builder, items, string, cstr!(""));
+        let items = self.call_c_fn("realloc", vec![items, bytes]);
+        let items_ty = LLVMPointerType(llvm_type(&item), 0);
+        let items = LLVMBuildBitCast(self.builder, items, items_ty, cstr!(""));
+        LLVMBuildStore(self.builder, items, items_ptr);
+        LLVMBuildStore(self.builder, capacity, capacity_ptr);
+        LLVMBuildBr(self.builder, add);
+
+        LLVMPositionBuilderAtEnd(self.builder, add);
+        let items = self.growing_items(ptr, &item);
+        let mut indices = [count];
+        let slot = LLVMBuildInBoundsGEP2(
+            self.builder,
+            llvm_type(&item),
+            items,
+            indices.as_mut_ptr(),
+            1,
+            cstr!(""),
+        );
+        self.move_value(store.value, slot, *item);
+        let one = LLVMConstInt(LLVMInt64Type(), 1, 0);
+        let count = LLVMBuildAdd(self.builder, count, one, cstr!(""));
+        LLVMBuildStore(self.builder, count, count_ptr);
+    }
+
+    /// The items of the list that grows at `ptr`.
+    unsafe fn growing_items(&mut self, ptr: LLVMValueRef, item: &VariableType) -> LLVMValueRef {
+        let header = growing_list_type(item);
+        let items = LLVMBuildStructGEP2(self.builder, header, ptr, 2, cstr!(""));
+        let items_ty = LLVMPointerType(llvm_type(item), 0);
+        LLVMBuildLoad2(self.builder, items_ty, items, cstr!(""))
+    }
+}
@@ -0,0 +1,145 @@
+//! Records, stored as LLVM structs with one member per field, in the order they were defined.
+//! Overlays have no member of their own and are reached by casting a pointer to the field they
+//! redefine.
+
+use llvm_sys::{
+    core::{
+        LLVMBuildBitCast, LLVMBuildStructGEP2, LLVMConstInt, LLVMConstNull, LLVMConstStruct,
+        LLVMInt1Type, LLVMInt8Type, LLVMPointerType,
+    },
+    prelude::LLVMValueRef,
+};
+
+use crate::node::{Access, Expr, Figurative, Stmt, Store, VariableType};
+
+use super::{llvm_type, CodeGen};
+
+impl<T> CodeGen<T>
+where
+    T: Iterator<Item = Stmt>,
+{
+    /// The value of a variable that isn't given one, like a record. As with COBOL's
+    /// `INITIALIZE`, text is blank and numbers are zero.
+    pub unsafe fn const_default(&mut self, ty: &VariableType) -> LLVMValueRef {
+        match ty {
+            VariableType::Record(fields) => {
+                let mut values = fields
+                    .iter()
+                    .filter(|field| field.redefines.is_none())
+                    .map(|field| self.const_default(&field.ty))
+                    .collect::<Vec<_>>();
+                LLVMConstStruct(values.as_mut_ptr(), values.len() as u32, 1)
+            }
+            VariableType::List { item, length, .. } => self.const_list(item, *length),
+            VariableType::Table { .. } => self.const_table(),
+            VariableType::Boolean => LLVMConstInt(LLVMInt1Type(), 0, 0),
+            VariableType::Choice { .. } => LLVMConstInt(LLVMInt8Type(), 0, 0),
+            VariableType::String => self.const_string(b""),
+            VariableType::Function { .. } => LLVMConstNull(llvm_type(ty)),
+            ty if ty.is_numeric() => self.const_figurative(Figurative::Zeros, ty),
+            ty => self.const_figurative(Figurative::Spaces, ty),
+        }
+    }
+
+    /// A pointer to stored field number `index` of the record of type `record` at `ptr`.
+    pub unsafe fn field_ptr(
+        &mut self,
+        ptr: LLVMValueRef,
+        record: &VariableType,
+        index: usize,
+    ) -> LLVMValueRef {
+        LLVMBuildStructGEP2(
+            self.builder,
+            llvm_type(record),
+            ptr,
+            index as u32,
+            cstr!(""),
+        )
+    }
+
+    /// Where the variable, field, item or entry named by `expr` is stored, and its type.
+    pub unsafe fn member_ptr(&mut self, expr: &Expr) -> (LLVMValueRef, VariableType) {
+        match expr {
+            Expr::Variable(reference) => {
+                let Some(variable) = self.variable_ptr(reference.id) else {
+                    panic!(
+                        "Variable {:?} was resolved but never defined.",
+                        reference.name
+                    );
+                };
+                variable
+            }
+            Expr::Member(member) => {
+                let (ptr, record) = self.member_ptr(&member.record);
+                self.select_field(ptr, &record, &member.field)
+            }
+            Expr::Item(item) => {
+                let (ptr, list) = self.member_ptr(&item.list);
+                self.item_ptr(ptr, &list, &item.index)
+            }
+            Expr::Entry(entry) => {
+                let (ptr, table) = self.member_ptr(&entry.table);
+                self.entry_ptr(ptr, &table, &entry.key, false)
+            }
+            expr => panic!("{expr:?} is not stored anywhere."),
+        }
+    }
+
+    /// Where a `MOVE` or arithmetic verb stores its result, and the type stored there.
+    pub unsafe fn target_ptr(&mut self, store: &Store) -> (LLVMValueRef, VariableType) {
+        let Some((mut ptr, mut ty)) = store.id.and_then(|id| self.variable_ptr(id)) else {
+            panic!("Undefined var {:?}.", store.target);
+        };
+        for access in &store.path {
+            (ptr, ty) = match access {
+                Access::Field(field) => self.select_field(ptr, &ty, field),
+                Access::Item(index) => self.item_ptr(ptr, &ty, index),
+                // Storing into an entry adds it if the table doesn't have it yet.
+                Access::Entry(key) => self.entry_ptr(ptr, &ty, key, true),
+            };
+        }
+        (ptr, ty)
+    }
+
+    /// The type `store` stores into, without working out where that is.
+    pub unsafe fn target_type(&mut self, store: &Store) -> VariableType {
+        let Some((_, mut ty)) = store.id.and_then(|id| self.variable_ptr(id)) else {
+            panic!("Undefined var {:?}.", store.target);
+        };
+        for access in &store.path {
+            ty = match (access, ty) {
+                (Access::Field(field), ty) => match ty.field(field) {
+                    Some(field) => field.ty.clone(),
+                    None => panic!("A {ty:?} has no field {field:?}."),
+                },
+                (Access::Item(_), VariableType::List { item, .. }) => *item,
+                (Access::Entry(_), VariableType::Table { value, .. }) => *value,
+                (_, ty) => panic!("Cannot store into an item or entry of a {ty:?}."),
+            };
+        }
+        ty
+    }
+
+    /// A pointer to the field `name` of the record of type `record` at `ptr`, and its type.
+    pub unsafe fn select_field(
+        &mut self,
+        ptr: LLVMValueRef,
+        record: &VariableType,
+        name: &str,
+    ) -> (LLVMValueRef, VariableType) {
+        let (Some(field), Some(slot)) = (record.field(name), record.slot(name)) else {
+            panic!("A {record:?} has no field {name:?}.");
+        };
+        let field = field.clone();
+        let ptr = self.field_ptr(ptr, record, slot);
+        if field.redefines.is_none() {
+            return (ptr, field.ty);
+        }
+        // An overlay reads the storage of the field it redefines as its own type.
+        let overlay = LLVMPointerType(llvm_type(&field.ty), 0);
+        (
+            LLVMBuildBitCast(self.builder, ptr, overlay, cstr!("")),
+            field.ty,
+        )
+    }
+}
@@ -0,0 +1,410 @@
+//! `SORT` and `SEARCH`. A `SORT` hands `qsort` a pointer to each item, with a comparator made
+//! for its keys. Items that compare equal are ordered by where they were, which keeps the sort
+//! stable.
+
+use llvm_sys::{
+    core::{
+        LLVMAddFunction, LLVMAppendBasicBlock, LLVMBuildAdd, LLVMBuildBitCast, LLVMBuildBr,
+        LLVMBuildCondBr, LLVMBuildICmp, LLVMBuildInBoundsGEP2, LLVMBuildLShr, LLVMBuildLoad2,
+        LLVMBuildMul, LLVMBuildPtrToInt, LLVMBuildRet, LLVMBuildSelect, LLVMBuildStore,
+        LLVMBuildStructGEP2, LLVMConstInt, LLVMConstNull, LLVMFunctionType,
+        LLVMGetBasicBlockParent, LLVMGetInsertBlock, LLVMGetParam, LLVMInt32Type, LLVMInt64Type,
+        LLVMInt8Type, LLVMPointerType, LLVMPositionBuilderAtEnd, LLVMSetLinkage, LLVMSizeOf,
+        LLVMStructType,
+    },
+    prelude::{LLVMTypeRef, LLVMValueRef},
+    LLVMIntPredicate, LLVMLinkage,
+};
+
+use crate::node::{BinaryOperators, Rounding, Search, Sort, SortKey, Stmt, VariableType};
+
+use super::{llvm_type, table::table_type, CodeGen};
+
+/// What `qsort` sorts: the key of a table entry, null for a list, and a pointer to the item or
+/// value.
+unsafe fn sort_entry_type() -> LLVMTypeRef {
+    let string = LLVMPointerType(LLVMInt8Type(), 0);
+    let mut members = [string, string];
+    LLVMStructType(members.as_mut_ptr(), 2, 0)
+}
+
+impl<T> CodeGen<T>
+where
+    T: Iterator<Item = Stmt>,
+{
+    /// Sorts pointers to the items, then moves the items into their new places through a
+    /// buffer, as an item can be moved onto one that hasn't moved yet. The entries of a table
+    /// take their keys along and the table is indexed again.
+    pub unsafe fn visit_sort(&mut self, sort: Sort) {
+        let (ptr, collection) = self.member_ptr(&sort.collection);
+        let (count, element) = match &collection {
+            VariableType::List { item, .. } => (self.item_count(ptr, &collection), item),
+            VariableType::Table { value, .. } => {
+                let count = LLVMBuildStructGEP2(self.builder, table_type(), ptr, 0, cstr!(""));
+                let count = LLVMBuildLoad2(self.builder, LLVMInt64Type(), count, cstr!(""));
+                (count, value)
+            }
+            ty => panic!("Can't sort a {ty:?}."),
+        };
+        let is_table = matches!(collection, VariableType::Table { .. });
+        let element_ty = llvm_type(element);
+        let string = LLVMPointerType(LLVMInt8Type(), 0);
+
+        // Nothing to do for fewer than two items, which also keeps an empty list's null
+        // pointer away from `memmove`.
+        let func = LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.builder));
+        let sorting = LLVMAppendBasicBlock(func, cstr!("sort"));
+        let end = LLVMAppendBasicBlock(func, cstr!("sort.end"));
+        let several = LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntUGT,
+            count,
+            LLVMConstInt(LLVMInt64Type(), 1, 0),
+            cstr!(""),
+        );
+        LLVMBuildCondBr(self.builder, several, sorting, end);
+        LLVMPositionBuilderAtEnd(self.builder, sorting);
+
+        let entry_ty = sort_entry_type();
+        let entry_size = LLVMSizeOf(entry_ty);
+        let bytes = LLVMBuildMul(self.builder, count, entry_size, cstr!(""));
+        let raw_entries = self.call_c_fn("malloc", vec![bytes]);
+        let entries_ty = LLVMPointerType(entry_ty, 0);
+        let entries = LLVMBuildBitCast(self.builder, raw_entries, entries_ty, cstr!(""));
+        self.count_loop(count, &mut |cg, index| {
+            let (key, item) = if is_table {
+                let key = cg.entry_key(ptr, index);
+                (key, cg.value_ptr(ptr, element_ty, index))
+            } else {
+                (LLVMConstNull(string), cg.item_at(ptr, &collection, index))
+            };
+            let mut indices = [index];
+            let entry = LLVMBuildInBoundsGEP2(
+                cg.builder,
+                entry_ty,
+                entries,
+                indices.as_mut_ptr(),
+                1,
+                cstr!(""),
+            );
+            let key_ptr = LLVMBuildStructGEP2(cg.builder, entry_ty, entry, 0, cstr!(""));
+            LLVMBuildStore(cg.builder, key, key_ptr);
+            let item_ptr = LLVMBuildStructGEP2(cg.builder, entry_ty, entry, 1, cstr!(""));
+            let item = LLVMBuildBitCast(cg.builder, item, string, cstr!(""));
+            LLVMBuildStore(cg.builder, item, item_ptr);
+        });
+
+        let comparator = self.sort_comparator(&collection, &sort.keys);
+        let comparator = LLVMBuildBitCast(self.builder, comparator, string, cstr!(""));
+        self.call_c_fn("qsort", vec![raw_entries, count, entry_size, comparator]);
+
+        let size = LLVMSizeOf(element_ty);
+        let bytes = LLVMBuildMul(self.builder, count, size, cstr!(""));
+        let buffer = self.call_c_fn("malloc", vec![bytes]);
+        let keys = is_table.then(|| {
+            let keys = LLVMBuildStructGEP2(self.builder, table_type(), ptr, 2, cstr!(""));
+            LLVMBuildLoad2(self.builder, LLVMPointerType(string, 0), keys, cstr!(""))
+        });
+        self.count_loop(count, &mut |cg, index| {
+            let mut indices = [index];
+            let entry = LLVMBuildInBoundsGEP2(
+                cg.builder,
+                entry_ty,
+                entries,
+                indices.as_mut_ptr(),
+                1,
+                cstr!(""),
+            );
+            let item = LLVMBuildStructGEP2(cg.builder, entry_ty, entry, 1, cstr!(""));
+            let item = LLVMBuildLoad2(cg.builder, string, item, cstr!(""));
+            let offset = LLVMBuildMul(cg.builder, index, size, cstr!(""));
+            let mut indices = [offset];
+            let place = LLVMBuildInBoundsGEP2(
+                cg.builder,
+                LLVMInt8Type(),
+                buffer,
+                indices.as_mut_ptr(),
+                1,
+                cstr!(""),
+            );
+            cg.call_c_fn("memmove", vec![place, item, size]);
+            if let Some(keys) = keys {
+                let key = LLVMBuildStructGEP2(cg.builder, entry_ty, entry, 0, cstr!(""));
+                let key = LLVMBuildLoad2(cg.builder, string, key, cstr!(""));
+                let mut indices = [index];
+                let place = LLVMBuildInBoundsGEP2(
+                    cg.builder,
+                    string,
+                    keys,
+                    indices.as_mut_ptr(),
+                    1,
+                    cstr!(""),
+                );
+                LLVMBuildStore(cg.builder, key, place);
+            }
+        });
+        let zero = LLVMConstInt(LLVMInt64Type(), 0, 0);
+        let first = match is_table {
+            true => self.value_ptr(ptr, element_ty, zero),
+            false => self.item_at(ptr, &collection, zero),
+        };
+        let first = LLVMBuildBitCast(self.builder, first, string, cstr!(""));
+        self.call_c_fn("memmove", vec![first, buffer, bytes]);
+        self.call_c_fn("free", vec![buffer]);
+        self.call_c_fn("free", vec![raw_entries]);
+        if is_table {
+            self.reindex_table(ptr);
+        }
+        LLVMBuildBr(self.builder, end);
+        LLVMPositionBuilderAtEnd(self.builder, end);
+    }
+
+    /// A comparator for `qsort` over the entries of `collection`, returning -1, 0 or 1. Each
+    /// key is only looked at when the ones before it are equal, and the place of the items
+    /// decides when they all are.
+    unsafe fn sort_comparator(
+        &mut self,
+        collection: &VariableType,
+        keys: &[SortKey],
+    ) -> LLVMValueRef {
+        let string = LLVMPointerType(LLVMInt8Type(), 0);
+        let mut params = [string, string];
+        let ty = LLVMFunctionType(LLVMInt32Type(), params.as_mut_ptr(), 2, 0);
+        let func = LLVMAddFunction(self.cur_module.unwrap(), cstr!("cobalt.sort.compare"), ty);
+        LLVMSetLinkage(func, LLVMLinkage::LLVMInternalLinkage);
+        let resume = LLVMGetInsertBlock(self.builder);
+
+        let entry = LLVMAppendBasicBlock(func, cstr!("entry"));
+        let before = LLVMAppendBasicBlock(func, cstr!("before"));
+        let after = LLVMAppendBasicBlock(func, cstr!("after"));
+        LLVMPositionBuilderAtEnd(self.builder, before);
+        LLVMBuildRet(self.builder, LLVMConstInt(LLVMInt32Type(), -1i64 as u64, 1));
+        LLVMPositionBuilderAtEnd(self.builder, after);
+        LLVMBuildRet(self.builder, LLVMConstInt(LLVMInt32Type(), 1, 0));
+
+        LLVMPositionBuilderAtEnd(self.builder, entry);
+        let entry_ty = LLVMPointerType(sort_entry_type(), 0);
+        let a = LLVMBuildBitCast(self.builder, LLVMGetParam(func, 0), entry_ty, cstr!(""));
+        let b = LLVMBuildBitCast(self.builder, LLVMGetParam(func, 1), entry_ty, cstr!(""));
+        for key in keys {
+            let l = self.sort_operand(a, collection, key.field.as_deref());
+            let r = self.sort_operand(b, collection, key.field.as_deref());
+            let less = self.compare(BinaryOperators::LessThan, l.clone(), r.clone());
+            let greater = self.compare(BinaryOperators::GreaterThan, l, r);
+            let (first, second) = match key.descending {
+                true => (greater, less),
+                false => (less, greater),
+            };
+            let undecided = LLVMAppendBasicBlock(func, cstr!("undecided"));
+            let next = LLVMAppendBasicBlock(func, cstr!("next"));
+            LLVMBuildCondBr(self.builder, first, before, undecided);
+            LLVMPositionBuilderAtEnd(self.builder, undecided);
+            LLVMBuildCondBr(self.builder, second, after, next);
+            LLVMPositionBuilderAtEnd(self.builder, next);
+        }
+
+        let place = |builder, entry| {
+            let item = LLVMBuildStructGEP2(builder, sort_entry_type(), entry, 1, cstr!(""));
+            let item = LLVMBuildLoad2(builder, string, item, cstr!(""));
+            LLVMBuildPtrToInt(builder, item, LLVMInt64Type(), cstr!(""))
+        };
+        let (l, r) = (place(self.builder, a), place(self.builder, b));
+        let earlier = LLVMBuildICmp(self.builder, LLVMIntPredicate::LLVMIntULT, l, r, cstr!(""));
+        let undecided = LLVMAppendBasicBlock(func, cstr!("undecided"));
+        let same = LLVMAppendBasicBlock(func, cstr!("same"));
+        LLVMBuildCondBr(self.builder, earlier, before, undecided);
+        LLVMPositionBuilderAtEnd(self.builder, undecided);
+        let later = LLVMBuildICmp(self.builder, LLVMIntPredicate::LLVMIntUGT, l, r, cstr!(""));
+        LLVMBuildCondBr(self.builder, later, after, same);
+        LLVMPositionBuilderAtEnd(self.builder, same);
+        LLVMBuildRet(self.builder, LLVMConstInt(LLVMInt32Type(), 0, 0));
+
+        LLVMPositionBuilderAtEnd(self.builder, resume);
+        func
+    }
+
+    /// The value a sort key compares for the sort entry at `entry`.
+    unsafe fn sort_operand(
+        &mut self,
+        entry: LLVMValueRef,
+        collection: &VariableType,
+        field: Option<&str>,
+    ) -> (LLVMValueRef, VariableType) {
+        let string = LLVMPointerType(LLVMInt8Type(), 0);
+        let element = match (collection, field) {
+            (VariableType::Table { .. }, None) => {
+                let key = LLVMBuildStructGEP2(self.builder, sort_entry_type(), entry, 0, cstr!(""));
+                let key = LLVMBuildLoad2(self.builder, string, key, cstr!(""));
+                return (key, VariableType::String);
+            }
+            (VariableType::Table { value, .. }, _) => value,
+            (VariableType::List { item, .. }, _) => item,
+            (ty, _) => panic!("Can't sort a {ty:?}."),
+        };
+        let item = LLVMBuildStructGEP2(self.builder, sort_entry_type(), entry, 1, cstr!(""));
+        let item = LLVMBuildLoad2(self.builder, string, item, cstr!(""));
+        let item_ty = LLVMPointerType(llvm_type(element), 0);
+        let item = LLVMBuildBitCast(self.builder, item, item_ty, cstr!(""));
+        self.element_operand(item, element, field)
+    }
+
+    /// The item of type `element` at `ptr`, or its field `field`.
+    unsafe fn element_operand(
+        &mut self,
+        ptr: LLVMValueRef,
+        element: &VariableType,
+        field: Option<&str>,
+    ) -> (LLVMValueRef, VariableType) {
+        match field {
+            Some(field) => {
+                let (ptr, ty) = self.select_field(ptr, element, field);
+                self.load(ptr, ty)
+            }
+            None => self.load(ptr, element.clone()),
+        }
+    }
+
+    /// `SEARCH` goes through the items one by one, unless the list is `SORTED` by what is
+    /// searched for. Then the first item that isn't before the value is found by halving, and
+    /// it is the one when it is equal.
+    pub unsafe fn visit_search(&mut self, search: Search) {
+        let (ptr, list) = self.member_ptr(&search.collection);
+        let VariableType::List { item, sorted, .. } = &list else {
+            panic!("Can't search a {list:?}.");
+        };
+        let field = search.key(&list);
+        let searched = match field {
+            Some(field) => item.field(field).map(|field| field.ty.clone()),
+            None => Some((**item).clone()),
+        };
+        let Some(searched) = searched else {
+            panic!("A {item:?} has no field {field:?}.");
+        };
+        let value = self.visit_operand(search.giving.value.clone(), &searched);
+        let count = self.item_count(ptr, &list);
+
+        let func = LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.builder));
+        let one = LLVMConstInt(LLVMInt64Type(), 1, 0);
+        let zero = LLVMConstInt(LLVMInt64Type(), 0, 0);
+        let result = self.entry_alloca(LLVMInt64Type(), "search.result");
+        LLVMBuildStore(self.builder, zero, result);
+        let low = self.entry_alloca(LLVMInt64Type(), "search.low");
+        LLVMBuildStore(self.builder, zero, low);
+        let high = self.entry_alloca(LLVMInt64Type(), "search.high");
+        LLVMBuildStore(self.builder, count, high);
+
+        let cond = LLVMAppendBasicBlock(func, cstr!("search.cond"));
+        let body = LLVMAppendBasicBlock(func, cstr!("search.body"));
+        let done = LLVMAppendBasicBlock(func, cstr!("search.done"));
+        let end = LLVMAppendBasicBlock(func, cstr!("search.end"));
+        LLVMBuildBr(self.builder, cond);
+        LLVMPositionBuilderAtEnd(self.builder, cond);
+        let l = LLVMBuildLoad2(self.builder, LLVMInt64Type(), low, cstr!(""));
+        let h = LLVMBuildLoad2(self.builder, LLVMInt64Type(), high, cstr!(""));
+        let more = LLVMBuildICmp(self.builder, LLVMIntPredicate::LLVMIntULT, l, h, cstr!(""));
+        LLVMBuildCondBr(self.builder, more, body, done);
+        LLVMPositionBuilderAtEnd(self.builder, body);
+
+        match sorted {
+            Some(key) if key.field.as_deref() == field => {
+                let sum = LLVMBuildAdd(self.builder, l, h, cstr!(""));
+                let middle = LLVMBuildLShr(self.builder, sum, one, cstr!(""));
+                let at = self.item_at(ptr, &list, middle);
+                let element = self.element_operand(at, item, field);
+                let op = match key.descending {
+                    true => BinaryOperators::GreaterThan,
+                    false => BinaryOperators::LessThan,
+                };
+                let before = self.compare(op, element, value.clone());
+                let past = LLVMBuildAdd(self.builder, middle, one, cstr!(""));
+                let next_low = LLVMBuildSelect(self.builder, before, past, l, cstr!(""));
+                let next_high = LLVMBuildSelect(self.builder, before, h, middle, cstr!(""));
+                LLVMBuildStore(self.builder, next_low, low);
+                LLVMBuildStore(self.builder, next_high, high);
+                LLVMBuildBr(self.builder, cond);
+
+                LLVMPositionBuilderAtEnd(self.builder, done);
+                let check = LLVMAppendBasicBlock(func, cstr!("search.check"));
+                let inside = LLVMBuildICmp(
+                    self.builder,
+                    LLVMIntPredicate::LLVMIntULT,
+                    l,
+                    count,
+                    cstr!(""),
+                );
+                LLVMBuildCondBr(self.builder, inside, check, end);
+                LLVMPositionBuilderAtEnd(self.builder, check);
+                let position = l;
+                let at = self.item_at(ptr, &list, position);
+                let element = self.element_operand(at, item, field);
+                let equal = self.compare(BinaryOperators::EqualTo, element, value);
+                let number = LLVMBuildAdd(self.builder, position, one, cstr!(""));
+                let number = LLVMBuildSelect(self.builder, equal, number, zero, cstr!(""));
+                LLVMBuildStore(self.builder, number, result);
+                LLVMBuildBr(self.builder, end);
+            }
+            _ => {
+                let at = self.item_at(ptr, &list, l);
+                let element = self.element_operand(at, item, field);
+                let equal = self.compare(BinaryOperators::EqualTo, element, value);
+                let found = LLVMAppendBasicBlock(func, cstr!("search.found"));
+                let next = LLVMAppendBasicBlock(func, cstr!("search.next"));
+                let number = LLVMBuildAdd(self.builder, l, one, cstr!(""));
+                LLVMBuildCondBr(self.builder, equal, found, next);
+                LLVMPositionBuilderAtEnd(self.builder, found);
+                LLVMBuildStore(self.builder, number, result);
+                LLVMBuildBr(self.builder, end);
+                LLVMPositionBuilderAtEnd(self.builder, next);
+                LLVMBuildStore(self.builder, number, low);
+                LLVMBuildBr(self.builder, cond);
+                LLVMPositionBuilderAtEnd(self.builder, done);
+                LLVMBuildBr(self.builder, end);
+            }
+        }
+
+        LLVMPositionBuilderAtEnd(self.builder, end);
+        let number = LLVMBuildLoad2(self.builder, LLVMInt64Type(), result, cstr!(""));
+        let (target, kind) = self.target_ptr(&search.giving);
+        match kind {
+            VariableType::Fixed { digits, scale } => {
+                let integer = VariableType::Integer;
+                let truncate = Rounding::Truncate;
+                self.store_fixed(number, &integer, (digits, scale), truncate, target, false);
+            }
+            kind => {
+                let number = self.convert(number, &VariableType::Integer, &kind);
+                LLVMBuildStore(self.builder, number, target);
+            }
+        }
+    }
+
+    /// Runs `body` with every index from 0 up to `count`, which must not be 0.
+    unsafe fn count_loop(
+        &mut self,
+        count: LLVMValueRef,
+        body: &mut dyn FnMut(&mut Self, LLVMValueRef),
+    ) {
+        let func = LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.builder));
+        let index = self.entry_alloca(LLVMInt64Type(), "index");
+        LLVMBuildStore(self.builder, LLVMConstInt(LLVMInt64Type(), 0, 0), index);
+        let repeat = LLVMAppendBasicBlock(func, cstr!("loop"));
+        let end = LLVMAppendBasicBlock(func, cstr!("loop.end"));
+        LLVMBuildBr(self.builder, repeat);
+
+        LLVMPositionBuilderAtEnd(self.builder, repeat);
+        let current = LLVMBuildLoad2(self.builder, LLVMInt64Type(), index, cstr!(""));
+        body(self, current);
+        let one = LLVMConstInt(LLVMInt64Type(), 1, 0);
+        let next = LLVMBuildAdd(self.builder, current, one, cstr!(""));
+        LLVMBuildStore(self.builder, next, index);
+        let more = LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntULT,
+            next,
+            count,
+            cstr!(""),
+        );
+        LLVMBuildCondBr(self.builder, more, repeat, end);
+        LLVMPositionBuilderAtEnd(self.builder, end);
+    }
+}
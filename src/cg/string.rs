@@ -0,0 +1,144 @@
+//! Strings built while the program runs. Joined strings and parts of strings are new strings
+//! on the heap, sized for their characters, so they can be stored in a `STRING` or moved into
+//! a `TEXT` like any other.
+
+use llvm_sys::{
+    core::{
+        LLVMAppendBasicBlock, LLVMBuildAdd, LLVMBuildCondBr, LLVMBuildGlobalStringPtr,
+        LLVMBuildICmp, LLVMBuildInBoundsGEP2, LLVMBuildOr, LLVMBuildStore, LLVMBuildSub,
+        LLVMBuildUnreachable, LLVMBuildZExt, LLVMConstInt, LLVMConstNull, LLVMGetBasicBlockParent,
+        LLVMGetInsertBlock, LLVMInt32Type, LLVMInt64Type, LLVMInt8Type, LLVMPointerType,
+        LLVMPositionBuilderAtEnd,
+    },
+    prelude::LLVMValueRef,
+    LLVMIntPredicate,
+};
+
+use crate::node::{Characters, Concat, Expr, Stmt, VariableType};
+
+use super::CodeGen;
+
+impl<T> CodeGen<T>
+where
+    T: Iterator<Item = Stmt>,
+{
+    /// Joins the values the way `DISPLAY` would show them next to each other. The string is
+    /// measured by a first `snprintf` and written by a second into memory of that size.
+    pub unsafe fn visit_concat(&mut self, concat: Concat) -> LLVMValueRef {
+        let mut parts = Vec::new();
+        flatten(Expr::Concat(concat), &mut parts);
+        let mut format = String::new();
+        let mut values = vec![];
+        for part in parts {
+            let (value, ty) = self.visit_expr(part);
+            self.display_value(value, ty, &mut format, &mut values);
+        }
+        let format = LLVMBuildGlobalStringPtr(self.builder, cstr!(format.as_bytes()), cstr!(""));
+
+        let string = LLVMPointerType(LLVMInt8Type(), 0);
+        let mut arguments = vec![
+            LLVMConstNull(string),
+            LLVMConstInt(LLVMInt64Type(), 0, 0),
+            format,
+        ];
+        arguments.extend(&values);
+        let length = self.call_c_fn("snprintf", arguments);
+        let length = LLVMBuildZExt(self.builder, length, LLVMInt64Type(), cstr!(""));
+        let one = LLVMConstInt(LLVMInt64Type(), 1, 0);
+        let size = LLVMBuildAdd(self.builder, length, one, cstr!(""));
+        let buffer = self.call_c_fn("malloc", vec![size]);
+
+        let mut arguments = vec![buffer, size, format];
+        arguments.extend(values);
+        self.call_c_fn("snprintf", arguments);
+        buffer
+    }
+
+    /// `CHARACTERS 2 THROUGH 4 OF "code"`, copied into a new string. The program stops with an
+    /// error when the string doesn't have all of them.
+    pub unsafe fn visit_characters(&mut self, characters: Characters) -> LLVMValueRef {
+        let (text, ty) = self.visit_expr(*characters.text);
+        let length = self.text_length(text, &ty);
+        let (from, _) = self.visit_index(&characters.from);
+        let (through, _) = self.visit_index(&characters.through);
+
+        // Characters are numbered from 1, so 0 wraps around and is caught with the rest.
+        let one = LLVMConstInt(LLVMInt64Type(), 1, 0);
+        let start = LLVMBuildSub(self.builder, from, one, cstr!(""));
+        let checks = [
+            (LLVMIntPredicate::LLVMIntUGE, start, length),
+            (LLVMIntPredicate::LLVMIntUGT, through, length),
+            (LLVMIntPredicate::LLVMIntSLT, through, from),
+        ];
+        let outside = checks
+            .into_iter()
+            .map(|(predicate, l, r)| LLVMBuildICmp(self.builder, predicate, l, r, cstr!("")))
+            .reduce(|l, r| LLVMBuildOr(self.builder, l, r, cstr!("")))
+            .unwrap();
+        let func = LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.builder));
+        let out_of_bounds = LLVMAppendBasicBlock(func, cstr!("characters.out_of_bounds"));
+        let in_bounds = LLVMAppendBasicBlock(func, cstr!("characters.in_bounds"));
+        LLVMBuildCondBr(self.builder, outside, out_of_bounds, in_bounds);
+
+        LLVMPositionBuilderAtEnd(self.builder, out_of_bounds);
+        let format = LLVMBuildGlobalStringPtr(
+            self.builder,
+            cstr!("%s: CHARACTERS %lld THROUGH %lld are out of bounds, the string has %lld character(s).\n"),
+            cstr!(""),
+        );
+        let location = LLVMBuildGlobalStringPtr(
+            self.builder,
+            cstr!(characters.from.location.as_bytes()),
+            cstr!(""),
+        );
+        self.call_c_fn("printf", vec![format, location, from, through, length]);
+        self.call_c_fn("exit", vec![LLVMConstInt(LLVMInt32Type(), 1, 0)]);
+        LLVMBuildUnreachable(self.builder);
+
+        LLVMPositionBuilderAtEnd(self.builder, in_bounds);
+        let count = LLVMBuildSub(self.builder, through, start, cstr!(""));
+        let size = LLVMBuildAdd(self.builder, count, one, cstr!(""));
+        let buffer = self.call_c_fn("malloc", vec![size]);
+        let mut indices = [start];
+        let first = LLVMBuildInBoundsGEP2(
+            self.builder,
+            LLVMInt8Type(),
+            text,
+            indices.as_mut_ptr(),
+            1,
+            cstr!(""),
+        );
+        self.call_c_fn("memmove", vec![buffer, first, count]);
+        let mut indices = [count];
+        let nul = LLVMBuildInBoundsGEP2(
+            self.builder,
+            LLVMInt8Type(),
+            buffer,
+            indices.as_mut_ptr(),
+            1,
+            cstr!(""),
+        );
+        LLVMBuildStore(self.builder, LLVMConstInt(LLVMInt8Type(), 0, 0), nul);
+        buffer
+    }
+
+    /// How many characters the string `value` of type `ty` has. A `TEXT` always has all of them,
+    /// including the spaces it is padded with.
+    pub unsafe fn text_length(&mut self, value: LLVMValueRef, ty: &VariableType) -> LLVMValueRef {
+        match ty {
+            VariableType::Text { length } => LLVMConstInt(LLVMInt64Type(), *length as u64, 0),
+            _ => self.call_c_fn("strlen", vec![value]),
+        }
+    }
+}
+
+/// The values of a chain of `&`, from left to right.
+fn flatten(expr: Expr, parts: &mut Vec<Expr>) {
+    match expr {
+        Expr::Concat(concat) => {
+            flatten(*concat.l, parts);
+            flatten(*concat.r, parts);
+        }
+        expr => parts.push(expr),
+    }
+}
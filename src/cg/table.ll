@@ -0,0 +1,221 @@
+; The runtime of `TABLE`s, linked into programs that use one.
+;
+; A table keeps its keys and values in the order they were added, so `FOR EACH` goes through
+; them in that order. The slots are an open-addressed index into them: each holds the number of
+; an entry plus one, or 0 when it is empty. There are always twice as many slots as there is
+; room for entries, and both are powers of two.
+;
+; The values can be of any type, the compiler passes their size in bytes.
+
+%cobalt.table = type <{ i64, i64, i8**, i8*, i64* }>
+
+declare i8* @realloc(i8*, i64)
+declare i8* @calloc(i64, i64)
+declare void @free(i8*)
+declare i8* @strdup(i8*)
+declare i32 @strcmp(i8*, i8*)
+declare i8* @memset(i8*, i32, i64)
+
+; FNV-1a over the characters of `key`.
+define internal i64 @cobalt.table.hash(i8* %key) {
+entry:
+  br label %loop
+
+loop:
+  %hash = phi i64 [ -3750763034362895579, %entry ], [ %next, %mix ]
+  %ptr = phi i8* [ %key, %entry ], [ %after, %mix ]
+  %char = load i8, i8* %ptr
+  %ended = icmp eq i8 %char, 0
+  br i1 %ended, label %end, label %mix
+
+mix:
+  %byte = zext i8 %char to i64
+  %mixed = xor i64 %hash, %byte
+  %next = mul i64 %mixed, 1099511628211
+  %after = getelementptr inbounds i8, i8* %ptr, i64 1
+  br label %loop
+
+end:
+  ret i64 %hash
+}
+
+; The slot that refers to `key`, or the empty one where it would go. The table must have room
+; for at least one entry.
+define internal i64* @cobalt.table.probe(%cobalt.table* %table, i8* %key) {
+entry:
+  %capacity.ptr = getelementptr inbounds %cobalt.table, %cobalt.table* %table, i32 0, i32 1
+  %capacity = load i64, i64* %capacity.ptr
+  %keys.ptr = getelementptr inbounds %cobalt.table, %cobalt.table* %table, i32 0, i32 2
+  %keys = load i8**, i8*** %keys.ptr
+  %slots.ptr = getelementptr inbounds %cobalt.table, %cobalt.table* %table, i32 0, i32 4
+  %slots = load i64*, i64** %slots.ptr
+  %slot.count = shl i64 %capacity, 1
+  %mask = sub i64 %slot.count, 1
+  %hash = call i64 @cobalt.table.hash(i8* %key)
+  br label %loop
+
+loop:
+  %position = phi i64 [ %hash, %entry ], [ %next, %taken ]
+  %index = and i64 %position, %mask
+  %slot = getelementptr inbounds i64, i64* %slots, i64 %index
+  %number = load i64, i64* %slot
+  %empty = icmp eq i64 %number, 0
+  br i1 %empty, label %found, label %compare
+
+compare:
+  %entry.index = sub i64 %number, 1
+  %other.ptr = getelementptr inbounds i8*, i8** %keys, i64 %entry.index
+  %other = load i8*, i8** %other.ptr
+  %order = call i32 @strcmp(i8* %key, i8* %other)
+  %same = icmp eq i32 %order, 0
+  br i1 %same, label %found, label %taken
+
+taken:
+  %next = add i64 %index, 1
+  br label %loop
+
+found:
+  ret i64* %slot
+}
+
+; Doubles the room for entries, starting at 8, and rebuilds the slots for it.
+define internal void @cobalt.table.grow(%cobalt.table* %table, i64 %size) {
+entry:
+  %capacity.ptr = getelementptr inbounds %cobalt.table, %cobalt.table* %table, i32 0, i32 1
+  %capacity = load i64, i64* %capacity.ptr
+  %none = icmp eq i64 %capacity, 0
+  %doubled = shl i64 %capacity, 1
+  %grown = select i1 %none, i64 8, i64 %doubled
+  store i64 %grown, i64* %capacity.ptr
+
+  %pointer.size = ptrtoint i8** getelementptr (i8*, i8** null, i32 1) to i64
+  %keys.ptr = getelementptr inbounds %cobalt.table, %cobalt.table* %table, i32 0, i32 2
+  %keys = load i8**, i8*** %keys.ptr
+  %keys.raw = bitcast i8** %keys to i8*
+  %keys.bytes = mul i64 %grown, %pointer.size
+  %keys.grown.raw = call i8* @realloc(i8* %keys.raw, i64 %keys.bytes)
+  %keys.grown = bitcast i8* %keys.grown.raw to i8**
+  store i8** %keys.grown, i8*** %keys.ptr
+
+  %values.ptr = getelementptr inbounds %cobalt.table, %cobalt.table* %table, i32 0, i32 3
+  %values = load i8*, i8** %values.ptr
+  %values.bytes = mul i64 %grown, %size
+  %values.grown = call i8* @realloc(i8* %values, i64 %values.bytes)
+  store i8* %values.grown, i8** %values.ptr
+
+  %slots.ptr = getelementptr inbounds %cobalt.table, %cobalt.table* %table, i32 0, i32 4
+  %slots = load i64*, i64** %slots.ptr
+  %slots.raw = bitcast i64* %slots to i8*
+  call void @free(i8* %slots.raw)
+  %slot.count = shl i64 %grown, 1
+  %slots.grown.raw = call i8* @calloc(i64 %slot.count, i64 8)
+  %slots.grown = bitcast i8* %slots.grown.raw to i64*
+  store i64* %slots.grown, i64** %slots.ptr
+  call void @cobalt.table.rehash(%cobalt.table* %table)
+  ret void
+}
+
+; Points the slots, which must all be empty, at every entry.
+define internal void @cobalt.table.rehash(%cobalt.table* %table) {
+entry:
+  %count.ptr = getelementptr inbounds %cobalt.table, %cobalt.table* %table, i32 0, i32 0
+  %count = load i64, i64* %count.ptr
+  %keys.ptr = getelementptr inbounds %cobalt.table, %cobalt.table* %table, i32 0, i32 2
+  %keys = load i8**, i8*** %keys.ptr
+  br label %loop
+
+loop:
+  %index = phi i64 [ 0, %entry ], [ %number, %rehash ]
+  %done = icmp eq i64 %index, %count
+  br i1 %done, label %end, label %rehash
+
+rehash:
+  %key.ptr = getelementptr inbounds i8*, i8** %keys, i64 %index
+  %key = load i8*, i8** %key.ptr
+  %slot = call i64* @cobalt.table.probe(%cobalt.table* %table, i8* %key)
+  %number = add i64 %index, 1
+  store i64 %number, i64* %slot
+  br label %loop
+
+end:
+  ret void
+}
+
+; Rebuilds the slots after the entries have been put in another order.
+define void @cobalt.table.reindex(i8* %raw) {
+entry:
+  %table = bitcast i8* %raw to %cobalt.table*
+  %capacity.ptr = getelementptr inbounds %cobalt.table, %cobalt.table* %table, i32 0, i32 1
+  %capacity = load i64, i64* %capacity.ptr
+  %empty = icmp eq i64 %capacity, 0
+  br i1 %empty, label %end, label %clear
+
+clear:
+  %slots.ptr = getelementptr inbounds %cobalt.table, %cobalt.table* %table, i32 0, i32 4
+  %slots = load i64*, i64** %slots.ptr
+  %slots.raw = bitcast i64* %slots to i8*
+  %bytes = shl i64 %capacity, 4
+  call i8* @memset(i8* %slots.raw, i32 0, i64 %bytes)
+  call void @cobalt.table.rehash(%cobalt.table* %table)
+  br label %end
+
+end:
+  ret void
+}
+
+; The number of the entry for `key`, counting from 0, or -1 when there is none.
+define i64 @cobalt.table.find(i8* %raw, i8* %key) {
+entry:
+  %table = bitcast i8* %raw to %cobalt.table*
+  %capacity.ptr = getelementptr inbounds %cobalt.table, %cobalt.table* %table, i32 0, i32 1
+  %capacity = load i64, i64* %capacity.ptr
+  %empty = icmp eq i64 %capacity, 0
+  br i1 %empty, label %missing, label %probe
+
+probe:
+  %slot = call i64* @cobalt.table.probe(%cobalt.table* %table, i8* %key)
+  %number = load i64, i64* %slot
+  %index = sub i64 %number, 1
+  ret i64 %index
+
+missing:
+  ret i64 -1
+}
+
+; The number of the entry for `key`, adding one with a copy of the key if there is none. Its
+; value is left for the caller to fill in.
+define i64 @cobalt.table.insert(i8* %raw, i8* %key, i64 %size) {
+entry:
+  %table = bitcast i8* %raw to %cobalt.table*
+  %count.ptr = getelementptr inbounds %cobalt.table, %cobalt.table* %table, i32 0, i32 0
+  %count = load i64, i64* %count.ptr
+  %capacity.ptr = getelementptr inbounds %cobalt.table, %cobalt.table* %table, i32 0, i32 1
+  %capacity = load i64, i64* %capacity.ptr
+  %full = icmp eq i64 %count, %capacity
+  br i1 %full, label %grow, label %probe
+
+grow:
+  call void @cobalt.table.grow(%cobalt.table* %table, i64 %size)
+  br label %probe
+
+probe:
+  %slot = call i64* @cobalt.table.probe(%cobalt.table* %table, i8* %key)
+  %number = load i64, i64* %slot
+  %missing = icmp eq i64 %number, 0
+  br i1 %missing, label %add, label %existing
+
+existing:
+  %index = sub i64 %number, 1
+  ret i64 %index
+
+add:
+  %keys.ptr = getelementptr inbounds %cobalt.table, %cobalt.table* %table, i32 0, i32 2
+  %keys = load i8**, i8*** %keys.ptr
+  %key.ptr = getelementptr inbounds i8*, i8** %keys, i64 %count
+  %copy = call i8* @strdup(i8* %key)
+  store i8* %copy, i8** %key.ptr
+  %added = add i64 %count, 1
+  store i64 %added, i64* %slot
+  store i64 %added, i64* %count.ptr
+  ret i64 %count
+}
@@ -0,0 +1,324 @@
+//! Tables, which map `STRING` keys to values. Finding and adding entries is done by a small
+//! runtime written in LLVM IR, `table.ll`, which is linked into programs that use a table.
+
+use llvm_sys::{
+    core::{
+        LLVMAddFunction, LLVMAddIncoming, LLVMAppendBasicBlock, LLVMBuildAdd, LLVMBuildBitCast,
+        LLVMBuildBr, LLVMBuildCall2, LLVMBuildCondBr, LLVMBuildGlobalStringPtr, LLVMBuildICmp,
+        LLVMBuildInBoundsGEP2, LLVMBuildLoad2, LLVMBuildPhi, LLVMBuildStore, LLVMBuildStructGEP2,
+        LLVMBuildUnreachable, LLVMConstInt, LLVMConstNull, LLVMConstStruct,
+        LLVMCreateMemoryBufferWithMemoryRangeCopy, LLVMFunctionType, LLVMGetBasicBlockParent,
+        LLVMGetBasicBlockTerminator, LLVMGetGlobalContext, LLVMGetInsertBlock,
+        LLVMGetNamedFunction, LLVMInt32Type, LLVMInt64Type, LLVMInt8Type, LLVMPointerType,
+        LLVMPositionBuilderAtEnd, LLVMSizeOf, LLVMStructType, LLVMVoidType,
+    },
+    ir_reader::LLVMParseIRInContext,
+    prelude::{LLVMModuleRef, LLVMTypeRef, LLVMValueRef},
+    target::LLVMABISizeOfType,
+    LLVMIntPredicate,
+};
+
+use crate::node::{ForEach, Index, Stmt, VariableType};
+
+use super::{func::Function, llvm_type, var::Variable, CodeGen};
+
+const RUNTIME: &str = include_str!("table.ll");
+
+/// `%cobalt.table` in the runtime: the number of entries, the room there is for them, the
+/// keys, the values and the slots that index them.
+pub unsafe fn table_type() -> LLVMTypeRef {
+    let string = LLVMPointerType(LLVMInt8Type(), 0);
+    let mut members = [
+        LLVMInt64Type(),
+        LLVMInt64Type(),
+        LLVMPointerType(string, 0),
+        string,
+        LLVMPointerType(LLVMInt64Type(), 0),
+    ];
+    LLVMStructType(members.as_mut_ptr(), 5, 1)
+}
+
+/// The runtime, parsed into a module of its own, if any of `modules` calls into it.
+pub unsafe fn runtime(modules: &[LLVMModuleRef]) -> Option<LLVMModuleRef> {
+    let used = modules.iter().any(|module| {
+        [
+            "cobalt.table.find",
+            "cobalt.table.insert",
+            "cobalt.table.reindex",
+        ]
+        .iter()
+        .any(|name| !LLVMGetNamedFunction(*module, cstr!(*name)).is_null())
+    });
+    if !used {
+        return None;
+    }
+    let buffer = LLVMCreateMemoryBufferWithMemoryRangeCopy(
+        RUNTIME.as_ptr() as *const i8,
+        RUNTIME.len(),
+        cstr!("table.ll"),
+    );
+    let mut module = std::ptr::null_mut();
+    let mut message = std::ptr::null_mut();
+    if LLVMParseIRInContext(LLVMGetGlobalContext(), buffer, &mut module, &mut message) != 0 {
+        let message = std::ffi::CStr::from_ptr(message).to_string_lossy();
+        panic!("The table runtime doesn't parse: {message}");
+    }
+    Some(module)
+}
+
+impl<T> CodeGen<T>
+where
+    T: Iterator<Item = Stmt>,
+{
+    /// A table starts without any entries or memory for them.
+    pub unsafe fn const_table(&mut self) -> LLVMValueRef {
+        let string = LLVMPointerType(LLVMInt8Type(), 0);
+        let zero = LLVMConstInt(LLVMInt64Type(), 0, 0);
+        let mut members = [
+            zero,
+            zero,
+            LLVMConstNull(LLVMPointerType(string, 0)),
+            LLVMConstNull(string),
+            LLVMConstNull(LLVMPointerType(LLVMInt64Type(), 0)),
+        ];
+        LLVMConstStruct(members.as_mut_ptr(), 5, 1)
+    }
+
+    /// Rebuilds the index of the table at `ptr` after its entries have been moved around.
+    pub unsafe fn reindex_table(&mut self, ptr: LLVMValueRef) {
+        let (ty, func) = self.table_fn("cobalt.table.reindex");
+        let string = LLVMPointerType(LLVMInt8Type(), 0);
+        let table = LLVMBuildBitCast(self.builder, ptr, string, cstr!(""));
+        let mut arguments = [table];
+        LLVMBuildCall2(self.builder, ty, func, arguments.as_mut_ptr(), 1, cstr!(""));
+    }
+
+    /// Declares the runtime function `name` in the current module. The table is passed as an
+    /// `i8*`, so the declaration doesn't need the runtime's struct type.
+    unsafe fn table_fn(&mut self, name: &str) -> (LLVMTypeRef, LLVMValueRef) {
+        let module = self.cur_module.unwrap();
+        let string = LLVMPointerType(LLVMInt8Type(), 0);
+        let (ret, mut params) = match name {
+            "cobalt.table.find" => (LLVMInt64Type(), vec![string, string]),
+            "cobalt.table.insert" => (LLVMInt64Type(), vec![string, string, LLVMInt64Type()]),
+            _ => (LLVMVoidType(), vec![string]),
+        };
+        let ty = LLVMFunctionType(ret, params.as_mut_ptr(), params.len() as u32, 0);
+        let existing = LLVMGetNamedFunction(module, cstr!(name));
+        if !existing.is_null() {
+            return (ty, existing);
+        }
+        (ty, LLVMAddFunction(module, cstr!(name), ty))
+    }
+
+    /// The number of the entry for `key` in the table at `ptr`, counting from 0, or -1.
+    pub unsafe fn find_entry(&mut self, ptr: LLVMValueRef, key: LLVMValueRef) -> LLVMValueRef {
+        let (ty, func) = self.table_fn("cobalt.table.find");
+        let string = LLVMPointerType(LLVMInt8Type(), 0);
+        let table = LLVMBuildBitCast(self.builder, ptr, string, cstr!(""));
+        let mut arguments = [table, key];
+        LLVMBuildCall2(self.builder, ty, func, arguments.as_mut_ptr(), 2, cstr!(""))
+    }
+
+    /// A pointer to the value for the key `key` of the table of type `table` at `ptr`, and its
+    /// type. Reading an entry that isn't there stops the program, storing into one adds it
+    /// with a blank value first.
+    pub unsafe fn entry_ptr(
+        &mut self,
+        ptr: LLVMValueRef,
+        table: &VariableType,
+        key: &Index,
+        add: bool,
+    ) -> (LLVMValueRef, VariableType) {
+        let VariableType::Table { value, .. } = table else {
+            panic!("A {table:?} has no entries.");
+        };
+        let (key_value, _) = self.visit_index(key);
+        let header = table_type();
+        let count_ptr = LLVMBuildStructGEP2(self.builder, header, ptr, 0, cstr!(""));
+        let func = LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.builder));
+        let value_ty = llvm_type(value);
+
+        let index = if add {
+            let before = LLVMBuildLoad2(self.builder, LLVMInt64Type(), count_ptr, cstr!(""));
+            let (ty, insert) = self.table_fn("cobalt.table.insert");
+            let string = LLVMPointerType(LLVMInt8Type(), 0);
+            let table = LLVMBuildBitCast(self.builder, ptr, string, cstr!(""));
+            let mut arguments = [table, key_value, LLVMSizeOf(value_ty)];
+            let index = LLVMBuildCall2(
+                self.builder,
+                ty,
+                insert,
+                arguments.as_mut_ptr(),
+                3,
+                cstr!(""),
+            );
+            let after = LLVMBuildLoad2(self.builder, LLVMInt64Type(), count_ptr, cstr!(""));
+            let added = LLVMBuildICmp(
+                self.builder,
+                LLVMIntPredicate::LLVMIntNE,
+                before,
+                after,
+                cstr!(""),
+            );
+            let blank = LLVMAppendBasicBlock(func, cstr!("entry.blank"));
+            let ready = LLVMAppendBasicBlock(func, cstr!("entry.ready"));
+            LLVMBuildCondBr(self.builder, added, blank, ready);
+
+            LLVMPositionBuilderAtEnd(self.builder, blank);
+            let slot = self.value_ptr(ptr, value_ty, index);
+            let default = self.const_default(value);
+            LLVMBuildStore(self.builder, default, slot);
+            LLVMBuildBr(self.builder, ready);
+            LLVMPositionBuilderAtEnd(self.builder, ready);
+            index
+        } else {
+            let index = self.find_entry(ptr, key_value);
+            let missing = LLVMBuildICmp(
+                self.builder,
+                LLVMIntPredicate::LLVMIntSLT,
+                index,
+                LLVMConstInt(LLVMInt64Type(), 0, 0),
+                cstr!(""),
+            );
+            let not_found = LLVMAppendBasicBlock(func, cstr!("entry.not_found"));
+            let found = LLVMAppendBasicBlock(func, cstr!("entry.found"));
+            LLVMBuildCondBr(self.builder, missing, not_found, found);
+
+            LLVMPositionBuilderAtEnd(self.builder, not_found);
+            let format = LLVMBuildGlobalStringPtr(
+                self.builder,
+                cstr!("%s: there is no ENTRY \"%s\" in the table.\n"),
+                cstr!(""),
+            );
+            let location =
+                LLVMBuildGlobalStringPtr(self.builder, cstr!(key.location.as_bytes()), cstr!(""));
+            self.call_c_fn("printf", vec![format, location, key_value]);
+            self.call_c_fn("exit", vec![LLVMConstInt(LLVMInt32Type(), 1, 0)]);
+            LLVMBuildUnreachable(self.builder);
+
+            LLVMPositionBuilderAtEnd(self.builder, found);
+            index
+        };
+        (self.value_ptr(ptr, value_ty, index), (**value).clone())
+    }
+
+    /// A pointer to value number `index` of the table at `ptr`, whose values are `value_ty`.
+    pub unsafe fn value_ptr(
+        &mut self,
+        ptr: LLVMValueRef,
+        value_ty: LLVMTypeRef,
+        index: LLVMValueRef,
+    ) -> LLVMValueRef {
+        let values = LLVMBuildStructGEP2(self.builder, table_type(), ptr, 3, cstr!(""));
+        let string = LLVMPointerType(LLVMInt8Type(), 0);
+        let values = LLVMBuildLoad2(self.builder, string, values, cstr!(""));
+        let pointer_ty = LLVMPointerType(value_ty, 0);
+        let values = LLVMBuildBitCast(self.builder, values, pointer_ty, cstr!(""));
+        let mut indices = [index];
+        LLVMBuildInBoundsGEP2(
+            self.builder,
+            value_ty,
+            values,
+            indices.as_mut_ptr(),
+            1,
+            cstr!(""),
+        )
+    }
+
+    /// `FOR EACH` goes through the items of a list, or the keys of a table in the order they
+    /// were added, copying each into the loop variable. How many there are is read once, so
+    /// appending in the body doesn't make the loop go on forever.
+    pub unsafe fn visit_for_each(&mut self, func: &Function, each: ForEach) {
+        let (ptr, collection) = self.member_ptr(&each.collection);
+        let (count, element) = match &collection {
+            VariableType::List { item, .. } => {
+                (self.item_count(ptr, &collection), (**item).clone())
+            }
+            VariableType::Table { key, .. } => {
+                let count = LLVMBuildStructGEP2(self.builder, table_type(), ptr, 0, cstr!(""));
+                let count = LLVMBuildLoad2(self.builder, LLVMInt64Type(), count, cstr!(""));
+                (count, (**key).clone())
+            }
+            ty => panic!("Can't go through each of a {ty:?}."),
+        };
+        let Some(id) = each.id else {
+            panic!("FOR EACH {:?} was never resolved.", each.variable);
+        };
+        let element_ty = llvm_type(&element);
+        let variable = self.entry_alloca(element_ty, &each.variable);
+        self.variables.insert(
+            id,
+            Variable {
+                ptr: variable,
+                ty: element_ty,
+                kind: element.clone(),
+                size: LLVMABISizeOfType(self.target_data(), element_ty) as u32,
+            },
+        );
+
+        let before = LLVMGetInsertBlock(self.builder);
+        let cond = LLVMAppendBasicBlock(func.func, cstr!("for.cond"));
+        let body = LLVMAppendBasicBlock(func.func, cstr!("for.body"));
+        let end = LLVMAppendBasicBlock(func.func, cstr!("for.end"));
+        LLVMBuildBr(self.builder, cond);
+
+        LLVMPositionBuilderAtEnd(self.builder, cond);
+        let index = LLVMBuildPhi(self.builder, LLVMInt64Type(), cstr!(""));
+        let more = LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntULT,
+            index,
+            count,
+            cstr!(""),
+        );
+        LLVMBuildCondBr(self.builder, more, body, end);
+
+        LLVMPositionBuilderAtEnd(self.builder, body);
+        let value = match &collection {
+            VariableType::Table { .. } => self.entry_key(ptr, index),
+            _ => {
+                let item = self.item_at(ptr, &collection, index);
+                LLVMBuildLoad2(self.builder, element_ty, item, cstr!(""))
+            }
+        };
+        LLVMBuildStore(self.builder, value, variable);
+        if let Stmt::Block(block) = *each.body {
+            self.visit_block(func, block);
+        }
+
+        let mut values = vec![LLVMConstInt(LLVMInt64Type(), 0, 0)];
+        let mut blocks = vec![before];
+        let body_end = LLVMGetInsertBlock(self.builder);
+        if LLVMGetBasicBlockTerminator(body_end).is_null() {
+            let one = LLVMConstInt(LLVMInt64Type(), 1, 0);
+            values.push(LLVMBuildAdd(self.builder, index, one, cstr!("")));
+            blocks.push(body_end);
+            LLVMBuildBr(self.builder, cond);
+        }
+        LLVMAddIncoming(
+            index,
+            values.as_mut_ptr(),
+            blocks.as_mut_ptr(),
+            values.len() as u32,
+        );
+        LLVMPositionBuilderAtEnd(self.builder, end);
+    }
+
+    /// The key of entry number `index` of the table at `ptr`.
+    pub unsafe fn entry_key(&mut self, ptr: LLVMValueRef, index: LLVMValueRef) -> LLVMValueRef {
+        let string = LLVMPointerType(LLVMInt8Type(), 0);
+        let keys = LLVMBuildStructGEP2(self.builder, table_type(), ptr, 2, cstr!(""));
+        let keys = LLVMBuildLoad2(self.builder, LLVMPointerType(string, 0), keys, cstr!(""));
+        let mut indices = [index];
+        let key = LLVMBuildInBoundsGEP2(
+            self.builder,
+            string,
+            keys,
+            indices.as_mut_ptr(),
+            1,
+            cstr!(""),
+        );
+        LLVMBuildLoad2(self.builder, string, key, cstr!(""))
+    }
+}
@@ -0,0 +1,258 @@
+//! Fixed-length `TEXT` variables. Like COBOL's `PIC X(n)` they always hold all of their
+//! characters: shorter values are padded with spaces and longer ones are cut off.
+
+use llvm_sys::{
+    core::{
+        LLVMAddFunction, LLVMAddIncoming, LLVMAppendBasicBlock, LLVMArrayType, LLVMBuildAnd,
+        LLVMBuildBr, LLVMBuildCondBr, LLVMBuildICmp, LLVMBuildInBoundsGEP2, LLVMBuildLoad2,
+        LLVMBuildPhi, LLVMBuildRet, LLVMBuildSelect, LLVMBuildStore, LLVMBuildSub, LLVMBuildZExt,
+        LLVMConstInt, LLVMConstString, LLVMFunctionType, LLVMGetInsertBlock, LLVMGetNamedFunction,
+        LLVMGetParam, LLVMInt32Type, LLVMInt64Type, LLVMInt8Type, LLVMPointerType,
+        LLVMPositionBuilderAtEnd, LLVMSetLinkage,
+    },
+    prelude::{LLVMTypeRef, LLVMValueRef},
+    LLVMIntPredicate, LLVMLinkage,
+};
+
+use crate::node::{Expr, LiteralExpr, Rounding, Stmt, Store, VariableType};
+
+use super::{llvm_type, CodeGen};
+
+impl<T> CodeGen<T>
+where
+    T: Iterator<Item = Stmt>,
+{
+    /// A string literal padded or cut off to `length` characters, as the initial value of a
+    /// `TEXT`. The type checker has already warned if anything is cut off.
+    pub unsafe fn const_text(&mut self, value: &Expr, length: u32) -> LLVMValueRef {
+        let Expr::Literal(LiteralExpr::String(value)) = value else {
+            panic!("Expected a string literal, not {value:?}.");
+        };
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.resize(length as usize, b' ');
+        LLVMConstString(bytes.as_ptr() as *const i8, length, 1)
+    }
+
+    /// A pointer to the first character of a `TEXT` variable, where its characters are stored.
+    pub unsafe fn text_pointer(&mut self, ptr: LLVMValueRef, length: u32) -> LLVMValueRef {
+        let zero = LLVMConstInt(LLVMInt64Type(), 0, 0);
+        let mut indices = [zero, zero];
+        LLVMBuildInBoundsGEP2(
+            self.builder,
+            llvm_type(&VariableType::Text { length }),
+            ptr,
+            indices.as_mut_ptr(),
+            2,
+            cstr!(""),
+        )
+    }
+
+    /// A `TEXT` as a string. Its characters aren't followed by a NUL, so that fields and
+    /// overlays line up, and are copied into a buffer that is. The buffer is allocated once in
+    /// the entry block, so reading a `TEXT` in a loop doesn't grow the stack.
+    pub unsafe fn text_value(&mut self, ptr: LLVMValueRef, length: u32) -> LLVMValueRef {
+        let buffer_ty = LLVMArrayType(LLVMInt8Type(), length + 1);
+        let buffer = self.entry_alloca(buffer_ty, "");
+
+        let zero = LLVMConstInt(LLVMInt64Type(), 0, 0);
+        let end = LLVMConstInt(LLVMInt64Type(), length as u64, 0);
+        let mut indices = [zero, zero];
+        let string = LLVMBuildInBoundsGEP2(
+            self.builder,
+            buffer_ty,
+            buffer,
+            indices.as_mut_ptr(),
+            2,
+            cstr!(""),
+        );
+        let characters = self.text_pointer(ptr, length);
+        self.call_c_fn("memmove", vec![string, characters, end]);
+        let mut indices = [end];
+        let nul = LLVMBuildInBoundsGEP2(
+            self.builder,
+            LLVMInt8Type(),
+            string,
+            indices.as_mut_ptr(),
+            1,
+            cstr!(""),
+        );
+        LLVMBuildStore(self.builder, LLVMConstInt(LLVMInt8Type(), 0, 0), nul);
+        string
+    }
+
+    /// `MOVE` converts the value to the type of its target. Strings moved out of a `TEXT` are
+    /// copied, so later moves into the `TEXT` don't change them.
+    pub unsafe fn visit_move(&mut self, store: Store) {
+        let (ptr, kind) = self.target_ptr(&store);
+        self.move_value(store.value, ptr, kind);
+    }
+
+    /// Stores `value` at `ptr` the way `MOVE` would, converted to `kind`.
+    pub unsafe fn move_value(&mut self, value: Expr, ptr: LLVMValueRef, kind: VariableType) {
+        let (value, value_ty) = match value {
+            // Filled in place, as `LOW-VALUES` would otherwise read as an empty string.
+            Expr::Literal(LiteralExpr::Figurative(figurative)) => {
+                if let VariableType::Text { length } = kind {
+                    let target = self.text_pointer(ptr, length);
+                    let fill = LLVMConstInt(LLVMInt32Type(), figurative.byte() as u64, 0);
+                    let length = LLVMConstInt(LLVMInt64Type(), length as u64, 0);
+                    self.call_c_fn("memset", vec![target, fill, length]);
+                    return;
+                }
+                self.figurative_value(figurative, &kind)
+            }
+            value @ Expr::Literal(LiteralExpr::String(_)) => self.visit_operand(value, &kind),
+            value => self.visit_expr(value),
+        };
+        match kind {
+            VariableType::Text { length } => self.move_text(value, ptr, length),
+            // A whole record or list is copied, the type checker made sure they are alike.
+            VariableType::Record(_) | VariableType::List { .. } => {
+                let record = LLVMBuildLoad2(self.builder, llvm_type(&kind), value, cstr!(""));
+                LLVMBuildStore(self.builder, record, ptr);
+            }
+            VariableType::String if value_ty != VariableType::String => {
+                let copy = self.call_c_fn("strdup", vec![value]);
+                LLVMBuildStore(self.builder, copy, ptr);
+            }
+            VariableType::Fixed { digits, scale } => {
+                let truncate = Rounding::Truncate;
+                self.store_fixed(value, &value_ty, (digits, scale), truncate, ptr, false);
+            }
+            kind => {
+                let value = self.convert(value, &value_ty, &kind);
+                LLVMBuildStore(self.builder, value, ptr);
+            }
+        }
+    }
+
+    /// Copies the string `value` into a `TEXT` of `length` characters at `ptr`. The characters
+    /// are moved before the rest is padded, so `value` may point into the target itself.
+    pub unsafe fn move_text(&mut self, value: LLVMValueRef, ptr: LLVMValueRef, length: u32) {
+        let target = self.text_pointer(ptr, length);
+        let length = LLVMConstInt(LLVMInt64Type(), length as u64, 0);
+        let count = self.call_c_fn("strlen", vec![value]);
+        let fits = LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntULT,
+            count,
+            length,
+            cstr!(""),
+        );
+        let count = LLVMBuildSelect(self.builder, fits, count, length, cstr!(""));
+        self.call_c_fn("memmove", vec![target, value, count]);
+
+        let mut indices = [count];
+        let rest = LLVMBuildInBoundsGEP2(
+            self.builder,
+            LLVMInt8Type(),
+            target,
+            indices.as_mut_ptr(),
+            1,
+            cstr!(""),
+        );
+        let space = LLVMConstInt(LLVMInt32Type(), b' ' as u64, 0);
+        let padding = LLVMBuildSub(self.builder, length, count, cstr!(""));
+        self.call_c_fn("memset", vec![rest, space, padding]);
+    }
+
+    /// Returns `cobalt.compare_padded`, generating it into the current module the first time.
+    /// It orders two strings like `strcmp`, but as if the shorter one were padded with spaces,
+    /// which is how COBOL compares text of different lengths.
+    pub unsafe fn compare_padded_fn(&mut self) -> (LLVMTypeRef, LLVMValueRef) {
+        let module = self.cur_module.unwrap();
+        let string = LLVMPointerType(LLVMInt8Type(), 0);
+        let ty = LLVMFunctionType(LLVMInt32Type(), [string, string].as_mut_ptr(), 2, 0);
+        let existing = LLVMGetNamedFunction(module, cstr!("cobalt.compare_padded"));
+        if !existing.is_null() {
+            return (ty, existing);
+        }
+
+        let func = LLVMAddFunction(module, cstr!("cobalt.compare_padded"), ty);
+        LLVMSetLinkage(func, LLVMLinkage::LLVMInternalLinkage);
+        let resume = LLVMGetInsertBlock(self.builder);
+
+        let entry = LLVMAppendBasicBlock(func, cstr!("entry"));
+        let lp = LLVMAppendBasicBlock(func, cstr!("loop"));
+        let check = LLVMAppendBasicBlock(func, cstr!("check"));
+        let next = LLVMAppendBasicBlock(func, cstr!("next"));
+        let differ = LLVMAppendBasicBlock(func, cstr!("differ"));
+        let equal = LLVMAppendBasicBlock(func, cstr!("equal"));
+
+        LLVMPositionBuilderAtEnd(self.builder, entry);
+        LLVMBuildBr(self.builder, lp);
+
+        // Both strings are walked together, one that has ended stays on its NUL.
+        LLVMPositionBuilderAtEnd(self.builder, lp);
+        let l = LLVMBuildPhi(self.builder, string, cstr!("l"));
+        let r = LLVMBuildPhi(self.builder, string, cstr!("r"));
+        let l_char = LLVMBuildLoad2(self.builder, LLVMInt8Type(), l, cstr!(""));
+        let r_char = LLVMBuildLoad2(self.builder, LLVMInt8Type(), r, cstr!(""));
+        let nul = LLVMConstInt(LLVMInt8Type(), 0, 0);
+        let l_ended = LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntEQ,
+            l_char,
+            nul,
+            cstr!(""),
+        );
+        let r_ended = LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntEQ,
+            r_char,
+            nul,
+            cstr!(""),
+        );
+        let both_ended = LLVMBuildAnd(self.builder, l_ended, r_ended, cstr!(""));
+        LLVMBuildCondBr(self.builder, both_ended, equal, check);
+
+        LLVMPositionBuilderAtEnd(self.builder, check);
+        let space = LLVMConstInt(LLVMInt8Type(), b' ' as u64, 0);
+        let l_char = LLVMBuildSelect(self.builder, l_ended, space, l_char, cstr!(""));
+        let r_char = LLVMBuildSelect(self.builder, r_ended, space, r_char, cstr!(""));
+        let l_char = LLVMBuildZExt(self.builder, l_char, LLVMInt32Type(), cstr!(""));
+        let r_char = LLVMBuildZExt(self.builder, r_char, LLVMInt32Type(), cstr!(""));
+        let difference = LLVMBuildSub(self.builder, l_char, r_char, cstr!(""));
+        let differs = LLVMBuildICmp(
+            self.builder,
+            LLVMIntPredicate::LLVMIntNE,
+            difference,
+            LLVMConstInt(LLVMInt32Type(), 0, 0),
+            cstr!(""),
+        );
+        LLVMBuildCondBr(self.builder, differs, differ, next);
+
+        LLVMPositionBuilderAtEnd(self.builder, differ);
+        LLVMBuildRet(self.builder, difference);
+
+        LLVMPositionBuilderAtEnd(self.builder, next);
+        let one = LLVMConstInt(LLVMInt64Type(), 1, 0);
+        let step = |ptr, ended| {
+            let mut indices = [one];
+            let after = LLVMBuildInBoundsGEP2(
+                self.builder,
+                LLVMInt8Type(),
+                ptr,
+                indices.as_mut_ptr(),
+                1,
+                cstr!(""),
+            );
+            LLVMBuildSelect(self.builder, ended, ptr, after, cstr!(""))
+        };
+        let l_next = step(l, l_ended);
+        let r_next = step(r, r_ended);
+        LLVMBuildBr(self.builder, lp);
+
+        let mut l_values = [LLVMGetParam(func, 0), l_next];
+        let mut r_values = [LLVMGetParam(func, 1), r_next];
+        let mut blocks = [entry, next];
+        LLVMAddIncoming(l, l_values.as_mut_ptr(), blocks.as_mut_ptr(), 2);
+        LLVMAddIncoming(r, r_values.as_mut_ptr(), blocks.as_mut_ptr(), 2);
+
+        LLVMPositionBuilderAtEnd(self.builder, equal);
+        LLVMBuildRet(self.builder, LLVMConstInt(LLVMInt32Type(), 0, 0));
+
+        LLVMPositionBuilderAtEnd(self.builder, resume);
+        (ty, func)
+    }
+}
@@ -1,10 +1,10 @@
-use llvm_sys::{
-    prelude::{LLVMBasicBlockRef, LLVMValueRef},
-    LLVMType,
-};
+use llvm_sys::{prelude::LLVMValueRef, LLVMType};
+
+use crate::node::VariableType;
 
 pub struct Variable {
     pub ptr: LLVMValueRef,
     pub ty: *mut LLVMType,
+    pub kind: VariableType,
     pub size: u32,
 }
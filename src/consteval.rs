@@ -0,0 +1,510 @@
+//! Works out constants and the parts of expressions made only of literals, after names are
+//! resolved and before types are checked.
+//!
+//! Every use of a constant is replaced by its value, so later passes only ever see literals.
+//! Arithmetic on numbers is done exactly, except that division with decimals is left to run time
+//! where it rounds like it always did, and so is a division of whole numbers in a `COMPUTE` that
+//! doesn't come out even, as its target decides what happens to the decimals. Comparisons, `AND`, `OR` and `NOT` of literals become
+//! `TRUE` or `FALSE`, strings joined with `&` become one string and the `LENGTH OF` a string is
+//! counted.
+
+use std::{cmp::Ordering, collections::HashMap, ops::Range};
+
+use crate::{
+    errors::ErrorClient,
+    node::{
+        Access, Arithmetic, ArithmeticOperators, BinaryOperators, DefId, Expr, LiteralExpr,
+        LogicalOperators, Stmt, Store, MAX_DIGITS,
+    },
+};
+
+/// How far a constant has been worked out.
+enum State {
+    Unfolded(Constant),
+    /// Its value is being worked out, so using it again is a cycle.
+    Folding(Constant),
+    Folded(Expr),
+}
+
+/// A constant outside of any function, which can be used before it is defined.
+struct Constant {
+    name: String,
+    value: Expr,
+    file: String,
+    span: Range<usize>,
+}
+
+#[derive(Default)]
+pub struct ConstEval {
+    constants: HashMap<DefId, State>,
+    file: String,
+    /// Set while the values of a `COMPUTE` are worked out.
+    in_compute: bool,
+}
+
+impl ConstEval {
+    /// Collects the constants outside of any function, which every function can use.
+    pub fn declare(&mut self, file: &str, nodes: &[Stmt]) {
+        for node in nodes {
+            let contents: Vec<&Stmt> = match node {
+                Stmt::Module { nodes, .. } => match &**nodes {
+                    Stmt::Block(contents) => contents.iter().map(|content| &**content).collect(),
+                    _ => continue,
+                },
+                node => vec![node],
+            };
+            for content in contents {
+                let Stmt::Constant(constant) = content else {
+                    continue;
+                };
+                let Some(id) = constant.id else {
+                    continue;
+                };
+                let constant = Constant {
+                    name: constant.name.clone(),
+                    value: constant.value.clone(),
+                    file: file.to_string(),
+                    span: constant.span.clone(),
+                };
+                self.constants.insert(id, State::Unfolded(constant));
+            }
+        }
+    }
+
+    pub fn fold(&mut self, file: &str, nodes: &mut [Stmt]) {
+        self.file = file.to_string();
+        for node in nodes {
+            self.fold_stmt(node);
+        }
+    }
+
+    fn fold_stmt(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::Block(nodes) => {
+                for node in nodes {
+                    self.fold_stmt(node);
+                }
+            }
+            Stmt::Module { nodes, .. } | Stmt::Function { nodes, .. } => self.fold_stmt(nodes),
+            Stmt::Expr(expr) => self.fold_expr(expr),
+            Stmt::Variable {
+                value, conditions, ..
+            } => {
+                if let Some(value) = value {
+                    self.fold_expr(value);
+                }
+                for condition in conditions {
+                    for value in &mut condition.values {
+                        self.fold_expr(value);
+                    }
+                }
+            }
+            Stmt::Condition(condition) => {
+                self.fold_expr(&mut condition.condition);
+                self.fold_stmt(&mut condition.then);
+                if let Some(el) = &mut condition.el {
+                    self.fold_stmt(el);
+                }
+            }
+            Stmt::Compute(compute) => {
+                self.in_compute = true;
+                for store in &mut compute.stores {
+                    self.fold_store(store);
+                }
+                self.in_compute = false;
+                if let Some(handler) = &mut compute.on_size_error {
+                    self.fold_stmt(handler);
+                }
+            }
+            Stmt::Move(store) | Stmt::Append(store) => self.fold_store(store),
+            Stmt::Display { exprs, .. } => {
+                for expr in exprs {
+                    self.fold_expr(expr);
+                }
+            }
+            Stmt::ForEach(for_each) => {
+                self.fold_expr(&mut for_each.collection);
+                self.fold_stmt(&mut for_each.body);
+            }
+            Stmt::Sort(sort) => self.fold_expr(&mut sort.collection),
+            Stmt::Search(search) => {
+                self.fold_expr(&mut search.collection);
+                self.fold_store(&mut search.giving);
+            }
+            Stmt::Evaluate(evaluate) => {
+                self.fold_expr(&mut evaluate.subject);
+                for when in &mut evaluate.branches {
+                    for value in &mut when.values {
+                        self.fold_expr(value);
+                    }
+                    self.fold_stmt(&mut when.body);
+                }
+                if let Some(other) = &mut evaluate.other {
+                    self.fold_stmt(other);
+                }
+            }
+            // Outside of a function it was collected by `declare`, inside one it can only be
+            // used after it is defined.
+            Stmt::Constant(constant) => {
+                let Some(id) = constant.id else {
+                    return;
+                };
+                if let Some(value) = self.fold_constant(id) {
+                    constant.value = value;
+                    return;
+                }
+                self.fold_expr(&mut constant.value);
+                self.check_constant(&constant.name, &constant.value, &constant.span);
+                self.constants
+                    .insert(id, State::Folded(constant.value.clone()));
+            }
+            Stmt::Choice(_) => {}
+        }
+    }
+
+    fn fold_store(&mut self, store: &mut Store) {
+        self.fold_expr(&mut store.value);
+        for access in &mut store.path {
+            if let Access::Item(index) | Access::Entry(index) = access {
+                self.fold_expr(&mut index.value);
+            }
+        }
+    }
+
+    fn fold_expr(&mut self, expr: &mut Expr) {
+        let folded = match expr {
+            Expr::Literal(_)
+            | Expr::Function(_)
+            | Expr::Interpolation(_)
+            | Expr::ModuleVariable(_) => None,
+            Expr::Variable(reference) => self.fold_constant(reference.id),
+            // The function itself is named, not worked out.
+            Expr::Call(call) => {
+                for arg in call.args.iter_mut().flatten() {
+                    self.fold_expr(arg);
+                }
+                None
+            }
+            Expr::Arithmetic(arithmetic) => {
+                self.fold_expr(&mut arithmetic.l);
+                self.fold_expr(&mut arithmetic.r);
+                self.check_arithmetic(arithmetic);
+                let uneven = match (number(&arithmetic.l), number(&arithmetic.r)) {
+                    (Some((l, _)), Some((r, _))) => {
+                        is_integer(&arithmetic.l)
+                            && is_integer(&arithmetic.r)
+                            && l.checked_rem(r).is_some_and(|rest| rest != 0)
+                    }
+                    _ => false,
+                };
+                match arithmetic.op {
+                    ArithmeticOperators::Divide if self.in_compute && uneven => None,
+                    _ => calculate(&arithmetic.l, &arithmetic.r, &arithmetic.op),
+                }
+            }
+            Expr::BinaryOp(binary) => {
+                self.fold_expr(&mut binary.l);
+                self.fold_expr(&mut binary.r);
+                compare(&binary.l, &binary.r, &binary.op)
+                    .map(|result| Expr::Literal(LiteralExpr::Boolean(result)))
+            }
+            Expr::Logical(logical) => {
+                self.fold_expr(&mut logical.l);
+                self.fold_expr(&mut logical.r);
+                match (boolean(&logical.l), boolean(&logical.r), &logical.op) {
+                    (Some(l), Some(r), LogicalOperators::And) => Some(l && r),
+                    (Some(l), Some(r), LogicalOperators::Or) => Some(l || r),
+                    _ => None,
+                }
+                .map(|result| Expr::Literal(LiteralExpr::Boolean(result)))
+            }
+            Expr::Not(inner) => {
+                self.fold_expr(inner);
+                boolean(inner).map(|value| Expr::Literal(LiteralExpr::Boolean(!value)))
+            }
+            Expr::Negate(inner) => {
+                self.fold_expr(inner);
+                match &**inner {
+                    Expr::Negate(twice) => Some((**twice).clone()),
+                    _ => None,
+                }
+            }
+            Expr::Concat(concat) => {
+                self.fold_expr(&mut concat.l);
+                self.fold_expr(&mut concat.r);
+                match (&*concat.l, &*concat.r) {
+                    (
+                        Expr::Literal(LiteralExpr::String(l)),
+                        Expr::Literal(LiteralExpr::String(r)),
+                    ) => Some(Expr::Literal(LiteralExpr::String(format!("{l}{r}")))),
+                    _ => None,
+                }
+            }
+            Expr::Length(length) => {
+                self.fold_expr(&mut length.text);
+                match &*length.text {
+                    Expr::Literal(LiteralExpr::String(text)) => {
+                        Some(Expr::Literal(LiteralExpr::Integer(text.len() as i64)))
+                    }
+                    _ => None,
+                }
+            }
+            Expr::Characters(characters) => {
+                self.fold_expr(&mut characters.text);
+                self.fold_expr(&mut characters.from.value);
+                self.fold_expr(&mut characters.through.value);
+                None
+            }
+            Expr::Edited(edited) => {
+                self.fold_expr(&mut edited.value);
+                None
+            }
+            Expr::Member(member) => {
+                self.fold_expr(&mut member.record);
+                None
+            }
+            Expr::Item(item) => {
+                self.fold_expr(&mut item.list);
+                self.fold_expr(&mut item.index.value);
+                None
+            }
+            Expr::Entry(entry) => {
+                self.fold_expr(&mut entry.table);
+                self.fold_expr(&mut entry.key.value);
+                None
+            }
+        };
+        if let Some(folded) = folded {
+            *expr = folded;
+        }
+    }
+
+    /// The value of the constant `id`, worked out the first time it is needed. `None` if `id`
+    /// is a variable or a constant defined inside a function that wasn't reached yet.
+    fn fold_constant(&mut self, id: DefId) -> Option<Expr> {
+        let constant = match self.constants.remove(&id)? {
+            State::Folded(value) => {
+                self.constants.insert(id, State::Folded(value.clone()));
+                return Some(value);
+            }
+            State::Folding(constant) => {
+                let label = format!("\"{}\" is worked out from itself", constant.name);
+                let mut error = ErrorClient::new("0038", crate::errors::MessageKind::ERROR);
+                error.end_process(true);
+                error.set_file(&constant.file, &constant.file);
+                error.set_span(constant.span.clone());
+                error.add_label(Some(&label));
+                error.build_and_emit();
+                unreachable!()
+            }
+            State::Unfolded(constant) => constant,
+        };
+        let mut value = constant.value.clone();
+        let (name, span) = (constant.name.clone(), constant.span.clone());
+        let file = std::mem::replace(&mut self.file, constant.file.clone());
+        self.constants.insert(id, State::Folding(constant));
+        let in_compute = std::mem::replace(&mut self.in_compute, false);
+        self.fold_expr(&mut value);
+        self.in_compute = in_compute;
+        self.check_constant(&name, &value, &span);
+        self.file = file;
+
+        self.constants.insert(id, State::Folded(value.clone()));
+        Some(value)
+    }
+
+    /// Reports arithmetic that can't be worked out even though its literals are known: dividing
+    /// by zero, or whole numbers whose result doesn't fit in an `INTEGER`.
+    fn check_arithmetic(&self, arithmetic: &Arithmetic) {
+        let (code, label) = match (number(&arithmetic.l), number(&arithmetic.r)) {
+            (_, Some((0, _)))
+                if matches!(
+                    arithmetic.op,
+                    ArithmeticOperators::Divide | ArithmeticOperators::Remainder
+                ) =>
+            {
+                ("0039", "This divides by zero")
+            }
+            (Some((l, _)), Some((r, _)))
+                if is_integer(&arithmetic.l)
+                    && is_integer(&arithmetic.r)
+                    && integer_result(l, r, &arithmetic.op).is_none() =>
+            {
+                ("0040", "The result doesn't fit in an INTEGER")
+            }
+            _ => return,
+        };
+        let mut error = ErrorClient::new(code, crate::errors::MessageKind::ERROR);
+        error.end_process(true);
+        error.set_file(&self.file, &self.file);
+        error.set_span(arithmetic.span.clone());
+        error.add_label(Some(label));
+        error.build_and_emit();
+    }
+
+    /// Reports a constant whose value still uses a variable or a function once worked out.
+    /// Anything else that isn't a literal is left for the type checker to explain.
+    fn check_constant(&self, name: &str, value: &Expr, span: &Range<usize>) {
+        if value.is_literal() {
+            return;
+        }
+        let label = match (value, variable(value)) {
+            (Expr::Function(_), _) => {
+                "A function can't be a constant, `SET` a variable to it instead".to_string()
+            }
+            (_, Some(variable)) => format!(
+                "\"{variable}\" is a variable, \"{name}\" can only use literals and other constants"
+            ),
+            _ => return,
+        };
+        let mut error = ErrorClient::new("0038", crate::errors::MessageKind::ERROR);
+        error.end_process(true);
+        error.set_file(&self.file, &self.file);
+        error.set_span(span.clone());
+        error.add_label(Some(&label));
+        error.build_and_emit();
+    }
+}
+
+/// The first variable `expr` uses, if any.
+fn variable(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Variable(reference) => Some(&reference.name),
+        Expr::Arithmetic(arithmetic) => variable(&arithmetic.l).or_else(|| variable(&arithmetic.r)),
+        Expr::BinaryOp(binary) => variable(&binary.l).or_else(|| variable(&binary.r)),
+        Expr::Logical(logical) => variable(&logical.l).or_else(|| variable(&logical.r)),
+        Expr::Concat(concat) => variable(&concat.l).or_else(|| variable(&concat.r)),
+        Expr::Negate(inner) | Expr::Not(inner) => variable(inner),
+        Expr::Edited(edited) => variable(&edited.value),
+        Expr::Length(length) => variable(&length.text),
+        Expr::Characters(characters) => variable(&characters.text)
+            .or_else(|| variable(&characters.from.value))
+            .or_else(|| variable(&characters.through.value)),
+        Expr::Member(member) => variable(&member.record),
+        Expr::Item(item) => variable(&item.list),
+        Expr::Entry(entry) => variable(&entry.table),
+        Expr::ModuleVariable(variable) => Some(&variable.name),
+        Expr::Literal(_) | Expr::Call(_) | Expr::Function(_) | Expr::Interpolation(_) => None,
+    }
+}
+
+fn boolean(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Literal(LiteralExpr::Boolean(value)) => Some(*value),
+        _ => None,
+    }
+}
+
+/// A number literal as a whole number of units of its last decimal place, and how many decimal
+/// places that is.
+fn number(expr: &Expr) -> Option<(i128, u32)> {
+    match expr {
+        Expr::Negate(inner) => number(inner).map(|(value, scale)| (-value, scale)),
+        Expr::Literal(LiteralExpr::Integer(_)) => Some((expr.scaled_literal(0)?, 0)),
+        Expr::Literal(LiteralExpr::Decimal(_)) => expr.fixed_literal(),
+        _ => None,
+    }
+}
+
+fn is_integer(expr: &Expr) -> bool {
+    match expr {
+        Expr::Negate(inner) => is_integer(inner),
+        expr => matches!(expr, Expr::Literal(LiteralExpr::Integer(_))),
+    }
+}
+
+/// Both numbers with the same number of decimal places.
+fn align((l, l_scale): (i128, u32), (r, r_scale): (i128, u32)) -> Option<(i128, i128, u32)> {
+    let scale = l_scale.max(r_scale);
+    let l = l.checked_mul(10i128.checked_pow(scale - l_scale)?)?;
+    let r = r.checked_mul(10i128.checked_pow(scale - r_scale)?)?;
+    Some((l, r, scale))
+}
+
+/// `l op r` for two whole numbers, if it fits in an `INTEGER`.
+fn integer_result(l: i128, r: i128, op: &ArithmeticOperators) -> Option<i64> {
+    let (l, r) = (i64::try_from(l).ok()?, i64::try_from(r).ok()?);
+    match op {
+        ArithmeticOperators::Add => l.checked_add(r),
+        ArithmeticOperators::Subtract => l.checked_sub(r),
+        ArithmeticOperators::Multiply => l.checked_mul(r),
+        ArithmeticOperators::Divide => l.checked_div(r),
+        ArithmeticOperators::Remainder => l.checked_rem(r),
+    }
+}
+
+/// `l op r` as a literal, if both are numbers and the result is exact and fits.
+fn calculate(l: &Expr, r: &Expr, op: &ArithmeticOperators) -> Option<Expr> {
+    let (l_number, r_number) = (number(l)?, number(r)?);
+    if is_integer(l) && is_integer(r) {
+        let result = integer_result(l_number.0, r_number.0, op)?;
+        let literal = Expr::Literal(LiteralExpr::Integer(result.checked_abs()?));
+        return Some(negated(literal, result < 0));
+    }
+    let (value, scale) = match op {
+        ArithmeticOperators::Add | ArithmeticOperators::Subtract => {
+            let (l, r, scale) = align(l_number, r_number)?;
+            let value = match op {
+                ArithmeticOperators::Add => l.checked_add(r)?,
+                _ => l.checked_sub(r)?,
+            };
+            (value, scale)
+        }
+        ArithmeticOperators::Multiply => {
+            (l_number.0.checked_mul(r_number.0)?, l_number.1 + r_number.1)
+        }
+        ArithmeticOperators::Divide | ArithmeticOperators::Remainder => return None,
+    };
+    let unit = 10i128.checked_pow(scale)?;
+    let (whole, part) = (value.abs() / unit, value.abs() % unit);
+    if scale > MAX_DIGITS || whole >= 10i128.pow(MAX_DIGITS) {
+        return None;
+    }
+    let text = match scale {
+        0 => format!("{whole}.0"),
+        scale => format!("{whole}.{part:0width$}", width = scale as usize),
+    };
+    Some(negated(
+        Expr::Literal(LiteralExpr::Decimal(text)),
+        value < 0,
+    ))
+}
+
+fn negated(expr: Expr, negative: bool) -> Expr {
+    match negative {
+        true => Expr::Negate(Box::new(expr)),
+        false => expr,
+    }
+}
+
+/// `l op r`, if both are numbers, strings or for equality booleans.
+fn compare(l: &Expr, r: &Expr, op: &BinaryOperators) -> Option<bool> {
+    let ordering = match (l, r) {
+        (Expr::Literal(LiteralExpr::String(l)), Expr::Literal(LiteralExpr::String(r))) => {
+            if let BinaryOperators::Contains = op {
+                return Some(l.contains(r.as_str()));
+            }
+            l.as_bytes().cmp(r.as_bytes())
+        }
+        (Expr::Literal(LiteralExpr::Boolean(l)), Expr::Literal(LiteralExpr::Boolean(r))) => {
+            return match op {
+                BinaryOperators::EqualTo => Some(l == r),
+                BinaryOperators::NotEqualTo => Some(l != r),
+                _ => None,
+            };
+        }
+        (l, r) => {
+            let (l, r, _) = align(number(l)?, number(r)?)?;
+            l.cmp(&r)
+        }
+    };
+    match op {
+        BinaryOperators::EqualTo => Some(ordering == Ordering::Equal),
+        BinaryOperators::NotEqualTo => Some(ordering != Ordering::Equal),
+        BinaryOperators::GreaterThan => Some(ordering == Ordering::Greater),
+        BinaryOperators::LessThan => Some(ordering == Ordering::Less),
+        BinaryOperators::GreaterThanOrEqualTo => Some(ordering != Ordering::Less),
+        BinaryOperators::LessThanOrEqualTo => Some(ordering != Ordering::Greater),
+        BinaryOperators::Contains | BinaryOperators::ContainsKey => None,
+    }
+}
@@ -17,7 +17,47 @@ pub enum MessageKind {
 
 static ERRORS: phf::Map<&'static str, Error> = phf_map! {
     "0001" => Error("Provided keyword did not match the expected keyword."),
-    "0002" => Error("Non-Existent keyword.")
+    "0002" => Error("Non-Existent keyword."),
+    "0003" => Error("Expected an expression."),
+    "0004" => Error("Expected a variable name."),
+    "0005" => Error("Number literal is out of range."),
+    "0006" => Error("Unknown comparison."),
+    "0007" => Error("Both an implicit and an explicit `main` function exist."),
+    "0008" => Error("Expected a statement."),
+    "0009" => Error("Statement is not allowed in a module."),
+    "0010" => Error("Statement is not allowed in an imported file."),
+    "0011" => Error("Expected a file path."),
+    "0012" => Error("Expected a module name."),
+    "0013" => Error("Imported file does not exist."),
+    "0014" => Error("Import cycle detected."),
+    "0015" => Error("Imported file does not define the module."),
+    "0016" => Error("Statement cannot be public or private."),
+    "0017" => Error("Private member used outside of its module."),
+    "0018" => Error("Undefined variable."),
+    "0019" => Error("Undefined function."),
+    "0020" => Error("Name is defined more than once."),
+    "0021" => Error("Unknown type."),
+    "0022" => Error("Mismatched types."),
+    "0023" => Error("Invalid number declaration."),
+    "0024" => Error("Value does not fit in its declared digits."),
+    "0025" => Error("Invalid picture."),
+    "0026" => Error("Invalid text length."),
+    "0027" => Error("Value is truncated."),
+    "0028" => Error("Invalid use of a condition name."),
+    "0029" => Error("Unknown field."),
+    "0030" => Error("Overlay does not fit."),
+    "0031" => Error("Invalid list length."),
+    "0032" => Error("List can't grow."),
+    "0033" => Error("Invalid sort key."),
+    "0034" => Error("Not a value of the choice."),
+    "0035" => Error("Not every value of the choice is handled."),
+    "0036" => Error("Wrong arguments for the function."),
+    "0037" => Error("Constant cannot be changed."),
+    "0038" => Error("Value is not constant."),
+    "0039" => Error("Division by zero."),
+    "0040" => Error("Result does not fit."),
+    "0041" => Error("Unknown name in a string."),
+    "0042" => Error("Definition is not allowed here."),
 };
 
 pub struct ErrorFile<'a> {
@@ -63,6 +103,32 @@ impl<'a> ErrorClient<'a> {
         }
         self
     }
+    /// Points at another place in the file that is related to the error.
+    pub fn add_secondary_label(&mut self, span: Range<usize>, message: &'a str) -> &mut Self {
+        if let Some(file_id) = self.file_id {
+            self.labels
+                .push(Label::secondary(file_id, span).with_message(message));
+        }
+        self
+    }
+    /// Like `add_secondary_label`, but for a place in another file.
+    pub fn add_secondary_label_in(
+        &mut self,
+        file: &'a str,
+        span: Range<usize>,
+        message: &'a str,
+    ) -> &mut Self {
+        let same_file = self.file_id.filter(|id| {
+            self.files
+                .get(*id)
+                .is_ok_and(|current| *current.name() == file)
+        });
+        let file_id = same_file
+            .unwrap_or_else(|| self.files.add(file, std::fs::read_to_string(file).unwrap()));
+        self.labels
+            .push(Label::secondary(file_id, span).with_message(message));
+        self
+    }
     pub fn add_note(&mut self, note: &'a str) -> &mut Self {
         self.notes.push(note);
         self
@@ -81,12 +147,16 @@ impl<'a> ErrorClient<'a> {
     }
 
     pub fn build_and_emit(self) {
-        let diagnostic = Diagnostic::error()
+        let diagnostic = match self.kind {
+            MessageKind::ERROR => Diagnostic::error(),
+            MessageKind::WARNING => Diagnostic::warning(),
+        };
+        let diagnostic = diagnostic
             .with_code(format!("E{}", self.error_code))
             .with_labels(self.labels)
             .with_message(self.error.0);
 
-        let diagnostic = if self.notes.len() > 0 {
+        let diagnostic = if !self.notes.is_empty() {
             diagnostic.with_notes(
                 self.notes
                     .into_iter()
@@ -0,0 +1,177 @@
+//! Follows `USE MODULE` statements so a program can be spread over several files.
+
+use std::{
+    collections::HashMap,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ast,
+    consteval::ConstEval,
+    errors::ErrorClient,
+    node::{Import, LiteralExpr, Stmt},
+    p,
+    resolve::Resolver,
+    typeck::TypeChecker,
+    Lexer,
+};
+
+pub struct Program {
+    /// The nodes of every file once they have been resolved, imported files first.
+    pub nodes: Vec<Stmt>,
+    /// Every file parsed so far with the modules it defines, so each is only compiled once.
+    loaded: HashMap<PathBuf, Vec<String>>,
+    /// The files currently being loaded, innermost last. Importing one of them again is a cycle.
+    stack: Vec<(PathBuf, String)>,
+    /// The nodes of every file, in the order they were loaded.
+    files: Vec<(String, Vec<Stmt>)>,
+    pub resolver: Resolver,
+}
+
+impl Program {
+    /// Parses the file at `path` and everything it imports, then resolves the names in all of
+    /// them, works out their constants and checks their types.
+    pub fn load(path: &str) -> Self {
+        let mut program = Self {
+            nodes: vec![],
+            loaded: HashMap::new(),
+            stack: vec![],
+            files: vec![],
+            resolver: Resolver::default(),
+        };
+        let Ok(canonical) = Path::new(path).canonicalize() else {
+            panic!("Failed to open {path}.");
+        };
+        program.load_file(path, canonical, false);
+
+        for (file, nodes) in &mut program.files {
+            program.resolver.declare(file, nodes);
+        }
+        for (file, nodes) in &mut program.files {
+            program.resolver.resolve(file, nodes);
+        }
+
+        let mut consteval = ConstEval::default();
+        for (file, nodes) in &program.files {
+            consteval.declare(file, nodes);
+        }
+        for (file, nodes) in &mut program.files {
+            consteval.fold(file, nodes);
+        }
+
+        let mut checker = TypeChecker::default();
+        for (_, nodes) in &program.files {
+            checker.declare_functions(nodes);
+        }
+        for (file, nodes) in &program.files {
+            checker.declare(file, nodes);
+        }
+        for (file, nodes) in &program.files {
+            checker.check(file, nodes);
+        }
+        program.nodes = program
+            .files
+            .drain(..)
+            .flat_map(|(_, nodes)| nodes)
+            .collect();
+        program
+    }
+
+    fn load_file(&mut self, path: &str, canonical: PathBuf, is_import: bool) {
+        self.stack.push((canonical.clone(), path.to_string()));
+        let (nodes, imports) = parse_file(path, is_import);
+
+        let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        for import in imports {
+            let import_path = dir.join(&import.file).to_string_lossy().to_string();
+            let Ok(import_canonical) = Path::new(&import_path).canonicalize() else {
+                let label = format!("`{import_path}` could not be opened");
+                emit_error(path, "0013", import.span.clone(), &label, None);
+                unreachable!()
+            };
+
+            if let Some(start) = self
+                .stack
+                .iter()
+                .position(|(file, _)| *file == import_canonical)
+            {
+                let chain = self.stack[start..]
+                    .iter()
+                    .map(|(_, name)| name.as_str())
+                    .chain(std::iter::once(import_path.as_str()))
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+                let note = format!("The files import each other: {chain}");
+                emit_error(
+                    path,
+                    "0014",
+                    import.span.clone(),
+                    "this file is already being imported",
+                    Some(&note),
+                );
+            }
+
+            if !self.loaded.contains_key(&import_canonical) {
+                self.load_file(&import_path, import_canonical.clone(), true);
+            }
+            if !self.loaded[&import_canonical].contains(&import.module) {
+                let label = format!("`{import_path}` has no module named \"{}\"", import.module);
+                emit_error(path, "0015", import.span.clone(), &label, None);
+            }
+        }
+
+        let modules = nodes
+            .iter()
+            .filter_map(|node| match node {
+                Stmt::Module {
+                    name: LiteralExpr::String(name),
+                    ..
+                } => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+        self.loaded.insert(canonical, modules);
+        self.files.push((path.to_string(), nodes));
+        self.stack.pop();
+    }
+}
+
+/// Lexes and parses a single file.
+pub fn parse_file(path: &str, is_import: bool) -> (Vec<Stmt>, Vec<Import>) {
+    let input_str = std::fs::read_to_string(path).unwrap();
+
+    let mut lexer = Lexer {
+        file: path.to_string(),
+        source: input_str.chars().collect(),
+        results: ast::AST { ast: vec![] },
+        idx: 0,
+    };
+
+    lexer.lex_all();
+
+    let mut parser = p::Parser {
+        file: path.to_string(),
+        source: lexer.results.ast.into_iter().peekable(),
+        idx: 0,
+        nodes: vec![],
+        imports: vec![],
+        is_import,
+        source_str: lexer.source,
+        choices: HashMap::new(),
+    };
+    parser.parse();
+    (parser.nodes, parser.imports)
+}
+
+fn emit_error(file: &str, code: &str, span: Range<usize>, label: &str, note: Option<&str>) {
+    let mut error = ErrorClient::new(code, crate::errors::MessageKind::ERROR);
+    error.end_process(true);
+    error.set_file(file, file);
+    error.set_span(span);
+    error.add_label(Some(label));
+    if let Some(note) = note {
+        error.add_note(note);
+    }
+    error.build_and_emit();
+}
@@ -1,12 +1,19 @@
+#![allow(clippy::missing_safety_doc)]
+
 use std::ops::Range;
 
 use errors::ErrorClient;
 
 pub mod ast;
 pub mod cg;
+pub mod consteval;
 pub mod errors;
+pub mod imports;
 pub mod node;
 pub mod p;
+pub mod picture;
+pub mod resolve;
+pub mod typeck;
 
 // Non-plural list of words. Some of these may be plural, or end an S, which will be handled later on.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -33,11 +40,84 @@ pub enum Words {
     If,
     Then,
     Do,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Compute,
+    From,
+    By,
+    Into,
+    Giving,
+    Remainder,
+    Not,
+    Greater,
+    Less,
+    Than,
+    Or,
+    And,
+    Use,
+    Of,
+    Public,
+    Private,
+    As,
+    An,
+    Integer,
+    Decimal,
+    Boolean,
+    String,
+    True,
+    False,
+    Number,
+    Digit,
+    Rounded,
+    Up,
+    Down,
+    Even,
+    On,
+    Size,
+    Error,
+    Format,
+    Using,
+    Picture,
+    Text,
+    Character,
+    Move,
+    Space,
+    Zero,
+    HighValue,
+    LowValue,
+    Condition,
+    When,
+    Record,
+    Field,
+    Redefines,
+    List,
+    Item,
+    Append,
+    Table,
+    Entry,
+    Key,
+    For,
+    Each,
+    In,
+    Sort,
+    Sorted,
+    Ascending,
+    Descending,
+    Search,
+    Choice,
+    Evaluate,
+    Other,
+    Constant,
+    Join,
+    Through,
+    Length,
 }
 
 impl TryFrom<&str> for Words {
     type Error = String;
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
+    fn try_from(value: &str) -> Result<Self, String> {
         match value {
             "define" => Ok(Self::Define),
             "module" => Ok(Self::Module),
@@ -61,6 +141,79 @@ impl TryFrom<&str> for Words {
             "if" => Ok(Self::If),
             "then" => Ok(Self::Then),
             "do" => Ok(Self::Do),
+            "add" => Ok(Self::Add),
+            "subtract" => Ok(Self::Subtract),
+            "multiply" => Ok(Self::Multiply),
+            "divide" => Ok(Self::Divide),
+            "compute" => Ok(Self::Compute),
+            "from" => Ok(Self::From),
+            "by" => Ok(Self::By),
+            "into" => Ok(Self::Into),
+            "giving" => Ok(Self::Giving),
+            "remainder" => Ok(Self::Remainder),
+            "not" => Ok(Self::Not),
+            "greater" => Ok(Self::Greater),
+            "less" => Ok(Self::Less),
+            "than" => Ok(Self::Than),
+            "or" => Ok(Self::Or),
+            "and" => Ok(Self::And),
+            "use" => Ok(Self::Use),
+            "of" => Ok(Self::Of),
+            "public" => Ok(Self::Public),
+            "private" => Ok(Self::Private),
+            "as" => Ok(Self::As),
+            "an" => Ok(Self::An),
+            "integer" => Ok(Self::Integer),
+            "decimal" => Ok(Self::Decimal),
+            "boolean" => Ok(Self::Boolean),
+            "string" => Ok(Self::String),
+            "true" => Ok(Self::True),
+            "false" => Ok(Self::False),
+            "number" => Ok(Self::Number),
+            "digit" => Ok(Self::Digit),
+            "rounded" => Ok(Self::Rounded),
+            "up" => Ok(Self::Up),
+            "down" => Ok(Self::Down),
+            "even" => Ok(Self::Even),
+            "on" => Ok(Self::On),
+            "size" => Ok(Self::Size),
+            "error" => Ok(Self::Error),
+            "format" => Ok(Self::Format),
+            "using" => Ok(Self::Using),
+            "picture" => Ok(Self::Picture),
+            "text" => Ok(Self::Text),
+            "character" => Ok(Self::Character),
+            "move" => Ok(Self::Move),
+            "space" => Ok(Self::Space),
+            "zero" | "zeroes" => Ok(Self::Zero),
+            "high-value" => Ok(Self::HighValue),
+            "low-value" => Ok(Self::LowValue),
+            "condition" => Ok(Self::Condition),
+            "when" => Ok(Self::When),
+            "record" => Ok(Self::Record),
+            "field" => Ok(Self::Field),
+            "redefines" => Ok(Self::Redefines),
+            "list" => Ok(Self::List),
+            "item" => Ok(Self::Item),
+            "append" => Ok(Self::Append),
+            "table" => Ok(Self::Table),
+            "entry" => Ok(Self::Entry),
+            "key" => Ok(Self::Key),
+            "for" => Ok(Self::For),
+            "each" => Ok(Self::Each),
+            "in" => Ok(Self::In),
+            "sort" => Ok(Self::Sort),
+            "sorted" => Ok(Self::Sorted),
+            "ascending" => Ok(Self::Ascending),
+            "descending" => Ok(Self::Descending),
+            "search" => Ok(Self::Search),
+            "choice" => Ok(Self::Choice),
+            "evaluate" => Ok(Self::Evaluate),
+            "other" => Ok(Self::Other),
+            "constant" => Ok(Self::Constant),
+            "join" => Ok(Self::Join),
+            "through" => Ok(Self::Through),
+            "length" => Ok(Self::Length),
             _ => Err(String::from("Ye bad")),
         }
     }
@@ -91,6 +244,79 @@ impl From<Words> for String {
             Words::If => "if",
             Words::Then => "then",
             Words::Do => "do",
+            Words::Add => "add",
+            Words::Subtract => "subtract",
+            Words::Multiply => "multiply",
+            Words::Divide => "divide",
+            Words::Compute => "compute",
+            Words::From => "from",
+            Words::By => "by",
+            Words::Into => "into",
+            Words::Giving => "giving",
+            Words::Remainder => "remainder",
+            Words::Not => "not",
+            Words::Greater => "greater",
+            Words::Less => "less",
+            Words::Than => "than",
+            Words::Or => "or",
+            Words::And => "and",
+            Words::Use => "use",
+            Words::Of => "of",
+            Words::Public => "public",
+            Words::Private => "private",
+            Words::As => "as",
+            Words::An => "an",
+            Words::Integer => "integer",
+            Words::Decimal => "decimal",
+            Words::Boolean => "boolean",
+            Words::String => "string",
+            Words::True => "true",
+            Words::False => "false",
+            Words::Number => "number",
+            Words::Digit => "digit",
+            Words::Rounded => "rounded",
+            Words::Up => "up",
+            Words::Down => "down",
+            Words::Even => "even",
+            Words::On => "on",
+            Words::Size => "size",
+            Words::Error => "error",
+            Words::Format => "format",
+            Words::Using => "using",
+            Words::Picture => "picture",
+            Words::Text => "text",
+            Words::Character => "character",
+            Words::Move => "move",
+            Words::Space => "space",
+            Words::Zero => "zero",
+            Words::HighValue => "high-value",
+            Words::LowValue => "low-value",
+            Words::Condition => "condition",
+            Words::When => "when",
+            Words::Record => "record",
+            Words::Field => "field",
+            Words::Redefines => "redefines",
+            Words::List => "list",
+            Words::Item => "item",
+            Words::Append => "append",
+            Words::Table => "table",
+            Words::Entry => "entry",
+            Words::Key => "key",
+            Words::For => "for",
+            Words::Each => "each",
+            Words::In => "in",
+            Words::Sort => "sort",
+            Words::Sorted => "sorted",
+            Words::Ascending => "ascending",
+            Words::Descending => "descending",
+            Words::Search => "search",
+            Words::Choice => "choice",
+            Words::Evaluate => "evaluate",
+            Words::Other => "other",
+            Words::Constant => "constant",
+            Words::Join => "join",
+            Words::Through => "through",
+            Words::Length => "length",
         }
         .to_ascii_uppercase()
     }
@@ -123,24 +349,52 @@ pub enum Tokens {
     SemiColon,
     Colon,
     String,
+    Number,
     DollarSign,
     Period,
+    Equals,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    /// `&`, which joins strings while compiling.
+    Ampersand,
+    LeftParen,
+    RightParen,
+}
+
+impl From<Words> for Tokens {
+    fn from(which: Words) -> Tokens {
+        Tokens::Word(Word {
+            which,
+            plural: false,
+        })
+    }
 }
 
-impl ToString for Tokens {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for Tokens {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Tokens::SemiColon => ";".to_string(),
-            Tokens::Colon => ":".to_string(),
-            Tokens::DollarSign => "$".to_string(),
-            Tokens::Period => ".".to_string(),
-            Tokens::Word(word) => word.which.clone().into(),
-            _ => "Unable to turn into string.".to_string(),
+            Tokens::SemiColon => write!(f, ";"),
+            Tokens::Colon => write!(f, ":"),
+            Tokens::DollarSign => write!(f, "$"),
+            Tokens::Period => write!(f, "."),
+            Tokens::Equals => write!(f, "="),
+            Tokens::Plus => write!(f, "+"),
+            Tokens::Minus => write!(f, "-"),
+            Tokens::Star => write!(f, "*"),
+            Tokens::Slash => write!(f, "/"),
+            Tokens::Ampersand => write!(f, "&"),
+            Tokens::LeftParen => write!(f, "("),
+            Tokens::RightParen => write!(f, ")"),
+            Tokens::Word(word) => write!(f, "{}", String::from(word.which.clone())),
+            _ => write!(f, "Unable to turn into string."),
         }
     }
 }
 
 pub struct Lexer {
+    pub file: String,
     pub source: Vec<char>,
     pub idx: usize,
     pub results: ast::AST,
@@ -156,6 +410,14 @@ impl Lexer {
             ';' => self.push_back(Tokens::SemiColon, None),
             '$' => self.push_back(Tokens::DollarSign, None),
             '.' => self.push_back(Tokens::Period, None),
+            '=' => self.push_back(Tokens::Equals, None),
+            '+' => self.push_back(Tokens::Plus, None),
+            '-' => self.push_back(Tokens::Minus, None),
+            '*' => self.push_back(Tokens::Star, None),
+            '/' => self.push_back(Tokens::Slash, None),
+            '&' => self.push_back(Tokens::Ampersand, None),
+            '(' => self.push_back(Tokens::LeftParen, None),
+            ')' => self.push_back(Tokens::RightParen, None),
             '"' => {
                 let current_idx = self.idx;
                 while let Some(next) = self.peek(None) {
@@ -167,11 +429,31 @@ impl Lexer {
                 self.push_back(Tokens::String, Some(current_idx + 1..self.idx + 1));
                 self.advance(None);
             }
+            '0'..='9' => {
+                let current_idx = self.idx;
+                while let Some(next) = self.peek(None) {
+                    // A period only belongs to the number if a digit follows it, otherwise it ends the statement.
+                    let is_fraction = next == '.'
+                        && self
+                            .peek(Some(2))
+                            .is_some_and(|after| after.is_ascii_digit());
+                    if !next.is_ascii_digit() && !is_fraction {
+                        break;
+                    }
+                    self.advance(None);
+                }
+                self.push_back(Tokens::Number, Some(current_idx..self.idx + 1));
+            }
             _ => {
-                if ('a'..='z').contains(current) || ('A'..='Z').contains(current) {
+                if current.is_ascii_alphabetic() {
                     let current_idx = self.idx;
                     while let Some(next) = self.peek(None) {
-                        if next == ' ' || !next.is_alphabetic() {
+                        // A hyphen joins two parts of a word, like `HIGH-VALUES`.
+                        let is_hyphen = next == '-'
+                            && self
+                                .peek(Some(2))
+                                .is_some_and(|after| after.is_ascii_alphabetic());
+                        if !next.is_alphabetic() && !is_hyphen {
                             break;
                         }
                         self.advance(None);
@@ -179,28 +461,27 @@ impl Lexer {
                     let Some(keyword) = self.source.get(current_idx..self.idx + 1) else {
                         panic!("Failed to collect keyword from the source.");
                     };
-                    let keyword = keyword.iter().collect::<String>();
-                    if let Ok(word) = keyword.to_lowercase().as_str().try_into() {
+                    let keyword = keyword.iter().collect::<String>().to_lowercase();
+                    // Words that aren't known as they are written may be the plural of one that is.
+                    let word = match Words::try_from(keyword.as_str()) {
+                        Ok(word) => Some((word, false)),
+                        Err(_) => keyword
+                            .strip_suffix('s')
+                            .and_then(|singular| Words::try_from(singular).ok())
+                            .map(|word| (word, true)),
+                    };
+                    if let Some((which, plural)) = word {
                         self.push_back(
-                            Tokens::Word(Word {
-                                which: word,
-                                plural: false,
-                            }),
-                            Some(current_idx..self.idx),
+                            Tokens::Word(Word { which, plural }),
+                            Some(current_idx..self.idx + 1),
                         );
                     } else {
                         let mut error = ErrorClient::new("0002", crate::errors::MessageKind::ERROR);
                         error.end_process(true);
-                        // TODO: The parser should have a context field that is aware of this kind of stuff.
-                        error.set_file(
-                            "module_level_func.cbt",
-                            "./tests/pass/module_level_func.cbt",
-                        );
+                        error.set_file(&self.file, &self.file);
                         error.set_span(current_idx..self.idx + 1);
-                        let note = format!("This is not a valid keyword");
-                        error.add_label(Some(&note));
+                        error.add_label(Some("This is not a valid keyword"));
                         error.build_and_emit();
-                        return;
                     }
                 }
             }
@@ -250,31 +531,26 @@ impl Lexer {
 }
 
 fn main() {
-    let input_str = std::fs::read_to_string("tests/pass/module_level_func.cbt").unwrap();
-
-    let mut lexer = Lexer {
-        source: input_str.chars().collect(),
-        results: ast::AST { ast: vec![] },
-        idx: 0,
-    };
-
-    lexer.lex_all();
-
-    let mut parser = p::Parser {
-        source: lexer.results.ast.into_iter().peekable(),
-        idx: 0,
-        nodes: vec![],
-        source_str: lexer.source,
-    };
-    parser.parse();
+    let mut path = String::from("tests/pass/module_level_func.cbt");
+    let mut opt_level = 3;
+    for arg in std::env::args().skip(1) {
+        match arg.strip_prefix("-O") {
+            Some(level) => {
+                opt_level = match level.parse() {
+                    Ok(level @ 0..=3) => level,
+                    _ => panic!("Unknown optimization level `{arg}`, expected -O0 to -O3."),
+                }
+            }
+            None => path = arg,
+        }
+    }
+    let program = imports::Program::load(&path);
     unsafe {
         //cg::codegen(parser.nodes);
-        let mut codegen = cg::CodeGen::init(parser.nodes.into_iter().peekable());
+        let mut codegen = cg::CodeGen::init(program.nodes.into_iter().peekable());
+        codegen.opt_level = opt_level;
         codegen.setup_main_module();
-        let func = codegen.stmts.next().unwrap();
-        codegen.visit_fn(func);
-        let func = codegen.stmts.next().unwrap();
-        codegen.visit_fn(func);
+        codegen.visit_program();
         codegen.verify_and_dump();
     }
 }
@@ -1,18 +1,141 @@
+use std::ops::Range;
+
+use crate::picture::Picture;
+
 #[derive(Debug, Clone)]
 pub enum LiteralExpr {
     String(String),
+    Integer(i64),
+    /// Kept as it was written in the source so no precision is lost before codegen.
+    Decimal(String),
+    Boolean(bool),
+    Figurative(Figurative),
+}
+
+/// `SPACES`, `ZEROS`, `HIGH-VALUES` and `LOW-VALUES`. They have no type of their own: each
+/// one fills whatever it is stored in or compared with, so `SPACES` in a `TEXT OF 8 CHARACTERS`
+/// is 8 spaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Figurative {
+    Spaces,
+    Zeros,
+    /// The highest character, `0xFF`, which sorts after any other.
+    HighValues,
+    /// The lowest character, `0x00`. As it also ends a string, text filled with it reads as
+    /// empty everywhere but in comparisons with `LOW-VALUES` itself.
+    LowValues,
+}
+
+impl Figurative {
+    /// The character that text is filled with.
+    pub fn byte(&self) -> u8 {
+        match self {
+            Figurative::Spaces => b' ',
+            Figurative::Zeros => b'0',
+            Figurative::HighValues => 0xFF,
+            Figurative::LowValues => 0,
+        }
+    }
+
+    /// Whether the constant can stand for a value of type `ty`. Only `ZEROS` is a number.
+    pub fn fits(&self, ty: &VariableType) -> bool {
+        ty.is_text() || (*self == Figurative::Zeros && ty.is_numeric())
+    }
+
+    /// The type used where nothing else decides it, like in `DISPLAY`.
+    pub fn default_type(&self) -> VariableType {
+        match self {
+            Figurative::Zeros => VariableType::Integer,
+            _ => VariableType::String,
+        }
+    }
+}
+
+impl std::fmt::Display for Figurative {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Figurative::Spaces => write!(f, "SPACES"),
+            Figurative::Zeros => write!(f, "ZEROS"),
+            Figurative::HighValues => write!(f, "HIGH-VALUES"),
+            Figurative::LowValues => write!(f, "LOW-VALUES"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Identifies a definition (a module, function or variable) once names have been resolved.
+pub struct DefId(pub usize);
+
+#[derive(Debug, Clone)]
+/// A string that named a variable in scope, see `resolve`.
+pub struct Reference {
+    pub name: String,
+    pub id: DefId,
 }
 
 #[derive(Debug, Clone)]
 pub struct FunctionCall {
+    /// The name of the function, or the variable holding the function once the resolver has
+    /// found that the call goes through one.
     pub func: Box<Expr>,
+    /// Set for qualified calls, `CALL FUNCTION "total" OF MODULE "billing"`, and filled in for
+    /// every other call by the resolver.
+    pub module: Option<String>,
     pub args: Option<Vec<Box<Expr>>>,
+    /// The function a direct call calls, filled in by the resolver. Not set for C functions.
+    pub id: Option<DefId>,
+    /// Where the function name was written, for diagnostics.
+    pub span: Range<usize>,
+    /// The same place as `file:line:column`, for errors reported while the program runs.
+    pub location: String,
+}
+
+#[derive(Debug, Clone)]
+/// `FUNCTION "greet" [OF MODULE "people"]`, a function used as a value.
+pub struct FunctionRef {
+    pub name: String,
+    /// The module of the function, filled in by the resolver like for a call.
+    pub module: Option<String>,
+    /// The function, filled in by the resolver.
+    pub id: Option<DefId>,
+    /// Where the function name was written, for diagnostics.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+/// `"name" AS A STRING`, one of the arguments a function expects. It is a local variable of the
+/// function holding a copy of what the caller passed.
+pub struct Parameter {
+    pub name: String,
+    pub ty: VariableType,
+    /// The variable, filled in by the resolver.
+    pub id: Option<DefId>,
+    /// Where the name was written, for diagnostics.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+/// `"rate" OF MODULE "billing"`, a module-level variable used from outside of its module.
+pub struct ModuleVariable {
+    pub name: String,
+    pub module: String,
+    /// Where the variable name was written, for diagnostics.
+    pub span: Range<usize>,
 }
 
 #[derive(Debug, Clone)]
 /// This is short hand for a phrase (like `EQUAL TO`)
 pub enum BinaryOperators {
     EqualTo,
+    NotEqualTo,
+    GreaterThan,
+    LessThan,
+    GreaterThanOrEqualTo,
+    LessThanOrEqualTo,
+    /// Whether a string has another in it, a list has an item or a table has an entry for a key.
+    Contains,
+    /// `"rates" CONTAINS KEY "EUR"`, whether a table has an entry for the key.
+    ContainsKey,
 }
 
 #[derive(Debug, Clone)]
@@ -20,6 +143,76 @@ pub struct Binary {
     pub l: Box<Expr>,
     pub r: Box<Expr>,
     pub op: BinaryOperators,
+    /// Where the operator was written, for diagnostics.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ArithmeticOperators {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+#[derive(Debug, Clone)]
+pub struct Arithmetic {
+    pub l: Box<Expr>,
+    pub r: Box<Expr>,
+    pub op: ArithmeticOperators,
+    /// Where the operator was written, for diagnostics.
+    pub span: Range<usize>,
+    /// The same place as `file:line:column`, reported when there is no result.
+    pub location: String,
+}
+
+#[derive(Debug, Clone)]
+/// `"Hello, " & "world"`, two values joined into one string. `JOIN` and the variables named in
+/// a string like `"Hello, {name}!"` are joined the same way.
+pub struct Concat {
+    pub l: Box<Expr>,
+    pub r: Box<Expr>,
+    /// Where the `&` or `AND` was written, or the string with variables in it, for diagnostics.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+/// A string with braces in it, like `"Hello, {name}!"`. The resolver joins the variables named
+/// in braces with the text around them. `{{` and `}}` are braces that don't name anything.
+pub struct Interpolation {
+    pub text: String,
+    /// Where the string was written, for diagnostics.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub enum LogicalOperators {
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone)]
+/// `AND`/`OR` between two conditions. The right side is only evaluated when it can change the result.
+pub struct Logical {
+    pub l: Box<Expr>,
+    pub r: Box<Expr>,
+    pub op: LogicalOperators,
+    /// Where the operator was written, for diagnostics.
+    pub span: Range<usize>,
+}
+
+/// `WITH CONDITION "is_active" WHEN "A" OR "B"`, like a level-88 item in COBOL. Using the name
+/// tests whether the variable holds one of the values and `SET "is_active" TO TRUE` stores the
+/// first of them. The resolver expands both, so later passes never see the name.
+#[derive(Debug, Clone)]
+pub struct ConditionName {
+    pub name: String,
+    pub values: Vec<Expr>,
+    /// Filled in by the resolver.
+    pub id: Option<DefId>,
+    /// Where the name was written, for diagnostics.
+    pub span: Range<usize>,
 }
 
 #[derive(Debug, Clone)]
@@ -27,18 +220,611 @@ pub struct Condition {
     pub then: Box<Stmt>,
     pub el: Option<Box<Stmt>>,
     pub condition: Box<Expr>,
+    /// Where the condition starts, for diagnostics.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+/// `FORMAT "amount" AS "$ZZ,ZZ9.99"`, a number edited into a string.
+pub struct Edited {
+    pub value: Box<Expr>,
+    pub picture: Picture,
+    /// Where `FORMAT` was written, for diagnostics.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+/// `"name" OF "customer"`, a field of a record.
+pub struct Member {
+    pub record: Box<Expr>,
+    pub field: String,
+    /// Where the field name was written, for diagnostics.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+/// `ITEM 3 OF "prices"`, an item of a list.
+pub struct Item {
+    pub list: Box<Expr>,
+    pub index: Index,
+}
+
+#[derive(Debug, Clone)]
+/// `ENTRY "USD" OF "rates"`, the value of a table for a key.
+pub struct Entry {
+    pub table: Box<Expr>,
+    pub key: Index,
+}
+
+#[derive(Debug, Clone)]
+/// `CHARACTERS 1 THROUGH 5 OF "name"`, a copy of part of a string. Characters are numbered from
+/// 1 and using any that aren't there stops the program.
+pub struct Characters {
+    pub text: Box<Expr>,
+    pub from: Index,
+    pub through: Index,
+}
+
+#[derive(Debug, Clone)]
+/// `LENGTH OF "name"`, how many characters a string has.
+pub struct Length {
+    pub text: Box<Expr>,
+    /// Where `LENGTH` was written, for diagnostics.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+/// Which item of a list or entry of a table is used. Items are numbered from 1. Reading an
+/// item or entry that isn't there stops the program.
+pub struct Index {
+    pub value: Box<Expr>,
+    /// Where the index was written, for diagnostics.
+    pub span: Range<usize>,
+    /// The same place as `file:line:column`, reported when there is no such item or entry.
+    pub location: String,
+}
+
+#[derive(Debug, Clone)]
+/// One step from a variable into the part of it that is stored into.
+pub enum Access {
+    Field(String),
+    Item(Index),
+    /// Storing into an entry adds it when the table doesn't have it yet.
+    Entry(Index),
 }
 
 #[derive(Debug, Clone)]
 pub enum Expr {
     Literal(LiteralExpr),
+    Variable(Reference),
     Call(FunctionCall),
     BinaryOp(Binary),
+    Arithmetic(Arithmetic),
+    Negate(Box<Expr>),
+    Logical(Logical),
+    Not(Box<Expr>),
+    ModuleVariable(ModuleVariable),
+    Edited(Edited),
+    Member(Member),
+    Item(Item),
+    Entry(Entry),
+    Function(FunctionRef),
+    Concat(Concat),
+    Interpolation(Interpolation),
+    Characters(Characters),
+    Length(Length),
+}
+
+impl Expr {
+    /// Whether this is a literal, or a negated number, which is what constants are made of.
+    pub fn is_literal(&self) -> bool {
+        match self {
+            Expr::Literal(_) => true,
+            Expr::Negate(inner) => matches!(
+                **inner,
+                Expr::Literal(LiteralExpr::Integer(_) | LiteralExpr::Decimal(_))
+            ),
+            _ => false,
+        }
+    }
+
+    /// Whether this expression produces a truth value rather than a string or a number.
+    pub fn is_condition(&self) -> bool {
+        matches!(self, Expr::BinaryOp(_) | Expr::Logical(_) | Expr::Not(_))
+    }
+
+    /// The variable and the fields and items within it that this expression names, if it can
+    /// be stored into: `"street" OF "address" OF "customer"` is `customer` with `address` and
+    /// then `street`.
+    pub fn as_target(&self) -> Option<(String, Vec<Access>)> {
+        match self {
+            Expr::Literal(LiteralExpr::String(name)) => Some((name.clone(), Vec::new())),
+            Expr::Variable(Reference { name, .. }) => Some((name.clone(), Vec::new())),
+            Expr::Member(member) => {
+                let (target, mut path) = member.record.as_target()?;
+                path.push(Access::Field(member.field.clone()));
+                Some((target, path))
+            }
+            Expr::Item(item) => {
+                let (target, mut path) = item.list.as_target()?;
+                path.push(Access::Item(item.index.clone()));
+                Some((target, path))
+            }
+            Expr::Entry(entry) => {
+                let (target, mut path) = entry.table.as_target()?;
+                path.push(Access::Entry(entry.key.clone()));
+                Some((target, path))
+            }
+            _ => None,
+        }
+    }
+
+    /// A decimal literal, possibly negated, as an exact scaled integer and the number of
+    /// decimals it was written with. Used in place of a `DECIMAL` next to a `NUMBER`, so
+    /// `"amount" + 0.10` stays exact.
+    pub fn fixed_literal(&self) -> Option<(i128, u32)> {
+        match self {
+            Expr::Literal(LiteralExpr::Decimal(text)) => {
+                let (_, decimals) = text.split_once('.')?;
+                let scale = decimals.len() as u32;
+                Some((self.scaled_literal(scale)?, scale))
+            }
+            Expr::Negate(inner) => inner.fixed_literal().map(|(value, scale)| (-value, scale)),
+            _ => None,
+        }
+    }
+
+    /// A literal number, possibly negated, multiplied by `10^scale` with any decimals past
+    /// `scale` dropped. This is how a literal is stored in a `NUMBER` with `scale` decimals.
+    pub fn scaled_literal(&self, scale: u32) -> Option<i128> {
+        match self {
+            Expr::Literal(LiteralExpr::Integer(value)) => {
+                (*value as i128).checked_mul(10i128.checked_pow(scale)?)
+            }
+            Expr::Literal(LiteralExpr::Decimal(text)) => {
+                let (whole, decimals) = text.split_once('.').unwrap_or((text, ""));
+                let decimals = decimals
+                    .chars()
+                    .chain(std::iter::repeat('0'))
+                    .take(scale as usize)
+                    .collect::<String>();
+                format!("{whole}{decimals}").parse().ok()
+            }
+            Expr::Negate(inner) => inner.scaled_literal(scale).map(|value| -value),
+            Expr::Literal(LiteralExpr::Figurative(Figurative::Zeros)) => Some(0),
+            _ => None,
+        }
+    }
 }
 
+/// The most digits a `NUMBER` can have, so any value fits in an `i64`.
+pub const MAX_DIGITS: u32 = 18;
+
+/// The most characters a `TEXT` can hold.
+pub const MAX_TEXT_LENGTH: u32 = 65535;
+
+/// The most items a list with a length can hold.
+pub const MAX_LIST_LENGTH: u32 = 65535;
+
+/// Decimals kept by a division of two `NUMBER`s on top of the most either operand has, so
+/// the result can still be rounded into the target.
+pub const DIVISION_DECIMALS: u32 = 6;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum VariableType {
     String,
+    Integer,
+    Decimal,
+    Boolean,
+    /// `AS A NUMBER WITH 7 DIGITS AND 2 DECIMALS`. Stored as an integer counting
+    /// hundredths (or whatever `scale` says) so arithmetic on it is exact. `digits` includes
+    /// the decimals.
+    Fixed {
+        digits: u32,
+        scale: u32,
+    },
+    /// `AS TEXT OF 20 CHARACTERS`. Always holds exactly `length` characters, shorter values are
+    /// padded with spaces and longer ones cut off.
+    Text {
+        length: u32,
+    },
+    /// `DEFINE RECORD "customer" WITH FIELDS: ...`, a group of fields laid out one after
+    /// another. Records with the same fields have the same type.
+    Record(Vec<Field>),
+    /// `DEFINE LIST "prices" OF 12 DECIMALS.` A list defined without a length starts empty
+    /// and grows with `APPEND`.
+    List {
+        item: Box<VariableType>,
+        length: Option<u32>,
+        /// The order the program promises to keep the items in, written `SORTED` before `OF`,
+        /// which lets `SEARCH` use a binary search.
+        sorted: Option<SortKey>,
+    },
+    /// `DEFINE TABLE "rates" FROM TEXT TO DECIMAL.`, values looked up by a `STRING` key. It
+    /// starts empty and an entry is added by storing into it.
+    Table {
+        key: Box<VariableType>,
+        value: Box<VariableType>,
+    },
+    /// `AS A FUNCTION WITH THE ARGUMENTS A STRING AN INTEGER`, a function that expects arguments
+    /// of these types. Stored as a pointer to it, which is null until one is stored.
+    Function {
+        parameters: Vec<VariableType>,
+    },
+    /// `SET "light" AS A "color" TO "red".`, one of the values of a `DEFINE CHOICE`. Stored as
+    /// the number of the value, counting from 0.
+    Choice {
+        name: String,
+        values: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: String,
+    pub ty: VariableType,
+    /// The earlier field whose storage this one is another view of, like COBOL's `REDEFINES`.
+    pub redefines: Option<String>,
+    pub span: Range<usize>,
+}
+
+impl PartialEq for Field {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.ty == other.ty && self.redefines == other.redefines
+    }
+}
+
+impl Eq for Field {}
+
+#[derive(Debug, Clone)]
+/// `BY "balance" DESCENDING`, one key of a `SORT` or the order of a `SORTED` list.
+pub struct SortKey {
+    /// The field of the records that is compared. Without one the items themselves, or the
+    /// keys of a table, are.
+    pub field: Option<String>,
+    pub descending: bool,
+    pub span: Range<usize>,
+}
+
+impl PartialEq for SortKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.field == other.field && self.descending == other.descending
+    }
+}
+
+impl Eq for SortKey {}
+
+impl std::fmt::Display for SortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SORTED")?;
+        if let Some(field) = &self.field {
+            write!(f, " BY \"{field}\"")?;
+        }
+        if self.descending {
+            write!(f, " DESCENDING")?;
+        }
+        Ok(())
+    }
+}
+
+impl VariableType {
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            VariableType::Integer | VariableType::Decimal | VariableType::Fixed { .. }
+        )
+    }
+
+    /// Whether values of this type are characters, either a `STRING` or a `TEXT`.
+    pub fn is_text(&self) -> bool {
+        matches!(self, VariableType::String | VariableType::Text { .. })
+    }
+
+    /// Whether this type, or the type of any field or item within it, passes `test`.
+    pub fn any(&self, test: &dyn Fn(&VariableType) -> bool) -> bool {
+        test(self)
+            || match self {
+                VariableType::Record(fields) => fields.iter().any(|field| field.ty.any(test)),
+                VariableType::List { item, .. } => item.any(test),
+                VariableType::Table { value, .. } => value.any(test),
+                _ => false,
+            }
+    }
+
+    /// The field called `name`, if this is a record that has one.
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        let VariableType::Record(fields) = self else {
+            return None;
+        };
+        fields.iter().find(|field| field.name == name)
+    }
+
+    /// Where the field called `name` is kept among the fields that have storage of their own.
+    /// An overlay shares the place of the field it redefines.
+    pub fn slot(&self, name: &str) -> Option<usize> {
+        let VariableType::Record(fields) = self else {
+            return None;
+        };
+        let name = self.field(name)?.redefines.as_deref().unwrap_or(name);
+        fields
+            .iter()
+            .filter(|field| field.redefines.is_none())
+            .position(|field| field.name == name)
+    }
+
+    /// How many bytes a value of this type takes up in a record. A `STRING`, a list that grows
+    /// and a table only hold pointers to their contents, so they have no size that could be
+    /// overlaid.
+    pub fn size(&self) -> Option<u32> {
+        match self {
+            VariableType::String | VariableType::Function { .. } => None,
+            VariableType::Integer | VariableType::Decimal | VariableType::Fixed { .. } => Some(8),
+            VariableType::Boolean | VariableType::Choice { .. } => Some(1),
+            VariableType::Text { length } => Some(*length),
+            VariableType::Record(fields) => fields
+                .iter()
+                .filter(|field| field.redefines.is_none())
+                .map(|field| field.ty.size())
+                .sum(),
+            VariableType::List {
+                item,
+                length: Some(length),
+                ..
+            } => item.size().map(|size| size * length),
+            VariableType::List { length: None, .. } | VariableType::Table { .. } => None,
+        }
+    }
+
+    /// The number of decimals a `NUMBER` is kept with, integers have none.
+    pub fn scale(&self) -> Option<u32> {
+        match self {
+            VariableType::Integer => Some(0),
+            VariableType::Fixed { scale, .. } => Some(*scale),
+            _ => None,
+        }
+    }
+
+    /// The type of `l op r` for two numbers. Integers stay integers and anything with a
+    /// `DECIMAL` is done in floating point. Otherwise the result is an exact `NUMBER` with as
+    /// many decimals as the operation needs, intermediate results are not limited in digits.
+    pub fn arithmetic(l: &VariableType, r: &VariableType, op: &ArithmeticOperators) -> Self {
+        match (l.scale(), r.scale()) {
+            (Some(_), Some(_)) if *l == VariableType::Integer && *r == VariableType::Integer => {
+                VariableType::Integer
+            }
+            (Some(l), Some(r)) => VariableType::Fixed {
+                digits: 2 * MAX_DIGITS,
+                scale: match op {
+                    ArithmeticOperators::Multiply => l + r,
+                    ArithmeticOperators::Divide => l.max(r) + DIVISION_DECIMALS,
+                    _ => l.max(r),
+                },
+            },
+            _ => VariableType::Decimal,
+        }
+    }
+}
+
+fn plural(count: u32) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "S"
+    }
+}
+
+/// Written the way the type is declared, `SET "n" AS AN INTEGER ...`.
+impl std::fmt::Display for VariableType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VariableType::String => write!(f, "STRING"),
+            VariableType::Integer => write!(f, "INTEGER"),
+            VariableType::Decimal => write!(f, "DECIMAL"),
+            VariableType::Boolean => write!(f, "BOOLEAN"),
+            VariableType::Fixed { digits, scale } => {
+                write!(f, "NUMBER WITH {digits} DIGIT{}", plural(*digits))?;
+                if *scale > 0 {
+                    write!(f, " AND {scale} DECIMAL{}", plural(*scale))?;
+                }
+                Ok(())
+            }
+            VariableType::Text { length } => {
+                write!(f, "TEXT OF {length} CHARACTER{}", plural(*length))
+            }
+            VariableType::Choice { name, .. } => write!(f, "\"{name}\""),
+            VariableType::Function { parameters } => {
+                write!(f, "FUNCTION")?;
+                if !parameters.is_empty() {
+                    let count = parameters.len() as u32;
+                    let parameters: Vec<_> = parameters.iter().map(|ty| ty.to_string()).collect();
+                    write!(
+                        f,
+                        " WITH THE ARGUMENT{} {}",
+                        plural(count),
+                        parameters.join(", ")
+                    )?;
+                }
+                Ok(())
+            }
+            VariableType::Record(fields) => {
+                let names: Vec<_> = fields.iter().map(|f| format!("\"{}\"", f.name)).collect();
+                write!(f, "RECORD WITH FIELDS {}", names.join(", "))
+            }
+            VariableType::List {
+                item,
+                length,
+                sorted,
+            } => {
+                write!(f, "LIST ")?;
+                if let Some(sorted) = sorted {
+                    write!(f, "{sorted} ")?;
+                }
+                write!(f, "OF {item}")?;
+                if let Some(length) = length {
+                    write!(f, " WITH {length} ITEM{}", plural(*length))?;
+                }
+                Ok(())
+            }
+            VariableType::Table { key, value } => write!(f, "TABLE FROM {key} TO {value}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A single `target = value` produced by one of the arithmetic verbs.
+pub struct Store {
+    pub target: String,
+    /// The fields and items within `target` that are stored into, outermost first.
+    pub path: Vec<Access>,
+    pub value: Expr,
+    /// Filled in by the resolver.
+    pub id: Option<DefId>,
+    /// How decimals that don't fit in the target are dropped, set with `ROUNDED`.
+    pub rounding: Rounding,
+    /// Where the target was written, for diagnostics.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// What happens to the decimals a target has no room for.
+pub enum Rounding {
+    /// Without `ROUNDED` they are cut off, as is `ROUNDED DOWN`.
+    #[default]
+    Truncate,
+    /// `ROUNDED`, halves round away from zero.
+    Nearest,
+    /// `ROUNDED UP`, anything left over rounds away from zero.
+    Up,
+    /// `ROUNDED TO EVEN`, halves round to the closest even digit.
+    Even,
+}
+
+#[derive(Debug, Clone)]
+/// `ADD`, `SUBTRACT`, `MULTIPLY`, `DIVIDE` and `COMPUTE` all end up here. Every value is
+/// evaluated before any target is written, so `DIVIDE ... REMAINDER` sees the original operands.
+pub struct Compute {
+    pub stores: Vec<Store>,
+    /// The statements after `ON SIZE ERROR`. They run when a result has more digits than a
+    /// `NUMBER` target can hold, that target is then left as it was.
+    pub on_size_error: Option<Box<Stmt>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Whether a function or module-level `SET` can be used outside of its module.
+pub enum Visibility {
+    #[default]
+    Public,
+    Private,
+}
+
+#[derive(Debug, Clone)]
+/// `USE MODULE "billing" FROM "billing.cbt".` The path is relative to the importing file.
+pub struct Import {
+    pub module: String,
+    pub file: String,
+    /// Where the file name was written, for diagnostics.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+/// `FOR EACH "price" IN "prices" DO ... END FOR.` runs the body with a copy of each item of a
+/// list, or each key of a table in the order they were added.
+pub struct ForEach {
+    pub variable: String,
+    /// The variable the loop defines, filled in by the resolver.
+    pub id: Option<DefId>,
+    pub collection: Expr,
+    pub body: Box<Stmt>,
+    /// Where the variable name was written, for diagnostics.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+/// `DEFINE CONSTANT "tax_rate" AS 0.07.` names a value that is worked out while compiling. Every
+/// use of the name is replaced by the value, and it can't be changed.
+pub struct Constant {
+    pub name: String,
+    pub value: Expr,
+    /// Filled in by the resolver.
+    pub id: Option<DefId>,
+    /// Where the name was written, for diagnostics.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+/// `DEFINE CHOICE "color" AS "red", "green" OR "blue".` names a type whose values are one of a
+/// few names.
+pub struct Choice {
+    pub name: String,
+    pub values: Vec<String>,
+    /// Where the name of the choice was written, for diagnostics.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+/// `EVALUATE "light" WHEN "red" DO ... WHEN OTHER DO ... END EVALUATE.` runs the statements of
+/// the first `WHEN` with a value equal to the subject, or those of `WHEN OTHER` if none has one.
+/// Over a choice, every value has to be handled.
+pub struct Evaluate {
+    pub subject: Expr,
+    pub branches: Vec<When>,
+    pub other: Option<Box<Stmt>>,
+    /// Where the subject was written, for diagnostics.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+/// `WHEN "green" OR "blue" DO ...`, one branch of an `EVALUATE`.
+pub struct When {
+    pub values: Vec<Expr>,
+    pub body: Box<Stmt>,
+    /// Where the first value was written, for diagnostics.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+/// `SORT "accounts" BY "balance" DESCENDING THEN BY "name".` puts the items of a list, or the
+/// entries of a table, in order. Items that compare equal keep the order they were in.
+pub struct Sort {
+    pub collection: Expr,
+    /// The first key decides, the ones after `THEN` break ties. There always is one, which
+    /// has no field in `SORT "prices".`
+    pub keys: Vec<SortKey>,
+    /// Where the list or table was written, for diagnostics.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+/// `SEARCH "accounts" BY "id" FOR 42 GIVING "index".` stores the number of the first item
+/// equal to the value, or 0 when there is none. A `SORTED` list searched by its own key is
+/// searched by halving.
+pub struct Search {
+    pub collection: Expr,
+    /// The field of the records that is compared, and where it was written.
+    pub field: Option<(String, Range<usize>)>,
+    /// Where the number of the item is stored. Its value is the one searched for, which is
+    /// resolved and checked with the target like that of a `MOVE`.
+    pub giving: Store,
+    /// Where the list was written, for diagnostics.
+    pub span: Range<usize>,
+}
+
+impl Search {
+    /// The field the items of `list` are compared by: the one after `BY`, or else the one the
+    /// list is `SORTED` by.
+    pub fn key<'a>(&'a self, list: &'a VariableType) -> Option<&'a str> {
+        match (&self.field, list) {
+            (Some((field, _)), _) => Some(field),
+            (
+                None,
+                VariableType::List {
+                    sorted: Some(sorted),
+                    ..
+                },
+            ) => sorted.field.as_deref(),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -47,16 +833,48 @@ pub enum Stmt {
     Module {
         name: LiteralExpr,
         nodes: Box<Stmt>,
+        /// Where the module name was written, for diagnostics.
+        span: Range<usize>,
     },
     Expr(Expr),
     Variable {
         name: String,
         ty: VariableType,
         value: Option<Expr>,
+        visibility: Visibility,
+        /// The variable this `SET` defines or assigns to, filled in by the resolver.
+        id: Option<DefId>,
+        /// Where the variable name was written, for diagnostics.
+        span: Range<usize>,
+        /// Names given to some of the variable's values with `WITH CONDITION`.
+        conditions: Vec<ConditionName>,
     },
     Function {
         name: LiteralExpr,
+        parameters: Vec<Parameter>,
         nodes: Box<Stmt>,
+        visibility: Visibility,
+        /// The function this defines, filled in by the resolver.
+        id: Option<DefId>,
+        /// Where the function name was written, for diagnostics.
+        span: Range<usize>,
     },
     Condition(Condition),
+    Compute(Compute),
+    /// `MOVE "a" TO "b".` copies a value into a variable that already exists, padding or cutting
+    /// it off to fit a `TEXT`.
+    Move(Store),
+    Display {
+        exprs: Vec<Expr>,
+        /// Where `DISPLAY` was written, for diagnostics.
+        span: Range<usize>,
+    },
+    /// `APPEND "Ada" TO "names".` adds an item to the end of a list defined without a length.
+    Append(Store),
+    ForEach(ForEach),
+    Sort(Sort),
+    Search(Search),
+    Choice(Choice),
+    Evaluate(Evaluate),
+    Constant(Constant),
 }
@@ -1,292 +1,1819 @@
-use codespan_reporting::diagnostic::{Diagnostic, Label};
-use codespan_reporting::files::SimpleFiles;
-use codespan_reporting::term;
-use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
+use std::collections::HashMap;
 use std::iter::Peekable;
+use std::ops::Range;
 
-use crate::cg::var;
-use crate::node::{Binary, BinaryOperators, Condition, FunctionCall};
+use crate::node::{
+    Access, Arithmetic, ArithmeticOperators, Binary, BinaryOperators, Characters, Choice, Compute,
+    Concat, Condition, ConditionName, Constant, Edited, Entry, Evaluate, Field, Figurative,
+    ForEach, FunctionCall, FunctionRef, Import, Index, Interpolation, Item, Length, Logical,
+    LogicalOperators, Member, ModuleVariable, Parameter, Rounding, Search, Sort, SortKey, Store,
+    When, MAX_DIGITS, MAX_LIST_LENGTH, MAX_TEXT_LENGTH,
+};
 use crate::{
     errors::ErrorClient,
-    node::{Expr, LiteralExpr, Stmt, VariableType},
+    node::{Expr, LiteralExpr, Stmt, VariableType, Visibility},
+    picture::Picture,
     Token, Tokens, Word, Words,
 };
 
 pub enum BlockType {
     Named(String, usize),
     Unamed(usize),
+    /// The `ON SIZE ERROR` block of an arithmetic verb, which ends with `END <verb>.`
+    Verb(Words),
 }
 
 pub struct Parser<T>
 where
     T: Iterator<Item = Token>,
 {
+    pub file: String,
     pub source: Peekable<T>,
     pub source_str: Vec<char>,
     pub idx: usize,
     pub nodes: Vec<Stmt>,
+    /// The `USE MODULE` statements of the file, loaded by the driver once parsing is done.
+    pub imports: Vec<Import>,
+    /// Imported files may only define modules, their statements would end up in another file's `main`.
+    pub is_import: bool,
+    /// The values of every `DEFINE CHOICE` so far, which has to come before the choice is used
+    /// as a type.
+    pub choices: HashMap<String, Vec<String>>,
 }
 
-impl<'a, T> Parser<T>
+impl<T> Parser<T>
 where
     T: Iterator<Item = Token>,
 {
     pub fn parse(&mut self) {
-        while let Some(stmt) = self.parse_stmt() {
+        let mut explicit_main = None;
+        let mut implicit_main = None;
+        while self.source.peek().is_some() {
+            let span = self.current_span();
+            if self.expect(Tokens::Word(Word {
+                which: Words::Use,
+                plural: false,
+            })) {
+                self.parse_use();
+                continue;
+            }
+            let Some(stmt) = self.parse_stmt() else {
+                self.emit_error("0008", span, "Expected a statement or a definition here");
+                unreachable!()
+            };
+            match &stmt {
+                Stmt::Function {
+                    name: LiteralExpr::String(name),
+                    ..
+                } if name == "main" => explicit_main = Some(span),
+                Stmt::Variable {
+                    visibility: Visibility::Private,
+                    ..
+                } => self.emit_error("0016", span, "Only a `SET` in a module can be private"),
+                Stmt::Module { .. } | Stmt::Choice(_) => {}
+                _ if self.is_import => self.emit_error(
+                    "0010",
+                    span,
+                    "Only `DEFINE MODULE` and `USE MODULE` are allowed in an imported file",
+                ),
+                // Constants outside of any function can be used by every function of `main`.
+                Stmt::Function { .. } | Stmt::Constant(_) => {}
+                _ => {
+                    implicit_main.get_or_insert(span);
+                }
+            }
             self.nodes.push(stmt);
         }
+
+        // Statements outside of any function are compiled into `main`, so they can't be mixed with one.
+        if let (Some(explicit), Some(implicit)) = (explicit_main, implicit_main) {
+            let mut error = ErrorClient::new("0007", crate::errors::MessageKind::ERROR);
+            error.end_process(true);
+            error.set_file(&self.file, &self.file);
+            error.set_span(explicit);
+            error.add_label(Some("`main` is defined here"));
+            error.add_secondary_label(implicit, "but this statement is outside of any function");
+            error.add_note("Move the top-level statements into `main`, or remove `main`.");
+            error.build_and_emit();
+        }
+    }
+
+    /// `USE MODULE "billing" FROM "billing.cbt".`, with `USE` already consumed.
+    pub fn parse_use(&mut self) {
+        self.expect_and_skip(vec![Tokens::Word(Word {
+            which: Words::Module,
+            plural: false,
+        })]);
+        let module = self.parse_module_name();
+        self.expect_and_skip(vec![Tokens::Word(Word {
+            which: Words::From,
+            plural: false,
+        })]);
+        let span = self.current_span();
+        let Some(Expr::Literal(LiteralExpr::String(file))) = self.parse_string() else {
+            self.emit_error("0011", span, "Expected the path of a file here");
+            unreachable!()
+        };
+        self.advance();
+        self.expect_and_skip(vec![Tokens::Period]);
+        self.imports.push(Import { module, file, span });
+    }
+
+    pub fn parse_module_name(&mut self) -> String {
+        let span = self.current_span();
+        let Some(Expr::Literal(LiteralExpr::String(name))) = self.parse_string() else {
+            self.emit_error("0012", span, "Expected the name of a module here");
+            unreachable!()
+        };
+        self.advance();
+        name
     }
+
+    /// Parses an infix expression. `*` and `/` bind tighter than `+` and `-`, and parentheses
+    /// can be used to group.
     pub fn parse_expr(&mut self) -> Option<Expr> {
-        if let Some(current) = self.source.peek() {
-            match current.inner {
-                Tokens::String => {
-                    let Some(lit) = self.parse_string() else {
-                        panic!("Failed to parse function name");
-                    };
+        let left = self.parse_term()?;
+        Some(self.parse_expr_from(left))
+    }
+
+    /// Continues an additive expression whose first operand has already been parsed. `&` joins
+    /// strings at the same level as `+` and `-`.
+    pub fn parse_expr_from(&mut self, mut left: Expr) -> Expr {
+        loop {
+            let span = self.current_span();
+            let op = match self.source.peek().map(|token| &token.inner) {
+                Some(Tokens::Plus) => ArithmeticOperators::Add,
+                Some(Tokens::Minus) => ArithmeticOperators::Subtract,
+                Some(Tokens::Ampersand) => {
                     self.advance();
-                    return Some(lit);
+                    let right = self.parse_required(Self::parse_term);
+                    left = Expr::Concat(Concat {
+                        l: Box::new(left),
+                        r: Box::new(right),
+                        span,
+                    });
+                    continue;
                 }
-                _ => {
-                    panic!("Unsupported.")
+                _ => break,
+            };
+            self.advance();
+            let right = self.parse_required(Self::parse_term);
+            left = Expr::Arithmetic(Arithmetic {
+                l: Box::new(left),
+                r: Box::new(right),
+                op,
+                location: self.location(&span),
+                span,
+            });
+        }
+        left
+    }
+
+    pub fn parse_term(&mut self) -> Option<Expr> {
+        let left = self.parse_unary()?;
+        Some(self.parse_term_from(left))
+    }
+
+    pub fn parse_term_from(&mut self, mut left: Expr) -> Expr {
+        loop {
+            let span = self.current_span();
+            let op = match self.source.peek().map(|token| &token.inner) {
+                Some(Tokens::Star) => ArithmeticOperators::Multiply,
+                Some(Tokens::Slash) => ArithmeticOperators::Divide,
+                _ => break,
+            };
+            self.advance();
+            let right = self.parse_required(Self::parse_unary);
+            left = Expr::Arithmetic(Arithmetic {
+                l: Box::new(left),
+                r: Box::new(right),
+                op,
+                location: self.location(&span),
+                span,
+            });
+        }
+        left
+    }
+
+    pub fn parse_unary(&mut self) -> Option<Expr> {
+        if self.expect(Tokens::Minus) {
+            let inner = self.parse_required(Self::parse_unary);
+            return Some(Expr::Negate(Box::new(inner)));
+        }
+        self.parse_primary()
+    }
+
+    pub fn parse_primary(&mut self) -> Option<Expr> {
+        let current = self.source.peek()?;
+        match current.inner {
+            Tokens::String => {
+                let span = self.current_span();
+                let lit = self.parse_string();
+                self.advance();
+                if !self.expect(Words::Of.into()) {
+                    return match lit {
+                        Some(Expr::Literal(LiteralExpr::String(text)))
+                            if text.contains(['{', '}']) =>
+                        {
+                            Some(Expr::Interpolation(Interpolation { text, span }))
+                        }
+                        lit => lit,
+                    };
+                }
+                if self.expect(Words::Module.into()) {
+                    let Some(Expr::Literal(LiteralExpr::String(name))) = lit else {
+                        unreachable!()
+                    };
+                    let module = self.parse_module_name();
+                    return Some(Expr::ModuleVariable(ModuleVariable { name, module, span }));
                 }
+                // `"street" OF "address" OF "customer"` picks fields from the inside out.
+                let Some(Expr::Literal(LiteralExpr::String(field))) = lit else {
+                    unreachable!()
+                };
+                let record = self.parse_container();
+                Some(Expr::Member(Member {
+                    record: Box::new(record),
+                    field,
+                    span,
+                }))
+            }
+            Tokens::Word(Word {
+                which: Words::Item, ..
+            }) => {
+                self.advance();
+                let index = self.parse_index();
+                self.expect_and_skip(vec![Words::Of.into()]);
+                let list = self.parse_container();
+                Some(Expr::Item(Item {
+                    list: Box::new(list),
+                    index,
+                }))
+            }
+            Tokens::Word(Word {
+                which: Words::Entry,
+                ..
+            }) => {
+                self.advance();
+                let key = self.parse_index();
+                self.expect_and_skip(vec![Words::Of.into()]);
+                let table = self.parse_container();
+                Some(Expr::Entry(Entry {
+                    table: Box::new(table),
+                    key,
+                }))
+            }
+            Tokens::Word(Word {
+                which: Words::Character,
+                ..
+            }) => {
+                self.advance();
+                let from = self.parse_index();
+                self.expect_and_skip(vec![Words::Through.into()]);
+                let through = self.parse_index();
+                self.expect_and_skip(vec![Words::Of.into()]);
+                let text = self.parse_container();
+                Some(Expr::Characters(Characters {
+                    text: Box::new(text),
+                    from,
+                    through,
+                }))
+            }
+            Tokens::Word(Word {
+                which: Words::Length,
+                ..
+            }) => {
+                let span = self.current_span();
+                self.advance();
+                self.expect_and_skip(vec![Words::Of.into()]);
+                let text = self.parse_container();
+                Some(Expr::Length(Length {
+                    text: Box::new(text),
+                    span,
+                }))
             }
+            Tokens::Number => {
+                let lit = self.parse_number();
+                self.advance();
+                lit
+            }
+            Tokens::Word(Word {
+                which: ref which @ (Words::True | Words::False),
+                ..
+            }) => {
+                let value = *which == Words::True;
+                self.advance();
+                Some(Expr::Literal(LiteralExpr::Boolean(value)))
+            }
+            Tokens::Word(Word {
+                which: ref which @ (Words::Space | Words::Zero | Words::HighValue | Words::LowValue),
+                ..
+            }) => {
+                let figurative = match which {
+                    Words::Space => Figurative::Spaces,
+                    Words::Zero => Figurative::Zeros,
+                    Words::HighValue => Figurative::HighValues,
+                    _ => Figurative::LowValues,
+                };
+                self.advance();
+                Some(Expr::Literal(LiteralExpr::Figurative(figurative)))
+            }
+            Tokens::Word(Word {
+                which: Words::Function,
+                ..
+            }) => {
+                self.advance();
+                let span = self.current_span();
+                let name = self.parse_name();
+                let module = if self.expect(Words::Of.into()) {
+                    self.expect_and_skip(vec![Words::Module.into()]);
+                    Some(self.parse_module_name())
+                } else {
+                    None
+                };
+                Some(Expr::Function(FunctionRef {
+                    name,
+                    module,
+                    id: None,
+                    span,
+                }))
+            }
+            Tokens::Word(Word {
+                which: Words::Format,
+                ..
+            }) => {
+                let span = self.current_span();
+                self.advance();
+                let value = self.parse_required(Self::parse_unary);
+                self.expect_and_skip(vec![Words::As.into()]);
+                Some(self.parse_edit_mask(value, span))
+            }
+            Tokens::LeftParen => {
+                self.advance();
+                let inner = self.parse_required(Self::parse_expr);
+                self.expect_and_skip(vec![Tokens::RightParen]);
+                Some(inner)
+            }
+            _ => None,
         }
-        None
     }
+
+    /// Parses what comes after `OF`, the record, list or table a field, item or entry is
+    /// picked from.
+    pub fn parse_container(&mut self) -> Expr {
+        let span = self.current_span();
+        match self.source.peek().map(|token| &token.inner) {
+            Some(Tokens::String)
+            | Some(Tokens::Word(Word {
+                which: Words::Item | Words::Entry,
+                ..
+            })) => self.parse_required(Self::parse_primary),
+            _ => {
+                self.emit_error("0004", span, "Expected the name of a variable here");
+                unreachable!()
+            }
+        }
+    }
+
+    /// Parses the number of an item after `ITEM` or the key of an entry after `ENTRY`. A
+    /// variable holding it is written on its own, as `"i" OF "prices"` would be a field of `"i"`.
+    pub fn parse_index(&mut self) -> Index {
+        let span = self.current_span();
+        let value = match self.source.peek().map(|token| &token.inner) {
+            Some(Tokens::String) => {
+                let name = self.parse_string();
+                self.advance();
+                name
+            }
+            _ => self.parse_primary(),
+        };
+        let Some(value) = value else {
+            self.emit_error("0004", span, "Expected the number of an item here");
+            unreachable!()
+        };
+        Index {
+            value: Box::new(value),
+            location: self.location(&span),
+            span,
+        }
+    }
+
+    /// Where `span` starts as `file:line:column`, for errors reported while the program runs.
+    pub fn location(&self, span: &Range<usize>) -> String {
+        let before = &self.source_str[..span.start.min(self.source_str.len())];
+        let line = before.iter().filter(|c| **c == '\n').count() + 1;
+        let column = before.iter().rev().take_while(|c| **c != '\n').count() + 1;
+        format!("{}:{line}:{column}", self.file)
+    }
+
+    /// Parses the mask of `FORMAT ... AS "mask"` or `USING PICTURE "mask"`, which has to be
+    /// written out so it can be checked here.
+    pub fn parse_edit_mask(&mut self, value: Expr, span: Range<usize>) -> Expr {
+        let mask_span = self.current_span();
+        let Some(Expr::Literal(LiteralExpr::String(mask))) = self.parse_string() else {
+            self.emit_error(
+                "0025",
+                mask_span,
+                "Expected a picture like \"$ZZ,ZZ9.99\" here",
+            );
+            unreachable!()
+        };
+        self.advance();
+        match Picture::parse(&mask) {
+            Ok(picture) => Expr::Edited(Edited {
+                value: Box::new(value),
+                picture,
+                span,
+            }),
+            Err(label) => {
+                self.emit_error("0025", mask_span, &label);
+                unreachable!()
+            }
+        }
+    }
+
+    /// Runs `parse` and reports an error at the current token if it didn't produce an expression.
+    pub fn parse_required(&mut self, parse: fn(&mut Self) -> Option<Expr>) -> Expr {
+        if let Some(expr) = parse(self) {
+            return expr;
+        }
+        let span = self.current_span();
+        self.emit_error("0003", span, "Expected a string, a number or `(` here");
+        unreachable!()
+    }
+
+    /// Parses the name of a type after `AS`, usually with `A`/`AN` in front of it.
+    pub fn parse_type(&mut self, name: &str) -> VariableType {
+        if !self.expect(Words::A.into()) {
+            self.expect(Words::An.into());
+        }
+        let span = self.current_span();
+        if let Some(Tokens::String) = self.source.peek().map(|token| &token.inner) {
+            let name = self.parse_name();
+            let Some(values) = self.choices.get(&name) else {
+                let label = format!("There is no choice \"{name}\" defined before this");
+                self.emit_error("0021", span, &label);
+                unreachable!()
+            };
+            return VariableType::Choice {
+                name,
+                values: values.clone(),
+            };
+        }
+        match self.advance().map(|token| token.inner) {
+            Some(Tokens::Word(Word {
+                which: Words::Integer,
+                ..
+            })) => VariableType::Integer,
+            Some(Tokens::Word(Word {
+                which: Words::Decimal,
+                ..
+            })) => VariableType::Decimal,
+            Some(Tokens::Word(Word {
+                which: Words::Boolean,
+                ..
+            })) => VariableType::Boolean,
+            Some(Tokens::Word(Word {
+                which: Words::String,
+                ..
+            })) => VariableType::String,
+            Some(Tokens::Word(Word {
+                which: Words::Number,
+                ..
+            })) => self.parse_picture(),
+            Some(Tokens::Word(Word {
+                which: Words::Text, ..
+            })) => {
+                self.expect_and_skip(vec![Words::Of.into()]);
+                let span = self.current_span();
+                let length = self.parse_count(Words::Character);
+                if length == 0 || length > MAX_TEXT_LENGTH {
+                    let label = format!("Text can be 1 to {MAX_TEXT_LENGTH} characters long");
+                    self.emit_error("0026", span, &label);
+                }
+                VariableType::Text { length }
+            }
+            Some(Tokens::Word(Word {
+                which: Words::List, ..
+            })) => self.parse_list(name),
+            Some(Tokens::Word(Word {
+                which: Words::Table,
+                ..
+            })) => self.parse_table(name),
+            Some(Tokens::Word(Word {
+                which: Words::Function,
+                ..
+            })) => {
+                // `WITH THE ARGUMENTS A STRING AN INTEGER`, the types without names.
+                let mut parameters = Vec::new();
+                if self.expect(Words::With.into()) {
+                    self.parse_arguments_word();
+                    while let Some(Tokens::Word(Word {
+                        which: Words::A | Words::An,
+                        ..
+                    })) = self.source.peek().map(|token| &token.inner)
+                    {
+                        parameters.push(self.parse_type(name));
+                    }
+                }
+                VariableType::Function { parameters }
+            }
+            _ => {
+                self.emit_error(
+                    "0021",
+                    span,
+                    "Expected `INTEGER`, `DECIMAL`, `NUMBER`, `TEXT`, `BOOLEAN`, `STRING`, `LIST`, `TABLE`, `FUNCTION` or the name of a choice here",
+                );
+                unreachable!()
+            }
+        }
+    }
+
+    /// Parses `[SORTED <key>] OF [12] <items>` after `LIST`. The items can be records, `OF 10
+    /// RECORDS WITH FIELDS: ... END RECORD "name"`, which end with the name of the list.
+    pub fn parse_list(&mut self, name: &str) -> VariableType {
+        let span = self.current_span();
+        let sorted = self.expect(Words::Sorted.into()).then(|| {
+            let mut key = self.parse_sort_key();
+            if key.field.is_none() {
+                key.span = span;
+            }
+            key
+        });
+        self.expect_and_skip(vec![Words::Of.into()]);
+        let length = match self.source.peek().map(|token| &token.inner) {
+            Some(Tokens::Number) => {
+                let span = self.current_span();
+                let length = match self.parse_number() {
+                    Some(Expr::Literal(LiteralExpr::Integer(length))) => length,
+                    _ => 0,
+                };
+                self.advance();
+                if length < 1 || length > MAX_LIST_LENGTH as i64 {
+                    let label = format!("A list can have 1 to {MAX_LIST_LENGTH} items");
+                    self.emit_error("0031", span, &label);
+                }
+                Some(length as u32)
+            }
+            _ => None,
+        };
+        let item = self.parse_element(name);
+        VariableType::List {
+            item: Box::new(item),
+            length,
+            sorted,
+        }
+    }
+
+    /// Parses `[BY "field"] [ASCENDING | DESCENDING]`, the order of a `SORT` or `SORTED` list.
+    pub fn parse_sort_key(&mut self) -> SortKey {
+        let mut span = self.current_span();
+        let field = if self.expect(Words::By.into()) {
+            span = self.current_span();
+            Some(self.parse_name())
+        } else {
+            None
+        };
+        let descending = self.expect(Words::Descending.into());
+        if !descending {
+            self.expect(Words::Ascending.into());
+        }
+        SortKey {
+            field,
+            descending,
+            span,
+        }
+    }
+
+    /// Parses what a list or table holds: a type, or `RECORDS WITH FIELDS:` followed by the
+    /// fields of a record and `END RECORD "<name>".`
+    fn parse_element(&mut self, name: &str) -> VariableType {
+        if !self.expect(Tokens::Word(Word {
+            which: Words::Record,
+            plural: true,
+        })) {
+            return self.parse_type(name);
+        }
+        self.expect_and_skip(vec![
+            Words::With.into(),
+            Tokens::Word(Word {
+                which: Words::Field,
+                plural: true,
+            }),
+            Tokens::Colon,
+        ]);
+        VariableType::Record(self.parse_fields(name.to_string(), false))
+    }
+
+    /// Parses `FROM <key> TO <value>` after `TABLE`. `TEXT` on its own is text of any length, a
+    /// `STRING`.
+    pub fn parse_table(&mut self, name: &str) -> VariableType {
+        self.expect_and_skip(vec![Words::From.into()]);
+        let key = match self.expect(Words::Text.into()) {
+            true => VariableType::String,
+            false => self.parse_type(name),
+        };
+        self.expect_and_skip(vec![Words::To.into()]);
+        let value = self.parse_element(name);
+        VariableType::Table {
+            key: Box::new(key),
+            value: Box::new(value),
+        }
+    }
+
+    /// Parses `WITH 7 DIGITS [AND 2 DECIMALS]` after `NUMBER`. The digits include the decimals.
+    pub fn parse_picture(&mut self) -> VariableType {
+        self.expect_and_skip(vec![Words::With.into()]);
+        let span = self.current_span();
+        let digits = self.parse_count(Words::Digit);
+        let scale = if self.expect(Words::And.into()) {
+            self.parse_count(Words::Decimal)
+        } else {
+            0
+        };
+        let mut end = self.current_span().start;
+        while end > span.start && self.source_str[end - 1].is_whitespace() {
+            end -= 1;
+        }
+        let span = span.start..end;
+        if digits == 0 || digits > MAX_DIGITS {
+            let label = format!("A number can have 1 to {MAX_DIGITS} digits, not {digits}");
+            self.emit_error("0023", span, &label);
+        } else if scale > digits {
+            let label = format!("{scale} decimals don't fit in {digits} digits");
+            self.emit_error("0023", span, &label);
+        }
+        VariableType::Fixed { digits, scale }
+    }
+
+    /// Parses a whole number followed by `word`, like `7 DIGITS`. The word can be singular or
+    /// plural.
+    pub fn parse_count(&mut self, word: Words) -> u32 {
+        let span = self.current_span();
+        let count = match self.parse_number() {
+            Some(Expr::Literal(LiteralExpr::Integer(count))) => count,
+            _ => {
+                self.emit_error("0023", span, "Expected a whole number here");
+                unreachable!()
+            }
+        };
+        self.advance();
+        match self.source.peek() {
+            Some(Token {
+                inner: Tokens::Word(Word { which, .. }),
+                ..
+            }) if *which == word => {
+                self.advance();
+            }
+            _ => self.expect_and_skip(vec![word.into()]),
+        }
+        u32::try_from(count).unwrap_or(u32::MAX)
+    }
+
+    /// Parses the optional `ROUNDED [UP | DOWN | TO EVEN]` after the target of an arithmetic
+    /// verb.
+    pub fn parse_rounding(&mut self) -> Rounding {
+        if !self.expect(Words::Rounded.into()) {
+            return Rounding::Truncate;
+        }
+        if self.expect(Words::Up.into()) {
+            Rounding::Up
+        } else if self.expect(Words::Down.into()) {
+            Rounding::Truncate
+        } else if self.expect(Words::To.into()) {
+            self.expect_and_skip(vec![Words::Even.into()]);
+            Rounding::Even
+        } else {
+            Rounding::Nearest
+        }
+    }
+
+    /// Parses the end of an arithmetic statement, either a period or
+    /// `ON SIZE ERROR DO ... END <verb>.`
+    pub fn parse_size_error(&mut self, verb: Words) -> Option<Box<Stmt>> {
+        if !self.expect(Words::On.into()) {
+            self.expect_and_skip(vec![Tokens::Period]);
+            return None;
+        }
+        self.expect_and_skip(vec![
+            Words::Size.into(),
+            Words::Error.into(),
+            Words::Do.into(),
+        ]);
+        let Some(block) = self.parse_block(BlockType::Verb(verb)) else {
+            panic!("Failed to parse the ON SIZE ERROR block");
+        };
+        Some(Box::new(block))
+    }
+
+    /// Parses the name of a variable, which is always written as a string.
+    pub fn parse_name(&mut self) -> String {
+        if let Some(Token {
+            inner: Tokens::String,
+            ..
+        }) = self.source.peek()
+        {
+            if let Some(Expr::Literal(LiteralExpr::String(name))) = self.parse_string() {
+                self.advance();
+                return name;
+            }
+        }
+        let span = self.current_span();
+        self.emit_error("0004", span, "Expected the name of a variable here");
+        unreachable!()
+    }
+
+    /// Handles the COBOL arithmetic verbs:
+    /// - `ADD "a" "b" TO "c" [GIVING "d"].`
+    /// - `SUBTRACT "a" FROM "b" [GIVING "c"].`
+    /// - `MULTIPLY "a" BY "b" [GIVING "c"].`
+    /// - `DIVIDE "a" INTO "b" [GIVING "c" [REMAINDER "d"]].` or `DIVIDE "a" BY "b" GIVING "c".`
+    /// - `COMPUTE "a" = <expr>.`
+    ///
+    /// Without `GIVING` the result is stored back into the second operand.
+    pub fn parse_arithmetic(&mut self, verb: Words) -> Stmt {
+        let verb_span = self.current_span();
+        self.advance();
+        if verb == Words::Compute {
+            let span = self.current_span();
+            let (target, path) = self.parse_target();
+            let rounding = self.parse_rounding();
+            self.expect_and_skip(vec![Tokens::Equals]);
+            let value = self.parse_required(Self::parse_expr);
+            return Stmt::Compute(Compute {
+                stores: vec![Store {
+                    target,
+                    path,
+                    value,
+                    id: None,
+                    rounding,
+                    span,
+                }],
+                on_size_error: self.parse_size_error(verb),
+            });
+        }
+
+        let location = self.location(&verb_span);
+        let mut operands = vec![self.parse_required(Self::parse_unary)];
+        if matches!(verb, Words::Add | Words::Subtract) {
+            while let Some(Tokens::String | Tokens::Number | Tokens::Minus) =
+                self.source.peek().map(|token| &token.inner)
+            {
+                operands.push(self.parse_required(Self::parse_unary));
+            }
+        }
+        let sum = operands
+            .into_iter()
+            .reduce(|l, r| {
+                Expr::Arithmetic(Arithmetic {
+                    l: Box::new(l),
+                    r: Box::new(r),
+                    op: ArithmeticOperators::Add,
+                    span: verb_span.clone(),
+                    location: location.clone(),
+                })
+            })
+            .unwrap();
+
+        let divide_by = verb == Words::Divide && self.expect(Words::By.into());
+        if !divide_by {
+            let joiner = match verb {
+                Words::Add => Words::To,
+                Words::Subtract => Words::From,
+                Words::Multiply => Words::By,
+                _ => Words::Into,
+            };
+            self.expect_and_skip(vec![joiner.into()]);
+        }
+
+        let second_span = self.current_span();
+        let second = self.parse_required(Self::parse_unary);
+
+        // `DIVIDE "a" INTO "b"` divides b by a, `DIVIDE "a" BY "b"` divides a by b.
+        let (l, r, op) = match verb {
+            Words::Add => (sum, second.clone(), ArithmeticOperators::Add),
+            Words::Subtract => (second.clone(), sum, ArithmeticOperators::Subtract),
+            Words::Multiply => (sum, second.clone(), ArithmeticOperators::Multiply),
+            _ if divide_by => (sum, second.clone(), ArithmeticOperators::Divide),
+            _ => (second.clone(), sum, ArithmeticOperators::Divide),
+        };
+        let (dividend, divisor) = (l.clone(), r.clone());
+        let value = Expr::Arithmetic(Arithmetic {
+            l: Box::new(l),
+            r: Box::new(r),
+            op,
+            span: verb_span.clone(),
+            location: location.clone(),
+        });
+
+        let mut stores = Vec::new();
+        if self.expect(Words::Giving.into()) {
+            let span = self.current_span();
+            let (target, path) = self.parse_target();
+            stores.push(Store {
+                target,
+                path,
+                value,
+                id: None,
+                rounding: self.parse_rounding(),
+                span,
+            });
+            if verb == Words::Divide && self.expect(Words::Remainder.into()) {
+                let span = self.current_span();
+                let (target, path) = self.parse_target();
+                stores.push(Store {
+                    target,
+                    path,
+                    value: Expr::Arithmetic(Arithmetic {
+                        l: Box::new(dividend),
+                        r: Box::new(divisor),
+                        op: ArithmeticOperators::Remainder,
+                        span: verb_span,
+                        location,
+                    }),
+                    id: None,
+                    rounding: self.parse_rounding(),
+                    span,
+                });
+            }
+        } else {
+            let Some((target, path)) = second.as_target() else {
+                self.emit_error(
+                    "0004",
+                    second_span.clone(),
+                    "Expected the name of a variable to store the result in, or use GIVING",
+                );
+                unreachable!()
+            };
+            if divide_by {
+                self.emit_error(
+                    "0004",
+                    second_span.clone(),
+                    "DIVIDE ... BY needs GIVING to know where to store the result",
+                );
+            }
+            stores.push(Store {
+                target,
+                path,
+                value,
+                id: None,
+                rounding: self.parse_rounding(),
+                span: second_span,
+            });
+        }
+        Stmt::Compute(Compute {
+            stores,
+            on_size_error: self.parse_size_error(verb),
+        })
+    }
+
+    /// Parses comparisons joined by `AND`, `OR` and `NOT`. `NOT` binds tightest and `OR` loosest,
+    /// parentheses can be used to group either conditions or arithmetic.
+    pub fn parse_condition(&mut self) -> Expr {
+        let mut left = self.parse_and_condition();
+        loop {
+            let span = self.current_span();
+            if !self.expect(Words::Or.into()) {
+                break;
+            }
+            let right = self.parse_and_condition();
+            left = Expr::Logical(Logical {
+                l: Box::new(left),
+                r: Box::new(right),
+                op: LogicalOperators::Or,
+                span,
+            });
+        }
+        left
+    }
+
+    pub fn parse_and_condition(&mut self) -> Expr {
+        let mut left = self.parse_not_condition();
+        loop {
+            let span = self.current_span();
+            if !self.expect(Words::And.into()) {
+                break;
+            }
+            let right = self.parse_not_condition();
+            left = Expr::Logical(Logical {
+                l: Box::new(left),
+                r: Box::new(right),
+                op: LogicalOperators::And,
+                span,
+            });
+        }
+        left
+    }
+
+    pub fn parse_not_condition(&mut self) -> Expr {
+        if self.expect(Words::Not.into()) {
+            return Expr::Not(Box::new(self.parse_not_condition()));
+        }
+
+        let left = if self.expect(Tokens::LeftParen) {
+            let inner = self.parse_condition();
+            self.expect_and_skip(vec![Tokens::RightParen]);
+            if inner.is_condition() {
+                return inner;
+            }
+            // The parentheses grouped arithmetic, so the rest of it still has to be parsed.
+            let inner = self.parse_term_from(inner);
+            self.parse_expr_from(inner)
+        } else {
+            self.parse_required(Self::parse_expr)
+        };
+
+        if self.expect_and_return(Words::Is.into()).is_some() {
+            return self.parse_comparison_from(left);
+        }
+        let span = self.current_span();
+        if self.expect(Words::Contains.into()) {
+            let op = match self.expect(Words::Key.into()) {
+                true => BinaryOperators::ContainsKey,
+                false => BinaryOperators::Contains,
+            };
+            let right = self.parse_required(Self::parse_expr);
+            return Expr::BinaryOp(Binary {
+                l: Box::new(left),
+                r: Box::new(right),
+                op,
+                span,
+            });
+        }
+        left
+    }
+
+    /// Parses `IS [NOT] <operator> <expr>` after `left`, where the operator is one of `EQUAL TO`,
+    /// `GREATER THAN [OR EQUAL TO]` or `LESS THAN [OR EQUAL TO]`.
+    pub fn parse_comparison_from(&mut self, left: Expr) -> Expr {
+        self.expect_and_skip(vec![Words::Is.into()]);
+        let negated = self.expect(Words::Not.into());
+
+        let op_span = self.current_span();
+        let op = if self.expect(Words::Equal.into()) {
+            self.expect_and_skip(vec![Words::To.into()]);
+            BinaryOperators::EqualTo
+        } else if self.expect(Words::Greater.into()) {
+            self.expect_and_skip(vec![Words::Than.into()]);
+            if self.expect(Words::Or.into()) {
+                self.expect_and_skip(vec![Words::Equal.into(), Words::To.into()]);
+                BinaryOperators::GreaterThanOrEqualTo
+            } else {
+                BinaryOperators::GreaterThan
+            }
+        } else if self.expect(Words::Less.into()) {
+            self.expect_and_skip(vec![Words::Than.into()]);
+            if self.expect(Words::Or.into()) {
+                self.expect_and_skip(vec![Words::Equal.into(), Words::To.into()]);
+                BinaryOperators::LessThanOrEqualTo
+            } else {
+                BinaryOperators::LessThan
+            }
+        } else {
+            self.emit_error(
+                "0006",
+                op_span,
+                "Expected `EQUAL TO`, `GREATER THAN` or `LESS THAN` here",
+            );
+            unreachable!()
+        };
+
+        let op = match (negated, op) {
+            (false, op) => op,
+            (true, BinaryOperators::EqualTo) => BinaryOperators::NotEqualTo,
+            (true, BinaryOperators::GreaterThan) => BinaryOperators::LessThanOrEqualTo,
+            (true, BinaryOperators::LessThan) => BinaryOperators::GreaterThanOrEqualTo,
+            (true, BinaryOperators::GreaterThanOrEqualTo) => BinaryOperators::LessThan,
+            (true, BinaryOperators::LessThanOrEqualTo) => BinaryOperators::GreaterThan,
+            (true, BinaryOperators::NotEqualTo) => BinaryOperators::EqualTo,
+            (true, BinaryOperators::Contains | BinaryOperators::ContainsKey) => unreachable!(),
+        };
+
+        let right = self.parse_required(Self::parse_expr);
+        Expr::BinaryOp(Binary {
+            l: Box::new(left),
+            r: Box::new(right),
+            op,
+            span: op_span,
+        })
+    }
+
     pub fn parse_stmt(&mut self) -> Option<Stmt> {
-        if let Some(current) = self.source.peek() {
-            match &current.inner {
-                Tokens::Word(word) => match word.which {
-                    Words::If => {
-                        self.advance();
-
-                        let Some(left) = self.parse_expr() else {panic!("failed")};
-                        let op = if self.expect(Tokens::Word(Word {
-                            which: Words::Is,
+        if let Some(Token {
+            inner: Tokens::Word(word),
+            ..
+        }) = self.source.peek()
+        {
+            match word.which {
+                Words::If => {
+                    self.advance();
+
+                    let span = self.current_span();
+                    let condition = self.parse_condition();
+
+                    self.expect_and_skip(vec![
+                        Tokens::Word(Word {
+                            which: Words::Then,
                             plural: false,
-                        })) {
-                            let (r1, r2) = (
-                                self.expect(Tokens::Word(Word {
-                                    which: Words::Equal,
-                                    plural: false,
-                                })),
-                                self.expect(Tokens::Word(Word {
-                                    which: Words::To,
-                                    plural: false,
-                                })),
-                            );
+                        }),
+                        Tokens::Word(Word {
+                            which: Words::Do,
+                            plural: false,
+                        }),
+                    ]);
 
-                            if r1 && r2 {
-                                BinaryOperators::EqualTo
-                            } else {
-                                panic!("Unsupported operator");
-                            }
-                        } else {
-                            panic!("Unsupported operator");
-                        };
-                        let Some(right) = self.parse_expr() else {panic!("failed")};
-
-                        self.expect_and_skip(vec![
-                            Tokens::Word(Word {
-                                which: Words::Then,
-                                plural: false,
-                            }),
-                            Tokens::Word(Word {
-                                which: Words::Do,
-                                plural: false,
-                            }),
-                        ]);
-
-                        let Some(then) = self.parse_block(BlockType::Unamed(0)) else {
-                            panic!("Failed to parse if block");
+                    let Some(then) = self.parse_block(BlockType::Unamed(0)) else {
+                        panic!("Failed to parse if block");
+                    };
+                    return Some(Stmt::Condition(Condition {
+                        then: Box::new(then),
+                        el: None,
+                        condition: Box::new(condition),
+                        span,
+                    }));
+                }
+                Words::Call => {
+                    self.advance();
+                    if let Some(next) = self.source.peek() {
+                        let Tokens::Word(word) = &next.inner else {
+                            panic!("Expected the word MODULE but instead got: {next:?}");
                         };
-                        return Some(Stmt::Condition(Condition {
-                            then: Box::new(then),
-                            el: None,
-                            condition: Box::new(Expr::BinaryOp(Binary {
-                                l: Box::new(left),
-                                r: Box::new(right),
-                                op,
-                            })),
-                        }));
-                    }
-                    Words::Call => {
-                        self.advance();
-                        if let Some(next) = self.source.peek() {
-                            let Tokens::Word(word) = &next.inner else {
-                                panic!("Expected the word MODULE but instead got: {next:?}");
-                            };
-                            match word.which {
-                                Words::Function => {
-                                    self.advance();
-                                    let Some(Expr::Literal(lit)) = self.parse_string() else {
-                                        panic!("Failed to parse function name");
-                                    };
-                                    self.advance();
-
-                                    if self.expect(Tokens::Word(Word {
-                                        which: Words::With,
-                                        plural: false,
-                                    })) {
-                                        if self.expect(Tokens::Word(Word {
-                                            which: Words::The,
-                                            plural: false,
-                                        })) {
-                                            self.expect_and_skip(vec![Tokens::Word(Word {
-                                                which: Words::Argument,
-                                                plural: false,
-                                            })]);
-
-                                            // TODO: make a parse_expr function to make stuff like this WAY easier.
-                                            let Some(Expr::Literal(only_arg)) = self.parse_string() else {
-                                                panic!("Failed to parse function name");
-                                            };
-                                            self.advance();
-
-                                            return Some(Stmt::Expr(Expr::Call(FunctionCall {
-                                                func: Box::new(Expr::Literal(lit)),
-                                                args: Some(vec![Box::new(Expr::Literal(only_arg))]),
-                                            })));
-                                        } else {
-                                            unimplemented!(
-                                                "Multiple function call args is NOT supported."
-                                            );
-                                        }
-                                    } else {
-                                        self.advance();
-                                        return Some(Stmt::Expr(Expr::Call(FunctionCall {
-                                            func: Box::new(Expr::Literal(lit)),
-                                            args: None,
-                                        })));
+                        match word.which {
+                            Words::Function => {
+                                self.advance();
+                                let span = self.current_span();
+                                let name = self.parse_name();
+                                let module = if self.expect(Words::Of.into()) {
+                                    self.expect_and_skip(vec![Words::Module.into()]);
+                                    Some(self.parse_module_name())
+                                } else {
+                                    None
+                                };
+                                let args = if self.expect(Words::With.into()) {
+                                    self.parse_arguments_word();
+                                    let mut args = Vec::new();
+                                    while !self.expect(Tokens::Period) {
+                                        args.push(Box::new(self.parse_required(Self::parse_expr)));
                                     }
-                                }
-                                _ => unimplemented!(),
+                                    Some(args)
+                                } else {
+                                    self.expect_and_skip(vec![Tokens::Period]);
+                                    None
+                                };
+                                return Some(Stmt::Expr(Expr::Call(FunctionCall {
+                                    func: Box::new(Expr::Literal(LiteralExpr::String(name))),
+                                    module,
+                                    args,
+                                    id: None,
+                                    location: self.location(&span),
+                                    span,
+                                })));
                             }
+                            _ => unimplemented!(),
                         }
                     }
-                    Words::Define => {
-                        self.advance();
-                        if let Some(next) = self.source.peek() {
-                            let Tokens::Word(word) = &next.inner else {
-                                panic!("Expected the word MODULE but instead got: {next:?}");
-                            };
-                            match word.which {
-                                Words::Function => {
-                                    self.advance();
-                                    let Some(Expr::Literal(LiteralExpr::String(func_name))) = self.parse_string() else {
-                                        panic!("Failed to parse function name");
-                                    };
-                                    self.advance();
-
-                                    /*
-                                        Here we can diverge. The function can have:
-                                        - No arguments
-                                        - One Argumet
-                                        - Or many arguments
-
-                                        Depending on which of the previous is used will determine the syntax required,
-                                        We can start by checking for the word "THAT". If it is "THAT" then we know the function takes no arguments.
-                                    */
-                                    // todo: change this to handle the other stuff
-                                    if self.expect(Tokens::Word(Word {
-                                        which: Words::That,
-                                        plural: false,
-                                    })) {
-                                        self.expect_and_skip(vec![
-                                            Tokens::Word(Word {
-                                                which: Words::Returns,
-                                                plural: false,
-                                            }),
-                                            Tokens::Word(Word {
-                                                which: Words::A,
-                                                plural: false,
-                                            }),
-                                        ]);
-
-                                        if !self.expect(Tokens::Colon) {
-                                            panic!("Expected a colon");
-                                        }
-
-                                        let Some(function_body) = self
-                                            .parse_block(BlockType::Named(func_name.clone(), 1))
-                                            else {
-                                                panic!("Failed to get function body.");
-                                            };
-                                        return Some(Stmt::Function {
-                                            name: LiteralExpr::String(func_name),
-                                            nodes: Box::new(function_body),
-                                        });
+                }
+                Words::Define => {
+                    self.advance();
+                    if let Some(next) = self.source.peek() {
+                        let Tokens::Word(word) = &next.inner else {
+                            panic!("Expected the word MODULE but instead got: {next:?}");
+                        };
+                        match word.which {
+                            Words::Function => {
+                                self.advance();
+                                let span = self.current_span();
+                                let Some(Expr::Literal(LiteralExpr::String(func_name))) =
+                                    self.parse_string()
+                                else {
+                                    panic!("Failed to parse function name");
+                                };
+                                self.advance();
+
+                                let parameters = if self.expect(Words::With.into()) {
+                                    self.parse_parameters()
+                                } else {
+                                    Vec::new()
+                                };
+                                self.expect_and_skip(vec![
+                                    Words::That.into(),
+                                    Words::Returns.into(),
+                                    Words::A.into(),
+                                ]);
+                                if !self.expect(Tokens::Colon) {
+                                    panic!("Expected a colon");
+                                }
+
+                                let Some(function_body) =
+                                    self.parse_block(BlockType::Named(func_name.clone(), 1))
+                                else {
+                                    panic!("Failed to get function body.");
+                                };
+                                return Some(Stmt::Function {
+                                    name: LiteralExpr::String(func_name),
+                                    parameters,
+                                    nodes: Box::new(function_body),
+                                    visibility: Visibility::default(),
+                                    id: None,
+                                    span,
+                                });
+                            }
+                            Words::Record => {
+                                self.advance();
+                                let span = self.current_span();
+                                let name = self.parse_name();
+                                self.expect_and_skip(vec![
+                                    Words::With.into(),
+                                    Tokens::Word(Word {
+                                        which: Words::Field,
+                                        plural: true,
+                                    }),
+                                    Tokens::Colon,
+                                ]);
+                                let fields = self.parse_fields(name.clone(), false);
+                                return Some(Stmt::Variable {
+                                    name,
+                                    ty: VariableType::Record(fields),
+                                    value: None,
+                                    visibility: Visibility::default(),
+                                    id: None,
+                                    span,
+                                    conditions: Vec::new(),
+                                });
+                            }
+                            Words::List => {
+                                self.advance();
+                                let span = self.current_span();
+                                let name = self.parse_name();
+                                let ty = self.parse_list(&name);
+                                // A list of records already ended with `END RECORD "name".`
+                                if let VariableType::List { item, .. } = &ty {
+                                    if !matches!(**item, VariableType::Record(_)) {
+                                        self.expect_and_skip(vec![Tokens::Period]);
                                     }
                                 }
-                                Words::Module => {
-                                    self.advance();
-                                    let Some(Expr::Literal(LiteralExpr::String(module_name))) = self.parse_string() else {
-                                        panic!("Failed to parse module name");
-                                    };
-                                    self.advance();
-                                    self.expect_and_skip(vec![
-                                        Tokens::Word(Word {
-                                            which: Words::With,
-                                            plural: false,
-                                        }),
-                                        Tokens::Word(Word {
-                                            which: Words::Contents,
-                                            plural: false,
-                                        }),
-                                        Tokens::Colon,
-                                    ]);
-
-                                    let Some(block) = self.parse_block(BlockType::Named(module_name.clone(), 0)) else {
-                                        panic!("Failed to parse module content");
-                                    };
-                                    return Some(Stmt::Module {
-                                        name: LiteralExpr::String(module_name),
-                                        nodes: Box::new(block),
-                                    });
+                                return Some(Stmt::Variable {
+                                    name,
+                                    ty,
+                                    value: None,
+                                    visibility: Visibility::default(),
+                                    id: None,
+                                    span,
+                                    conditions: Vec::new(),
+                                });
+                            }
+                            Words::Table => {
+                                self.advance();
+                                let span = self.current_span();
+                                let name = self.parse_name();
+                                let ty = self.parse_table(&name);
+                                // So did a table of records.
+                                if let VariableType::Table { value, .. } = &ty {
+                                    if !matches!(**value, VariableType::Record(_)) {
+                                        self.expect_and_skip(vec![Tokens::Period]);
+                                    }
                                 }
-                                _ => {}
+                                return Some(Stmt::Variable {
+                                    name,
+                                    ty,
+                                    value: None,
+                                    visibility: Visibility::default(),
+                                    id: None,
+                                    span,
+                                    conditions: Vec::new(),
+                                });
+                            }
+                            Words::Choice => {
+                                self.advance();
+                                return Some(Stmt::Choice(self.parse_choice()));
+                            }
+                            Words::Constant => {
+                                self.advance();
+                                let span = self.current_span();
+                                let name = self.parse_name();
+                                self.expect_and_skip(vec![Words::As.into()]);
+                                let value = self.parse_condition();
+                                self.expect_and_skip(vec![Tokens::Period]);
+                                return Some(Stmt::Constant(Constant {
+                                    name,
+                                    value,
+                                    id: None,
+                                    span,
+                                }));
+                            }
+                            Words::Module => {
+                                self.advance();
+                                let span = self.current_span();
+                                let Some(Expr::Literal(LiteralExpr::String(module_name))) =
+                                    self.parse_string()
+                                else {
+                                    panic!("Failed to parse module name");
+                                };
+                                self.advance();
+                                self.expect_and_skip(vec![
+                                    Tokens::Word(Word {
+                                        which: Words::With,
+                                        plural: false,
+                                    }),
+                                    Tokens::Word(Word {
+                                        which: Words::Contents,
+                                        plural: false,
+                                    }),
+                                    Tokens::Colon,
+                                ]);
+
+                                let Some(block) =
+                                    self.parse_block(BlockType::Named(module_name.clone(), 0))
+                                else {
+                                    panic!("Failed to parse module content");
+                                };
+                                return Some(Stmt::Module {
+                                    name: LiteralExpr::String(module_name),
+                                    nodes: Box::new(block),
+                                    span,
+                                });
                             }
+                            _ => {}
                         }
                     }
-                    Words::Set => {
-                        self.advance();
-                        let Some(Expr::Literal(LiteralExpr::String(variable_name))) = self.parse_string() else {
-                            panic!("Failed to parse module name");
-                        };
-                        self.advance();
-                        self.expect_and_skip(vec![
-                            Tokens::Word(Word {
-                                which: Words::Equal,
-                                plural: false,
-                            }),
-                            Tokens::Word(Word {
-                                which: Words::To,
-                                plural: false,
-                            }),
-                        ]);
-                        let Some(expr) = self.parse_string() else {
-                            panic!("Failed to parse module name");
-                        };
-                        self.advance();
-                        if self.expect_and_return(Tokens::Period).is_none() {
-                            panic!("END WITH A PERIOD DAMNIT.")
+                }
+                Words::Public | Words::Private => {
+                    let modifier = if word.which == Words::Public {
+                        Visibility::Public
+                    } else {
+                        Visibility::Private
+                    };
+                    self.advance();
+                    let span = self.current_span();
+                    return match self.parse_stmt() {
+                        Some(Stmt::Function {
+                            name,
+                            parameters,
+                            nodes,
+                            id,
+                            span,
+                            ..
+                        }) => Some(Stmt::Function {
+                            name,
+                            parameters,
+                            nodes,
+                            visibility: modifier,
+                            id,
+                            span,
+                        }),
+                        Some(Stmt::Variable {
+                            name,
+                            ty,
+                            value,
+                            id,
+                            span,
+                            conditions,
+                            ..
+                        }) => Some(Stmt::Variable {
+                            name,
+                            ty,
+                            value,
+                            visibility: modifier,
+                            id,
+                            span,
+                            conditions,
+                        }),
+                        _ => {
+                            self.emit_error(
+                                "0016",
+                                span,
+                                "Only `DEFINE FUNCTION` and `SET` can be public or private",
+                            );
+                            unreachable!()
                         }
+                    };
+                }
+                Words::Add | Words::Subtract | Words::Multiply | Words::Divide | Words::Compute => {
+                    let verb = word.which.clone();
+                    return Some(self.parse_arithmetic(verb));
+                }
+                Words::Display => {
+                    let span = self.current_span();
+                    self.advance();
+                    let mut exprs = Vec::new();
+                    while !self.expect(Tokens::Period) {
+                        let expr_span = self.current_span();
+                        let expr = self.parse_required(Self::parse_expr);
+                        if self.expect(Words::Using.into()) {
+                            self.expect_and_skip(vec![Words::Picture.into()]);
+                            exprs.push(self.parse_edit_mask(expr, expr_span));
+                            continue;
+                        }
+                        exprs.push(expr);
+                    }
+                    return Some(Stmt::Display { exprs, span });
+                }
+                Words::Move => {
+                    self.advance();
+                    let value = self.parse_required(Self::parse_expr);
+                    self.expect_and_skip(vec![Words::To.into()]);
+                    let span = self.current_span();
+                    let (target, path) = self.parse_target();
+                    self.expect_and_skip(vec![Tokens::Period]);
+                    return Some(Stmt::Move(Store {
+                        target,
+                        path,
+                        value,
+                        id: None,
+                        rounding: Rounding::default(),
+                        span,
+                    }));
+                }
+                // `JOIN "a" AND "b" INTO "c".` is a `MOVE` of the values joined with `&`.
+                Words::Join => {
+                    self.advance();
+                    let mut value = self.parse_required(Self::parse_expr);
+                    loop {
+                        let span = self.current_span();
+                        self.expect_and_skip(vec![Words::And.into()]);
+                        let right = self.parse_required(Self::parse_expr);
+                        value = Expr::Concat(Concat {
+                            l: Box::new(value),
+                            r: Box::new(right),
+                            span,
+                        });
+                        if self.expect(Words::Into.into()) {
+                            break;
+                        }
+                    }
+                    let span = self.current_span();
+                    let (target, path) = self.parse_target();
+                    self.expect_and_skip(vec![Tokens::Period]);
+                    return Some(Stmt::Move(Store {
+                        target,
+                        path,
+                        value,
+                        id: None,
+                        rounding: Rounding::default(),
+                        span,
+                    }));
+                }
+                Words::Sort => {
+                    self.advance();
+                    let span = self.current_span();
+                    let collection = self.parse_required(Self::parse_primary);
+                    let mut keys = vec![self.parse_sort_key()];
+                    while self.expect(Words::Then.into()) {
+                        let key = self.parse_sort_key();
+                        if key.field.is_none() {
+                            self.emit_error(
+                                "0001",
+                                key.span.clone(),
+                                "Expected `BY` and a field here",
+                            );
+                        }
+                        keys.push(key);
+                    }
+                    self.expect_and_skip(vec![Tokens::Period]);
+                    return Some(Stmt::Sort(Sort {
+                        collection,
+                        keys,
+                        span,
+                    }));
+                }
+                Words::Search => {
+                    self.advance();
+                    let span = self.current_span();
+                    let collection = self.parse_required(Self::parse_primary);
+                    let field = if self.expect(Words::By.into()) {
+                        let span = self.current_span();
+                        Some((self.parse_name(), span))
+                    } else {
+                        None
+                    };
+                    self.expect_and_skip(vec![Words::For.into()]);
+                    let value = self.parse_required(Self::parse_expr);
+                    self.expect_and_skip(vec![Words::Giving.into()]);
+                    let giving_span = self.current_span();
+                    let (target, path) = self.parse_target();
+                    self.expect_and_skip(vec![Tokens::Period]);
+                    return Some(Stmt::Search(Search {
+                        collection,
+                        field,
+                        giving: Store {
+                            target,
+                            path,
+                            value,
+                            id: None,
+                            rounding: Rounding::default(),
+                            span: giving_span,
+                        },
+                        span,
+                    }));
+                }
+                Words::Evaluate => {
+                    self.advance();
+                    return Some(Stmt::Evaluate(self.parse_evaluate()));
+                }
+                Words::For => {
+                    self.advance();
+                    self.expect_and_skip(vec![Words::Each.into()]);
+                    let span = self.current_span();
+                    let variable = self.parse_name();
+                    self.expect_and_skip(vec![Words::In.into()]);
+                    let collection = self.parse_required(Self::parse_primary);
+                    self.expect_and_skip(vec![Words::Do.into()]);
+                    let Some(body) = self.parse_block(BlockType::Verb(Words::For)) else {
+                        panic!("Failed to parse the body of FOR EACH");
+                    };
+                    return Some(Stmt::ForEach(ForEach {
+                        variable,
+                        id: None,
+                        collection,
+                        body: Box::new(body),
+                        span,
+                    }));
+                }
+                Words::Append => {
+                    self.advance();
+                    let value = self.parse_required(Self::parse_expr);
+                    self.expect_and_skip(vec![Words::To.into()]);
+                    let span = self.current_span();
+                    let (target, path) = self.parse_target();
+                    self.expect_and_skip(vec![Tokens::Period]);
+                    return Some(Stmt::Append(Store {
+                        target,
+                        path,
+                        value,
+                        id: None,
+                        rounding: Rounding::default(),
+                        span,
+                    }));
+                }
+                Words::Set => {
+                    self.advance();
+                    let span = self.current_span();
+                    let (variable_name, path) = self.parse_target();
+                    // A field can't be defined by a `SET`, storing into one is a `MOVE`.
+                    if !path.is_empty() {
+                        self.expect(Words::Equal.into());
+                        self.expect_and_skip(vec![Words::To.into()]);
+                        let value = self.parse_required(Self::parse_unary);
+                        self.expect_and_skip(vec![Tokens::Period]);
+                        return Some(Stmt::Move(Store {
+                            target: variable_name,
+                            path,
+                            value,
+                            id: None,
+                            rounding: Rounding::default(),
+                            span,
+                        }));
+                    }
+                    let declared = if self.expect(Words::As.into()) {
+                        Some(self.parse_type(&variable_name))
+                    } else {
+                        None
+                    };
+                    // `EQUAL` can be left out, which reads better in `SET "is_active" TO TRUE`.
+                    self.expect(Words::Equal.into());
+                    self.expect_and_skip(vec![Words::To.into()]);
+                    // `SET "callback" TO A FUNCTION.` defines a variable holding the default of
+                    // its type, for functions one that doesn't hold a function yet.
+                    if let (
+                        None,
+                        Some(Tokens::Word(Word {
+                            which: Words::A | Words::An,
+                            ..
+                        })),
+                    ) = (&declared, self.source.peek().map(|token| &token.inner))
+                    {
+                        let ty = self.parse_type(&variable_name);
+                        self.expect_and_skip(vec![Tokens::Period]);
                         return Some(Stmt::Variable {
                             name: variable_name,
-                            value: Some(expr),
-                            ty: VariableType::String,
+                            value: None,
+                            ty,
+                            visibility: Visibility::default(),
+                            id: None,
+                            span,
+                            conditions: Vec::new(),
                         });
                     }
-                    _ => {}
-                },
-
+                    let expr = match self.parse_required(Self::parse_unary) {
+                        // A `SET` stores a literal, braces and all.
+                        Expr::Interpolation(interpolation) => {
+                            Expr::Literal(LiteralExpr::String(interpolation.text))
+                        }
+                        expr => expr,
+                    };
+                    // The type of a function can't be told from its name, so without a declared
+                    // type this stores into a variable that is already defined.
+                    if let (None, Expr::Function(_)) = (&declared, &expr) {
+                        self.expect_and_skip(vec![Tokens::Period]);
+                        return Some(Stmt::Move(Store {
+                            target: variable_name,
+                            path,
+                            value: expr,
+                            id: None,
+                            rounding: Rounding::default(),
+                            span,
+                        }));
+                    }
+                    // Without a declared type, the type is inferred from the literal.
+                    let ty = declared.unwrap_or(match &expr {
+                        Expr::Literal(LiteralExpr::Integer(_)) => VariableType::Integer,
+                        Expr::Literal(LiteralExpr::Decimal(_)) => VariableType::Decimal,
+                        Expr::Literal(LiteralExpr::Boolean(_)) => VariableType::Boolean,
+                        Expr::Literal(LiteralExpr::Figurative(figurative)) => {
+                            figurative.default_type()
+                        }
+                        Expr::Negate(inner) => match **inner {
+                            Expr::Literal(LiteralExpr::Decimal(_)) => VariableType::Decimal,
+                            _ => VariableType::Integer,
+                        },
+                        _ => VariableType::String,
+                    });
+                    let mut conditions = Vec::new();
+                    while self.expect(Words::With.into()) {
+                        conditions.push(self.parse_condition_name());
+                    }
+                    self.expect_and_skip(vec![Tokens::Period]);
+                    return Some(Stmt::Variable {
+                        name: variable_name,
+                        value: Some(expr),
+                        ty,
+                        visibility: Visibility::default(),
+                        id: None,
+                        span,
+                        conditions,
+                    });
+                }
                 _ => {}
             }
         }
         None
     }
 
+    /// Parses the name of a variable, or of a field within one, that is stored into.
+    pub fn parse_target(&mut self) -> (String, Vec<Access>) {
+        let span = self.current_span();
+        match self.parse_primary().and_then(|target| target.as_target()) {
+            Some(target) => target,
+            None => {
+                self.emit_error("0004", span, "Expected the name of a variable here");
+                unreachable!()
+            }
+        }
+    }
+
+    /// Parses the fields of a record up to its `END RECORD`. A field is either `"name" AS
+    /// <type>` or a record of its own, `"name" WITH FIELDS: ... END RECORD "name"`, which is
+    /// `nested` in another.
+    pub fn parse_fields(&mut self, record: String, nested: bool) -> Vec<Field> {
+        let mut fields: Vec<Field> = Vec::new();
+        while !self.expect(Words::End.into()) {
+            let span = self.current_span();
+            let name = self.parse_name();
+            if fields.iter().any(|field| field.name == name) {
+                let label = format!("There already is a field \"{name}\" in \"{record}\"");
+                self.emit_error("0020", span.clone(), &label);
+            }
+            // An overlay of an overlay is another view of the field they both redefine.
+            let redefines = if self.expect(Words::Redefines.into()) {
+                let target_span = self.current_span();
+                let target = self.parse_name();
+                match fields.iter().find(|field| field.name == target) {
+                    Some(field) => Some(field.redefines.clone().unwrap_or(target)),
+                    None => {
+                        let label =
+                            format!("There is no earlier field \"{target}\" in \"{record}\"");
+                        self.emit_error("0029", target_span, &label);
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            let ty = if self.expect(Words::With.into()) {
+                self.expect_and_skip(vec![
+                    Tokens::Word(Word {
+                        which: Words::Field,
+                        plural: true,
+                    }),
+                    Tokens::Colon,
+                ]);
+                VariableType::Record(self.parse_fields(name.clone(), true))
+            } else {
+                self.expect_and_skip(vec![Words::As.into()]);
+                self.parse_type(&name)
+            };
+            fields.push(Field {
+                name,
+                ty,
+                redefines,
+                span,
+            });
+        }
+        if !nested {
+            self.parse_block_end(BlockType::Named(record, 2));
+            return fields;
+        }
+        // The `,` before the next field is dropped by the lexer, so the `.` is optional here.
+        self.parse_end_name(record, 2);
+        self.expect(Tokens::Period);
+        fields
+    }
+
+    /// Parses `CONDITION "name" WHEN value OR value ...` after the `WITH` of a `SET`.
+    pub fn parse_condition_name(&mut self) -> ConditionName {
+        self.expect_and_skip(vec![Words::Condition.into()]);
+        let span = self.current_span();
+        let name = self.parse_name();
+        self.expect_and_skip(vec![Words::When.into()]);
+        let mut values = vec![self.parse_required(Self::parse_unary)];
+        while self.expect(Words::Or.into()) {
+            values.push(self.parse_required(Self::parse_unary));
+        }
+        ConditionName {
+            name,
+            values,
+            id: None,
+            span,
+        }
+    }
+
+    /// Parses `THE ARGUMENT` or `THE ARGUMENTS` after `WITH`, which read the same way.
+    pub fn parse_arguments_word(&mut self) {
+        self.expect_and_skip(vec![Words::The.into()]);
+        match self.source.peek() {
+            Some(Token {
+                inner:
+                    Tokens::Word(Word {
+                        which: Words::Argument,
+                        ..
+                    }),
+                ..
+            }) => {
+                self.advance();
+            }
+            _ => self.expect_and_skip(vec![Words::Argument.into()]),
+        }
+    }
+
+    /// Parses `THE ARGUMENTS "name" AS A STRING "count" AS AN INTEGER` after the name of a
+    /// `DEFINE FUNCTION`, up to the `THAT RETURNS A`.
+    pub fn parse_parameters(&mut self) -> Vec<Parameter> {
+        self.parse_arguments_word();
+        let mut parameters: Vec<Parameter> = Vec::new();
+        while let Some(Tokens::String) = self.source.peek().map(|token| &token.inner) {
+            let span = self.current_span();
+            let name = self.parse_name();
+            if parameters.iter().any(|parameter| parameter.name == name) {
+                let label = format!("There already is an argument \"{name}\"");
+                self.emit_error("0020", span.clone(), &label);
+            }
+            self.expect_and_skip(vec![Words::As.into()]);
+            let ty = self.parse_type(&name);
+            parameters.push(Parameter {
+                name,
+                ty,
+                id: None,
+                span,
+            });
+        }
+        if parameters.is_empty() {
+            let span = self.current_span();
+            self.emit_error("0004", span, "Expected the name of an argument here");
+        }
+        parameters
+    }
+
+    /// Parses `"color" AS "red", "green" OR "blue".` after `DEFINE CHOICE`. The commas are
+    /// optional, like everywhere else.
+    pub fn parse_choice(&mut self) -> Choice {
+        let span = self.current_span();
+        let name = self.parse_name();
+        if self.choices.contains_key(&name) {
+            let label = format!("There already is a choice \"{name}\"");
+            self.emit_error("0020", span.clone(), &label);
+        }
+        self.expect_and_skip(vec![Words::As.into()]);
+        let mut values: Vec<String> = Vec::new();
+        loop {
+            let last = !values.is_empty() && self.expect(Words::Or.into());
+            let value_span = self.current_span();
+            let Some(Tokens::String) = self.source.peek().map(|token| &token.inner) else {
+                self.emit_error("0001", value_span, "Expected a value of the choice here");
+                unreachable!()
+            };
+            let value = self.parse_name();
+            if values.contains(&value) {
+                let label = format!("\"{value}\" is already a value of \"{name}\"");
+                self.emit_error("0020", value_span, &label);
+            }
+            values.push(value);
+            if last
+                || !matches!(
+                    self.source.peek().map(|token| &token.inner),
+                    Some(Tokens::String)
+                        | Some(Tokens::Word(Word {
+                            which: Words::Or,
+                            ..
+                        }))
+                )
+            {
+                break;
+            }
+        }
+        self.expect_and_skip(vec![Tokens::Period]);
+        self.choices.insert(name.clone(), values.clone());
+        Choice { name, values, span }
+    }
+
+    /// Parses `"light" WHEN "red" DO ... [WHEN OTHER DO ...] END EVALUATE.` after `EVALUATE`.
+    /// `WHEN OTHER` has to come last.
+    pub fn parse_evaluate(&mut self) -> Evaluate {
+        let span = self.current_span();
+        let subject = self.parse_required(Self::parse_expr);
+        let mut branches = Vec::new();
+        let mut other = None;
+        while self.expect(Words::When.into()) {
+            let span = self.current_span();
+            if other.is_some() {
+                self.emit_error(
+                    "0001",
+                    span.clone(),
+                    "`WHEN OTHER` has to be the last `WHEN`",
+                );
+            }
+            if self.expect(Words::Other.into()) {
+                self.expect_and_skip(vec![Words::Do.into()]);
+                other = Some(Box::new(self.parse_branch()));
+                continue;
+            }
+            let mut values = vec![self.parse_required(Self::parse_unary)];
+            while self.expect(Words::Or.into()) {
+                values.push(self.parse_required(Self::parse_unary));
+            }
+            self.expect_and_skip(vec![Words::Do.into()]);
+            branches.push(When {
+                values,
+                body: Box::new(self.parse_branch()),
+                span,
+            });
+        }
+        self.expect_and_skip(vec![
+            Words::End.into(),
+            Words::Evaluate.into(),
+            Tokens::Period,
+        ]);
+        Evaluate {
+            subject,
+            branches,
+            other,
+            span,
+        }
+    }
+
+    /// Parses the statements of a `WHEN`, up to the next `WHEN` or the `END EVALUATE`.
+    fn parse_branch(&mut self) -> Stmt {
+        let mut nodes = Vec::new();
+        while let Some(next) = self.source.peek() {
+            if let Tokens::Word(Word {
+                which: Words::When | Words::End,
+                ..
+            }) = next.inner
+            {
+                break;
+            }
+            let span = self.current_span();
+            match self.parse_stmt() {
+                Some(stmt) => {
+                    self.reject_nested_definition(&stmt, span);
+                    nodes.push(Box::new(stmt))
+                }
+                None => {
+                    self.advance();
+                }
+            }
+        }
+        Stmt::Block(nodes)
+    }
+
+    /// Functions and modules are only defined at the top level, or functions in a module.
+    fn reject_nested_definition(&self, stmt: &Stmt, span: Range<usize>) {
+        match stmt {
+            Stmt::Function { .. } => self.emit_error(
+                "0042",
+                span,
+                "A function can only be defined at the top level or in a module",
+            ),
+            Stmt::Module { .. } => self.emit_error(
+                "0042",
+                span,
+                "A module can only be defined at the top level",
+            ),
+            _ => {}
+        }
+    }
+
     pub fn parse_block(&mut self, block_type: BlockType) -> Option<Stmt> {
+        let is_module = matches!(block_type, BlockType::Named(_, 0));
         let mut nodes: Vec<Box<Stmt>> = Vec::new();
         while let Some(next) = self.source.peek() {
             if next.inner
@@ -296,75 +1823,120 @@ where
                 })
             {
                 self.advance();
-                match block_type {
-                    BlockType::Named(name, version) => {
-                        let word = match version {
-                            0 => Word {
-                                which: Words::Module,
-                                plural: false,
-                            },
-                            1 => Word {
-                                which: Words::Function,
-                                plural: false,
-                            },
-                            _ => panic!("Don't know this type of named block."),
-                        };
-                        if self.expect_and_return(Tokens::Word(word)).is_none() {
-                            panic!("Expected word");
-                        };
-                        self.advance();
-                        let Some(Expr::Literal(LiteralExpr::String(name2))) = self.parse_string() else {
-                            panic!("Expected module name");
-                        };
-                        if name2 != name {
-                            panic!("Expected module name {name:?} but instead got {name2:?}");
-                        }
-                        self.advance();
-                        if self.expect_and_return(Tokens::Period).is_none() {
-                            panic!("END WITH A PERIOD DAMNIT.")
-                        }
-                        self.advance();
-                    }
-                    BlockType::Unamed(version) => {
-                        if version == 0 {
-                            self.expect_and_skip(vec![Tokens::Word(Word {
-                                which: Words::If,
-                                plural: false,
-                            })]);
-                            self.advance();
-                        }
-                    }
-                }
+                self.parse_block_end(block_type);
                 break;
             }
 
+            let span = self.current_span();
             if let Some(stmt) = self.parse_stmt() {
+                if is_module
+                    && !matches!(
+                        stmt,
+                        Stmt::Function { .. }
+                            | Stmt::Variable { .. }
+                            | Stmt::Choice(_)
+                            | Stmt::Constant(_)
+                    )
+                {
+                    self.emit_error(
+                        "0009",
+                        span.clone(),
+                        "A module can only contain `SET`, `DEFINE FUNCTION`, `DEFINE CHOICE` and `DEFINE CONSTANT`",
+                    );
+                }
+                if !is_module
+                    && matches!(
+                        stmt,
+                        Stmt::Variable {
+                            visibility: Visibility::Private,
+                            ..
+                        }
+                    )
+                {
+                    self.emit_error(
+                        "0016",
+                        span.clone(),
+                        "Only a `SET` in a module can be private",
+                    );
+                }
+                if !is_module {
+                    self.reject_nested_definition(&stmt, span);
+                }
                 nodes.push(Box::new(stmt))
+            } else {
+                self.advance();
             }
-            self.advance();
         }
-        return Some(Stmt::Block(nodes));
+        Some(Stmt::Block(nodes))
+    }
+
+    /// Parses what follows the `END` of a block: `MODULE "name".`, `FUNCTION "name".`,
+    /// `RECORD "name".`, `IF.` or the verb of an `ON SIZE ERROR` block.
+    pub fn parse_block_end(&mut self, block_type: BlockType) {
+        match block_type {
+            BlockType::Named(name, version) => {
+                self.parse_end_name(name, version);
+                if self.expect_and_return(Tokens::Period).is_none() {
+                    panic!("END WITH A PERIOD DAMNIT.")
+                }
+                self.advance();
+            }
+            BlockType::Unamed(version) => {
+                if version == 0 {
+                    self.expect_and_skip(vec![Tokens::Word(Word {
+                        which: Words::If,
+                        plural: false,
+                    })]);
+                    self.advance();
+                }
+            }
+            BlockType::Verb(verb) => {
+                self.expect_and_skip(vec![verb.into(), Tokens::Period]);
+            }
+        }
+    }
+
+    /// Parses the `MODULE "name"`, `FUNCTION "name"` or `RECORD "name"` after the `END` of a
+    /// named block, which has to repeat the name it was defined with.
+    fn parse_end_name(&mut self, name: String, version: usize) {
+        let word = match version {
+            0 => Word {
+                which: Words::Module,
+                plural: false,
+            },
+            1 => Word {
+                which: Words::Function,
+                plural: false,
+            },
+            2 => Word {
+                which: Words::Record,
+                plural: false,
+            },
+            _ => panic!("Don't know this type of named block."),
+        };
+        if self.expect_and_return(Tokens::Word(word)).is_none() {
+            panic!("Expected word");
+        };
+        self.advance();
+        let Some(Expr::Literal(LiteralExpr::String(name2))) = self.parse_string() else {
+            panic!("Expected module name");
+        };
+        if name2 != name {
+            panic!("Expected module name {name:?} but instead got {name2:?}");
+        }
+        self.advance();
     }
 
     pub fn expect_and_skip(&mut self, expect: Vec<Tokens>) {
         for token in expect {
             if let Some(current_tok) = self.source.peek() {
                 if current_tok.inner != token {
-                    let mut error = ErrorClient::new("0001", crate::errors::MessageKind::ERROR);
-                    error.end_process(true);
-                    // TODO: The parser should have a context field that is aware of this kind of stuff.
-                    error.set_file(
-                        "module_level_func.cbt",
-                        "./tests/pass/module_level_func.cbt",
-                    );
-                    error.set_span(current_tok.location.span.clone());
+                    let span = current_tok.location.span.clone();
                     let note = format!(
-                        "Expected the word `{}` but instead got `{}`",
-                        token.to_string(),
-                        current_tok.inner.to_string()
+                        "Expected `{}` but instead got `{}`",
+                        token, current_tok.inner
                     );
-                    error.add_label(Some(&note));
-                    error.build_and_emit();
+                    self.emit_error("0001", span, &note);
                 }
             } else {
                 panic!("Failed to get current token");
@@ -373,6 +1945,24 @@ where
         }
     }
 
+    /// Reports an error pointing at `span` in the file being parsed and stops the compiler.
+    pub fn emit_error(&self, code: &str, span: Range<usize>, note: &str) {
+        let mut error = ErrorClient::new(code, crate::errors::MessageKind::ERROR);
+        error.end_process(true);
+        error.set_file(&self.file, &self.file);
+        error.set_span(span);
+        error.add_label(Some(note));
+        error.build_and_emit();
+    }
+
+    /// The span of the token about to be parsed, or an empty span at the end of the file.
+    pub fn current_span(&mut self) -> Range<usize> {
+        match self.source.peek() {
+            Some(token) => token.location.span.clone(),
+            None => self.source_str.len()..self.source_str.len(),
+        }
+    }
+
     pub fn expect(&mut self, expect: Tokens) -> bool {
         if let Some(next) = self.source.peek() {
             if next.inner == expect {
@@ -380,13 +1970,11 @@ where
                 return true;
             }
         };
-        return false;
+        false
     }
 
     pub fn expect_and_return(&mut self, expect: Tokens) -> Option<&Token> {
-        let Some(next) = self.source.peek() else {
-            return None;
-        };
+        let next = self.source.peek()?;
         if next.inner == expect {
             return Some(next);
         }
@@ -409,6 +1997,27 @@ where
         None
     }
 
+    pub fn parse_number(&mut self) -> Option<Expr> {
+        let current = self.source.peek()?;
+        if current.inner != Tokens::Number {
+            return None;
+        }
+        let span = current.location.span.clone();
+        let number = self
+            .source_str
+            .get(span.clone())?
+            .iter()
+            .collect::<String>();
+        if number.contains('.') {
+            return Some(Expr::Literal(LiteralExpr::Decimal(number)));
+        }
+        let Ok(number) = number.parse::<i64>() else {
+            self.emit_error("0005", span, "This number does not fit in an integer");
+            unreachable!()
+        };
+        Some(Expr::Literal(LiteralExpr::Integer(number)))
+    }
+
     pub fn advance(&mut self) -> Option<Token> {
         self.idx += 1;
         self.source.next()
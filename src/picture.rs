@@ -0,0 +1,228 @@
+//! Edit masks for `FORMAT "amount" AS "$ZZ,ZZ9.99"`, checked when the program is compiled.
+//!
+//! - `9` is a digit, `Z` a digit that is a space while only zeros are to its left and `*` one
+//!   that is an asterisk instead, for check protection. Both have to come before any `9`. They
+//!   can only follow the decimal point when every digit is one of them, and then a zero shows
+//!   no digits at all: `ZZZ.ZZ` is all spaces, `***.**` all asterisks but the point.
+//! - `.` is the decimal point. `,`, `B` (a space), `0` and `/` are inserted as they are, unless
+//!   they are among the suppressed leading zeros.
+//! - `$` is printed where it is written. Two or more of them float: the sign ends up right in
+//!   front of the first significant digit, and every `$` but the first is a digit.
+//! - `+` and `-` show the sign at the start or the end, and float like `$` when repeated. `+`
+//!   shows `+` or `-`, `-` shows `-` or a space. `CR` and `DB` at the end show when the value is
+//!   negative. Without any of them the sign is dropped.
+
+/// The most digits a mask can have, so every digit can be worked out from an `i128`.
+pub const MAX_DIGITS: u32 = 36;
+
+const AFTER_POINT: &str = "`Z` and `*` can only follow the decimal point when every digit is one";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Symbol {
+    /// A digit with value `10^place`. Suppressed digits have a `fill` that replaces leading
+    /// zeros.
+    Digit {
+        place: u32,
+        fill: Option<u8>,
+    },
+    /// Part of a floating `$`, `+` or `-`. The first of them has no place, the rest are digits.
+    Floating {
+        place: Option<u32>,
+        symbol: u8,
+    },
+    /// `,`, `B`, `0` or `/`.
+    Insert(u8),
+    Point,
+    Currency,
+    /// `+` or `-` at the start or the end.
+    Sign(u8),
+    /// `CR` or `DB`.
+    Credit(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Picture {
+    pub symbols: Vec<Symbol>,
+    /// The number of digits after the decimal point.
+    pub decimals: u32,
+}
+
+impl Picture {
+    /// The number of characters the mask produces.
+    pub fn width(&self) -> usize {
+        self.symbols
+            .iter()
+            .map(|symbol| match symbol {
+                Symbol::Credit(credit) => credit.len(),
+                _ => 1,
+            })
+            .sum()
+    }
+
+    /// Checks `mask` and splits it into symbols. The error explains what is wrong with it.
+    pub fn parse(mask: &str) -> Result<Self, String> {
+        let upper = mask.to_uppercase();
+        let (body, credit) = match upper.len().checked_sub(2).map(|end| upper.split_at(end)) {
+            Some((body, "CR")) => (body, Some("CR")),
+            Some((body, "DB")) => (body, Some("DB")),
+            _ => (upper.as_str(), None),
+        };
+        let chars = body.chars().collect::<Vec<_>>();
+
+        let mut symbols = Vec::new();
+        let mut seen_point = false;
+        let mut seen_nine = false;
+        let mut fill = None;
+        let mut floating = None;
+        let mut signs = 0;
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let last = i + 1 == chars.len();
+            match c {
+                '9' => {
+                    seen_nine = true;
+                    symbols.push(Symbol::Digit {
+                        place: 0,
+                        fill: None,
+                    });
+                }
+                'Z' | '*' => {
+                    let this = if c == 'Z' { b' ' } else { b'*' };
+                    if fill.is_some_and(|fill| fill != this) {
+                        return Err(String::from("`Z` and `*` can't be used together"));
+                    }
+                    if floating.is_some() {
+                        return Err(format!("`{c}` can't be used with a floating sign"));
+                    }
+                    if seen_nine && seen_point {
+                        return Err(String::from(AFTER_POINT));
+                    }
+                    if seen_nine {
+                        return Err(format!("`{c}` has to come before any `9`"));
+                    }
+                    fill = Some(this);
+                    symbols.push(Symbol::Digit {
+                        place: 0,
+                        fill: Some(this),
+                    });
+                }
+                '.' if seen_point => return Err(String::from("There can only be one `.`")),
+                '.' => {
+                    seen_point = true;
+                    symbols.push(Symbol::Point);
+                }
+                ',' | 'B' | '0' | '/' => symbols.push(Symbol::Insert(c as u8)),
+                '$' | '+' | '-' if chars.get(i + 1) == Some(&c) => {
+                    if floating.is_some() {
+                        return Err(String::from("Only one symbol can float"));
+                    }
+                    if fill.is_some() || seen_nine || seen_point {
+                        return Err(format!(
+                            "A floating `{c}` has to come before any digit and the decimal point"
+                        ));
+                    }
+                    floating = Some(c);
+                    if c != '$' {
+                        signs += 1;
+                    }
+                    symbols.push(Symbol::Floating {
+                        place: None,
+                        symbol: c as u8,
+                    });
+                    // The run goes on as long as the symbol repeats, with insertions in between.
+                    i += 1;
+                    while i < chars.len() {
+                        match chars[i] {
+                            next if next == c => symbols.push(Symbol::Floating {
+                                place: Some(0),
+                                symbol: c as u8,
+                            }),
+                            ',' | 'B' | '0' | '/' if chars.get(i + 1) == Some(&c) => {
+                                symbols.push(Symbol::Insert(chars[i] as u8))
+                            }
+                            _ => break,
+                        }
+                        i += 1;
+                    }
+                    continue;
+                }
+                '$' => {
+                    if symbols
+                        .iter()
+                        .any(|symbol| !matches!(symbol, Symbol::Sign(_)))
+                    {
+                        return Err(String::from("`$` has to be at the start"));
+                    }
+                    symbols.push(Symbol::Currency);
+                }
+                '+' | '-' => {
+                    if !symbols.is_empty() && !last {
+                        return Err(format!("`{c}` has to be at the start or the end"));
+                    }
+                    signs += 1;
+                    symbols.push(Symbol::Sign(c as u8));
+                }
+                c => return Err(format!("`{c}` can't be used in a picture")),
+            }
+            i += 1;
+        }
+
+        if let Some(credit) = credit {
+            signs += 1;
+            symbols.push(Symbol::Credit(credit));
+        }
+        if signs > 1 {
+            return Err(String::from("The sign can only be shown once"));
+        }
+
+        // Every digit's place is the number of digits to its right.
+        let mut places = 0;
+        for symbol in symbols.iter_mut().rev() {
+            match symbol {
+                Symbol::Digit { place, .. }
+                | Symbol::Floating {
+                    place: Some(place), ..
+                } => {
+                    *place = places;
+                    places += 1;
+                }
+                _ => {}
+            }
+        }
+        if places == 0 {
+            return Err(String::from("A picture needs at least one digit"));
+        }
+        if places > MAX_DIGITS {
+            return Err(format!("A picture can have at most {MAX_DIGITS} digits"));
+        }
+
+        let decimals = match symbols.iter().position(|symbol| *symbol == Symbol::Point) {
+            Some(point) => {
+                let suppressed = symbols[point..]
+                    .iter()
+                    .any(|symbol| matches!(symbol, Symbol::Digit { fill: Some(_), .. }));
+                if suppressed && seen_nine {
+                    return Err(String::from(AFTER_POINT));
+                }
+                symbols[point..]
+                    .iter()
+                    .filter(|symbol| matches!(symbol, Symbol::Digit { .. }))
+                    .count() as u32
+            }
+            None => 0,
+        };
+        Ok(Self { symbols, decimals })
+    }
+
+    /// What every digit is replaced with when the value is zero, if they all are `Z` or `*`.
+    pub fn zero_fill(&self) -> Option<u8> {
+        let mut fills = self.symbols.iter().filter_map(|symbol| match symbol {
+            Symbol::Digit { fill, .. } => Some(*fill),
+            Symbol::Floating { place: Some(_), .. } => Some(None),
+            _ => None,
+        });
+        let fill = fills.next()??;
+        fills.all(|other| other == Some(fill)).then_some(fill)
+    }
+}
@@ -0,0 +1,783 @@
+//! Resolves every name to the definition it refers to, before codegen.
+//!
+//! A string in an expression names a variable if one with that name is in scope, otherwise it is
+//! a literal. `SET` assigns to the variable if one is in scope and defines a new one in the
+//! innermost scope otherwise. In the same way `CALL FUNCTION` calls through a variable holding a
+//! function before it looks for a function by that name. Scopes nest as module, function and
+//! block (the body of an `IF` or `ON SIZE ERROR`).
+//!
+//! Constants are defined like variables, `DEFINE CONSTANT` outside of any function like a `SET` in
+//! a module, but nothing can store into them.
+//!
+//! A string that doesn't name a variable can still name some in braces, `"Hello, {name}!"` joins
+//! the text around them with their values.
+//!
+//! Condition names are defined next to their variable and expanded here: using one becomes a
+//! comparison of the variable with each of its values, and `SET` to `TRUE` stores the first.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
+use crate::{
+    errors::ErrorClient,
+    node::{
+        Access, Binary, BinaryOperators, Concat, ConditionName, DefId, Expr, FunctionCall,
+        Interpolation, LiteralExpr, Logical, LogicalOperators, ModuleVariable, Parameter,
+        Reference, Stmt, Store, VariableType, Visibility,
+    },
+};
+
+/// C library functions that can be called like functions of `main`, codegen declares them.
+pub const C_FUNCTIONS: [&str; 1] = ["printf"];
+
+/// Where something was defined, so later uses can point back at it.
+pub struct Definition {
+    pub name: String,
+    pub file: String,
+    pub span: Range<usize>,
+    pub visibility: Visibility,
+}
+
+/// What a condition name stands for.
+struct ConditionTarget {
+    variable: Reference,
+    ty: VariableType,
+    values: Vec<Expr>,
+    span: Range<usize>,
+}
+
+impl ConditionTarget {
+    /// Whether the variable holds any of the values.
+    fn test(&self) -> Expr {
+        let span = self.span.clone();
+        self.values
+            .iter()
+            .map(|value| {
+                Expr::BinaryOp(Binary {
+                    l: Box::new(Expr::Variable(self.variable.clone())),
+                    r: Box::new(value.clone()),
+                    op: BinaryOperators::EqualTo,
+                    span: span.clone(),
+                })
+            })
+            .reduce(|l, r| {
+                Expr::Logical(Logical {
+                    l: Box::new(l),
+                    r: Box::new(r),
+                    op: LogicalOperators::Or,
+                    span: span.clone(),
+                })
+            })
+            .expect("A condition name has at least one value.")
+    }
+}
+
+#[derive(Default)]
+pub struct Resolver {
+    /// Every definition, indexed by its `DefId`.
+    pub definitions: Vec<Definition>,
+    modules: HashMap<String, DefId>,
+    /// Functions of every module, keyed by `{module}-{name}` like in codegen.
+    functions: HashMap<String, DefId>,
+    /// The module-level variables of every module.
+    globals: HashMap<String, HashMap<String, DefId>>,
+    /// Every condition name, keyed by its own definition.
+    conditions: HashMap<DefId, ConditionTarget>,
+    /// Every constant, which can't be stored into.
+    constants: HashSet<DefId>,
+    /// The local scopes of the function being resolved, innermost last.
+    scopes: Vec<HashMap<String, DefId>>,
+    module: String,
+    file: String,
+}
+
+impl Resolver {
+    /// Registers the modules, functions and module-level variables of a file. This runs over
+    /// every file before any of them is resolved, so definitions can be used from anywhere.
+    pub fn declare(&mut self, file: &str, nodes: &mut [Stmt]) {
+        self.file = file.to_string();
+        for node in nodes {
+            match node {
+                Stmt::Module {
+                    name: LiteralExpr::String(name),
+                    nodes,
+                    span,
+                } => {
+                    if let Some(&previous) = self.modules.get(name) {
+                        self.duplicate(span.clone(), previous);
+                    }
+                    let id = self.define(name, span.clone(), Visibility::Public);
+                    self.modules.insert(name.clone(), id);
+                    if let Stmt::Block(contents) = &mut **nodes {
+                        for content in contents {
+                            self.declare_member(&name.clone(), content);
+                        }
+                    }
+                }
+                Stmt::Function { .. } | Stmt::Constant(_) => self.declare_member("main", node),
+                _ => {}
+            }
+        }
+    }
+
+    fn declare_member(&mut self, module: &str, node: &mut Stmt) {
+        match node {
+            Stmt::Function {
+                name: LiteralExpr::String(name),
+                visibility,
+                id,
+                span,
+                ..
+            } => {
+                let key = format!("{module}-{name}");
+                if let Some(&previous) = self.functions.get(&key) {
+                    self.duplicate(span.clone(), previous);
+                }
+                let def = self.define(name, span.clone(), *visibility);
+                self.functions.insert(key, def);
+                *id = Some(def);
+            }
+            Stmt::Constant(constant) => {
+                let globals = self.globals.get(module);
+                if let Some(&previous) = globals.and_then(|vars| vars.get(&constant.name)) {
+                    self.duplicate(constant.span.clone(), previous);
+                }
+                let def = self.define(&constant.name, constant.span.clone(), Visibility::default());
+                self.globals
+                    .entry(module.to_string())
+                    .or_default()
+                    .insert(constant.name.clone(), def);
+                self.constants.insert(def);
+                constant.id = Some(def);
+            }
+            Stmt::Variable {
+                name,
+                ty,
+                visibility,
+                id,
+                span,
+                conditions,
+                ..
+            } => {
+                if let Some(&previous) = self.globals.get(module).and_then(|vars| vars.get(name)) {
+                    self.duplicate(span.clone(), previous);
+                }
+                let def = self.define(name, span.clone(), *visibility);
+                self.globals
+                    .entry(module.to_string())
+                    .or_default()
+                    .insert(name.clone(), def);
+                *id = Some(def);
+
+                let variable = Reference {
+                    name: name.clone(),
+                    id: def,
+                };
+                for condition in conditions {
+                    let globals = self.globals.get(module);
+                    if let Some(&previous) = globals.and_then(|vars| vars.get(&condition.name)) {
+                        self.duplicate(condition.span.clone(), previous);
+                    }
+                    let def = self.define_condition(variable.clone(), ty, condition, *visibility);
+                    self.globals
+                        .entry(module.to_string())
+                        .or_default()
+                        .insert(condition.name.clone(), def);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Resolves the names used in a file. Statements outside of any function share one scope,
+    /// they make up the implicit `main`.
+    pub fn resolve(&mut self, file: &str, nodes: &mut [Stmt]) {
+        self.file = file.to_string();
+        let mut top_level = HashMap::new();
+        for node in nodes {
+            match node {
+                Stmt::Module {
+                    name: LiteralExpr::String(name),
+                    nodes,
+                    ..
+                } => {
+                    self.module = name.clone();
+                    if let Stmt::Block(contents) = &mut **nodes {
+                        for content in contents {
+                            match &mut **content {
+                                Stmt::Function {
+                                    parameters, nodes, ..
+                                } => self.resolve_function(parameters, nodes),
+                                // A module-level variable can start out holding a function.
+                                Stmt::Variable {
+                                    value: Some(value @ Expr::Function(_)),
+                                    ..
+                                } => self.resolve_expr(value),
+                                Stmt::Constant(constant) => self.resolve_expr(&mut constant.value),
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                Stmt::Function {
+                    parameters, nodes, ..
+                } => {
+                    self.module = String::from("main");
+                    self.resolve_function(parameters, nodes);
+                }
+                stmt => {
+                    self.module = String::from("main");
+                    self.scopes = vec![std::mem::take(&mut top_level)];
+                    self.resolve_stmt(stmt);
+                    top_level = self.scopes.pop().unwrap_or_default();
+                }
+            }
+        }
+    }
+
+    /// The arguments are the first locals of the function.
+    fn resolve_function(&mut self, parameters: &mut [Parameter], body: &mut Stmt) {
+        let mut scope = HashMap::new();
+        for parameter in parameters {
+            let def = self.define(
+                &parameter.name,
+                parameter.span.clone(),
+                Visibility::default(),
+            );
+            parameter.id = Some(def);
+            scope.insert(parameter.name.clone(), def);
+        }
+        self.scopes = vec![scope];
+        self.resolve_stmt(body);
+        self.scopes.clear();
+    }
+
+    fn resolve_stmt(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::Block(nodes) => {
+                for node in nodes {
+                    self.resolve_stmt(node);
+                }
+            }
+            Stmt::Condition(condition) => {
+                self.resolve_expr(&mut condition.condition);
+                self.scopes.push(HashMap::new());
+                self.resolve_stmt(&mut condition.then);
+                self.scopes.pop();
+                if let Some(el) = &mut condition.el {
+                    self.scopes.push(HashMap::new());
+                    self.resolve_stmt(el);
+                    self.scopes.pop();
+                }
+            }
+            Stmt::Evaluate(evaluate) => {
+                self.resolve_expr(&mut evaluate.subject);
+                for value in evaluate
+                    .branches
+                    .iter_mut()
+                    .flat_map(|when| &mut when.values)
+                {
+                    self.resolve_expr(value);
+                }
+                let bodies = evaluate.branches.iter_mut().map(|when| &mut when.body);
+                for body in bodies.chain(evaluate.other.as_mut()) {
+                    self.scopes.push(HashMap::new());
+                    self.resolve_stmt(body);
+                    self.scopes.pop();
+                }
+            }
+            Stmt::Expr(expr) => self.resolve_expr(expr),
+            // Outside of a function the constant was already defined by `declare`.
+            Stmt::Constant(constant) => {
+                self.resolve_expr(&mut constant.value);
+                if constant.id.is_none() {
+                    if let Some(existing) = self.lookup(&constant.name) {
+                        self.duplicate(constant.span.clone(), existing);
+                    }
+                    let def =
+                        self.define(&constant.name, constant.span.clone(), Visibility::default());
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.insert(constant.name.clone(), def);
+                    }
+                    self.constants.insert(def);
+                    constant.id = Some(def);
+                }
+            }
+            // The value of a `SET` is a literal or a function, so only the name and functions are
+            // resolved.
+            Stmt::Variable {
+                name,
+                ty,
+                value,
+                id,
+                span,
+                conditions,
+                ..
+            } => {
+                if let Some(value @ Expr::Function(_)) = value {
+                    self.resolve_expr(value);
+                }
+                let def = match self.lookup(name) {
+                    // Only a `SET` gives a value, so anything else can't assign.
+                    Some(existing) if value.is_none() => {
+                        self.duplicate(span.clone(), existing);
+                        existing
+                    }
+                    Some(existing) if self.conditions.contains_key(&existing) => {
+                        *stmt = self.set_condition(existing, value, span);
+                        return;
+                    }
+                    Some(existing) if self.constants.contains(&existing) => {
+                        self.constant_error(existing, span.clone());
+                        existing
+                    }
+                    Some(existing) => existing,
+                    None => {
+                        let def = self.define(name, span.clone(), Visibility::default());
+                        if let Some(scope) = self.scopes.last_mut() {
+                            scope.insert(name.clone(), def);
+                        }
+                        def
+                    }
+                };
+                *id = Some(def);
+
+                let variable = Reference {
+                    name: name.clone(),
+                    id: def,
+                };
+                for condition in conditions {
+                    if let Some(previous) = self.lookup(&condition.name) {
+                        self.duplicate(condition.span.clone(), previous);
+                    }
+                    let visibility = Visibility::default();
+                    let def = self.define_condition(variable.clone(), ty, condition, visibility);
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.insert(condition.name.clone(), def);
+                    }
+                }
+            }
+            Stmt::Compute(compute) => {
+                for store in &mut compute.stores {
+                    self.resolve_expr(&mut store.value);
+                }
+                for store in &mut compute.stores {
+                    self.resolve_target(store);
+                }
+                if let Some(handler) = &mut compute.on_size_error {
+                    self.scopes.push(HashMap::new());
+                    self.resolve_stmt(handler);
+                    self.scopes.pop();
+                }
+            }
+            // The loop variable only exists in the body of the loop.
+            Stmt::ForEach(each) => {
+                self.resolve_expr(&mut each.collection);
+                self.require_defined(&each.collection, each.span.clone());
+                if let Some(existing) = self.lookup(&each.variable) {
+                    self.duplicate(each.span.clone(), existing);
+                }
+                let def = self.define(&each.variable, each.span.clone(), Visibility::default());
+                each.id = Some(def);
+                self.scopes
+                    .push(HashMap::from([(each.variable.clone(), def)]));
+                self.resolve_stmt(&mut each.body);
+                self.scopes.pop();
+            }
+            Stmt::Sort(sort) => {
+                self.resolve_expr(&mut sort.collection);
+                self.require_defined(&sort.collection, sort.span.clone());
+            }
+            Stmt::Search(search) => {
+                self.resolve_expr(&mut search.collection);
+                self.require_defined(&search.collection, search.span.clone());
+                self.resolve_expr(&mut search.giving.value);
+                self.resolve_target(&mut search.giving);
+            }
+            Stmt::Move(store) | Stmt::Append(store) => {
+                self.resolve_expr(&mut store.value);
+                self.resolve_target(store);
+            }
+            Stmt::Display { exprs, .. } => {
+                for expr in exprs {
+                    self.resolve_expr(expr);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Statements other than `SET` can only store into variables that already exist.
+    fn resolve_target(&mut self, store: &mut Store) {
+        let Some(def) = self.lookup(&store.target) else {
+            let label = format!("\"{}\" has not been `SET` yet", store.target);
+            let mut error = self.error("0018", store.span.clone());
+            error.add_label(Some(&label));
+            error.build_and_emit();
+            unreachable!()
+        };
+        if self.conditions.contains_key(&def) {
+            self.condition_error(def, store.span.clone());
+        }
+        if self.constants.contains(&def) {
+            self.constant_error(def, store.span.clone());
+        }
+        store.id = Some(def);
+        for access in &mut store.path {
+            if let Access::Item(index) | Access::Entry(index) = access {
+                self.resolve_expr(&mut index.value);
+            }
+        }
+    }
+
+    fn define_condition(
+        &mut self,
+        variable: Reference,
+        ty: &VariableType,
+        condition: &mut ConditionName,
+        visibility: Visibility,
+    ) -> DefId {
+        let def = self.define(&condition.name, condition.span.clone(), visibility);
+        condition.id = Some(def);
+        self.conditions.insert(
+            def,
+            ConditionTarget {
+                variable,
+                ty: ty.clone(),
+                values: condition.values.clone(),
+                span: condition.span.clone(),
+            },
+        );
+        def
+    }
+
+    /// `SET "is_active" TO TRUE` becomes a `SET` of the variable to the condition's first value.
+    /// There is no value that is sure to make a condition false, so that can't be set.
+    fn set_condition(&self, def: DefId, value: &Option<Expr>, span: &Range<usize>) -> Stmt {
+        if !matches!(value, Some(Expr::Literal(LiteralExpr::Boolean(true)))) {
+            self.condition_error(def, span.clone());
+        }
+        let target = &self.conditions[&def];
+        Stmt::Variable {
+            name: target.variable.name.clone(),
+            ty: target.ty.clone(),
+            value: target.values.first().cloned(),
+            visibility: Visibility::default(),
+            id: Some(target.variable.id),
+            span: span.clone(),
+            conditions: Vec::new(),
+        }
+    }
+
+    fn condition_error(&self, def: DefId, span: Range<usize>) {
+        let definition = &self.definitions[def.0];
+        let target = &self.conditions[&def];
+        let label = format!(
+            "\"{}\" is a condition on \"{}\", it can only be `SET` to TRUE",
+            definition.name, target.variable.name
+        );
+        let mut error = self.error("0028", span);
+        error.add_label(Some(&label));
+        error.add_secondary_label_in(&definition.file, definition.span.clone(), "defined here");
+        error.build_and_emit();
+    }
+
+    fn constant_error(&self, def: DefId, span: Range<usize>) {
+        let definition = &self.definitions[def.0];
+        let label = format!("\"{}\" is a constant, it can't be changed", definition.name);
+        let mut error = self.error("0037", span);
+        error.add_label(Some(&label));
+        error.add_secondary_label_in(&definition.file, definition.span.clone(), "defined here");
+        error.build_and_emit();
+    }
+
+    fn resolve_expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Literal(LiteralExpr::String(name)) => {
+                if let Some(id) = self.lookup(name) {
+                    *expr = self.reference(name, id);
+                }
+            }
+            Expr::ModuleVariable(variable) => {
+                let id = self.lookup_global(variable);
+                *expr = self.reference(&variable.name, id);
+            }
+            Expr::Interpolation(interpolation) => {
+                *expr = match self.lookup(&interpolation.text) {
+                    Some(id) => self.reference(&interpolation.text, id),
+                    None => self.interpolate(interpolation),
+                };
+            }
+            Expr::Call(call) => self.resolve_call(call),
+            Expr::Function(function) => {
+                let module = self.function_module(&function.name, &function.module);
+                let id = self.lookup_function(&function.name, &module, &function.span);
+                function.module = Some(module);
+                function.id = Some(id);
+            }
+            Expr::BinaryOp(binary) => {
+                self.resolve_expr(&mut binary.l);
+                self.resolve_expr(&mut binary.r);
+            }
+            Expr::Arithmetic(arithmetic) => {
+                self.resolve_expr(&mut arithmetic.l);
+                self.resolve_expr(&mut arithmetic.r);
+            }
+            Expr::Logical(logical) => {
+                self.resolve_expr(&mut logical.l);
+                self.resolve_expr(&mut logical.r);
+            }
+            Expr::Concat(concat) => {
+                self.resolve_expr(&mut concat.l);
+                self.resolve_expr(&mut concat.r);
+            }
+            Expr::Characters(characters) => {
+                self.resolve_expr(&mut characters.text);
+                self.resolve_expr(&mut characters.from.value);
+                self.resolve_expr(&mut characters.through.value);
+            }
+            Expr::Length(length) => self.resolve_expr(&mut length.text),
+            Expr::Negate(inner) | Expr::Not(inner) => self.resolve_expr(inner),
+            Expr::Edited(edited) => self.resolve_expr(&mut edited.value),
+            Expr::Member(member) => {
+                self.resolve_expr(&mut member.record);
+                self.require_defined(&member.record, member.span.clone());
+            }
+            Expr::Item(item) => {
+                self.resolve_expr(&mut item.list);
+                self.require_defined(&item.list, item.index.span.clone());
+                self.resolve_expr(&mut item.index.value);
+            }
+            Expr::Entry(entry) => {
+                self.resolve_expr(&mut entry.table);
+                self.require_defined(&entry.table, entry.key.span.clone());
+                self.resolve_expr(&mut entry.key.value);
+            }
+            Expr::Literal(_) | Expr::Variable(_) => {}
+        }
+    }
+
+    /// The variable `name` resolved to, or the test of a condition name.
+    fn reference(&self, name: &str, id: DefId) -> Expr {
+        match self.conditions.get(&id) {
+            Some(target) => target.test(),
+            None => Expr::Variable(Reference {
+                name: name.to_string(),
+                id,
+            }),
+        }
+    }
+
+    /// `"Hello, {name}!"` joins the text around `{name}` with the variable. A name that isn't in
+    /// scope is kept as it is written, with a warning. `{{` and `}}` are single braces, and so
+    /// is a brace that isn't part of a pair.
+    fn interpolate(&self, interpolation: &Interpolation) -> Expr {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut rest = interpolation.text.as_str();
+        while let Some(start) = rest.find(['{', '}']) {
+            literal.push_str(&rest[..start]);
+            let brace = &rest[start..start + 1];
+            rest = &rest[start + 1..];
+            if let Some(after) = rest.strip_prefix(brace) {
+                literal.push_str(brace);
+                rest = after;
+                continue;
+            }
+            let (true, Some(length)) = (brace == "{", rest.find('}')) else {
+                literal.push_str(brace);
+                continue;
+            };
+            let name = &rest[..length];
+            match self.lookup(name) {
+                Some(id) => {
+                    if !literal.is_empty() {
+                        let text = std::mem::take(&mut literal);
+                        parts.push(Expr::Literal(LiteralExpr::String(text)));
+                    }
+                    parts.push(self.reference(name, id));
+                }
+                None => {
+                    self.unknown_name(name, interpolation.span.clone());
+                    literal.push_str(&format!("{{{name}}}"));
+                }
+            }
+            rest = &rest[length + 1..];
+        }
+        literal.push_str(rest);
+        // Joined with nothing, a string of just `{count}` is still a string.
+        if !literal.is_empty() || parts.len() < 2 {
+            parts.push(Expr::Literal(LiteralExpr::String(literal)));
+        }
+        parts
+            .into_iter()
+            .reduce(|l, r| {
+                Expr::Concat(Concat {
+                    l: Box::new(l),
+                    r: Box::new(r),
+                    span: interpolation.span.clone(),
+                })
+            })
+            .unwrap()
+    }
+
+    /// A name in braces that isn't a variable is most likely misspelled, but it could also be
+    /// text that happens to be in braces.
+    fn unknown_name(&self, name: &str, span: Range<usize>) {
+        let label = format!("\"{name}\" is not a variable, so \"{{{name}}}\" is kept as it is");
+        let mut warning = ErrorClient::new("0041", crate::errors::MessageKind::WARNING);
+        warning.set_file(&self.file, &self.file);
+        warning.set_span(span);
+        warning.add_label(Some(&label));
+        warning.add_note("Braces that don't name a variable are written `{{` and `}}`.");
+        warning.build_and_emit();
+    }
+
+    /// A record or list that is still a literal after resolving was never defined.
+    fn require_defined(&self, expr: &Expr, span: Range<usize>) {
+        if let Expr::Literal(LiteralExpr::String(name)) = expr {
+            let label = format!("\"{name}\" has not been defined yet");
+            let mut error = self.error("0018", span);
+            error.add_label(Some(&label));
+            error.build_and_emit();
+        }
+    }
+
+    /// Qualifies the call with the module of the function it calls. A call of a variable
+    /// calls the function it holds instead.
+    fn resolve_call(&mut self, call: &mut FunctionCall) {
+        for arg in call.args.iter_mut().flatten() {
+            self.resolve_expr(arg);
+        }
+        let Expr::Literal(LiteralExpr::String(name)) = &*call.func else {
+            return;
+        };
+        if let (None, Some(id)) = (&call.module, self.lookup(name)) {
+            if !self.conditions.contains_key(&id) {
+                let name = name.clone();
+                *call.func = Expr::Variable(Reference { name, id });
+                return;
+            }
+        }
+        let module = self.function_module(name, &call.module);
+        if module == "main" && C_FUNCTIONS.contains(&name.as_str()) {
+            call.module = Some(module);
+            return;
+        }
+        call.id = Some(self.lookup_function(name, &module, &call.span));
+        call.module = Some(module);
+    }
+
+    /// The module of a function that is named without one: the current module if it has a
+    /// function by that name, otherwise `main`.
+    fn function_module(&self, name: &str, module: &Option<String>) -> String {
+        match module {
+            Some(module) => module.clone(),
+            None if self
+                .functions
+                .contains_key(&format!("{}-{name}", self.module)) =>
+            {
+                self.module.clone()
+            }
+            None => String::from("main"),
+        }
+    }
+
+    /// The function `name` of `module`, which has to be visible from the current module.
+    fn lookup_function(&self, name: &str, module: &str, span: &Range<usize>) -> DefId {
+        let Some(&id) = self.functions.get(&format!("{module}-{name}")) else {
+            let label = format!("There is no function \"{name}\" in module \"{module}\"");
+            let mut error = self.error("0019", span.clone());
+            error.add_label(Some(&label));
+            error.build_and_emit();
+            unreachable!()
+        };
+
+        let definition = &self.definitions[id.0];
+        if module != self.module && definition.visibility == Visibility::Private {
+            let label = format!("\"{name}\" is private to module \"{module}\"");
+            let mut error = self.error("0017", span.clone());
+            error.add_label(Some(&label));
+            error.add_secondary_label_in(
+                &definition.file,
+                definition.span.clone(),
+                "defined as private here",
+            );
+            error.add_note("Mark the function `PUBLIC` to use it from other modules.");
+            error.build_and_emit();
+        }
+        id
+    }
+
+    /// The module-level variable named by `"rate" OF MODULE "billing"`, which has to be visible
+    /// from the current module like a function.
+    fn lookup_global(&self, variable: &ModuleVariable) -> DefId {
+        let ModuleVariable { name, module, span } = variable;
+        let globals = self.globals.get(module);
+        let Some(&id) = globals.and_then(|vars| vars.get(name)) else {
+            let label = format!("There is no variable \"{name}\" in module \"{module}\"");
+            let mut error = self.error("0018", span.clone());
+            error.add_label(Some(&label));
+            error.build_and_emit();
+            unreachable!()
+        };
+
+        let definition = &self.definitions[id.0];
+        if *module != self.module && definition.visibility == Visibility::Private {
+            let label = format!("\"{name}\" is private to module \"{module}\"");
+            let mut error = self.error("0017", span.clone());
+            error.add_label(Some(&label));
+            error.add_secondary_label_in(
+                &definition.file,
+                definition.span.clone(),
+                "defined as private here",
+            );
+            error.add_note("Mark the `SET` `PUBLIC` to use the variable from other modules.");
+            error.build_and_emit();
+        }
+        id
+    }
+
+    /// Looks through the local scopes from the innermost outwards, then at the module's globals.
+    fn lookup(&self, name: &str) -> Option<DefId> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .or_else(|| self.globals.get(&self.module)?.get(name))
+            .copied()
+    }
+
+    fn define(&mut self, name: &str, span: Range<usize>, visibility: Visibility) -> DefId {
+        self.definitions.push(Definition {
+            name: name.to_string(),
+            file: self.file.clone(),
+            span,
+            visibility,
+        });
+        DefId(self.definitions.len() - 1)
+    }
+
+    fn duplicate(&self, span: Range<usize>, previous: DefId) {
+        let definition = &self.definitions[previous.0];
+        let label = format!("\"{}\" is already defined", definition.name);
+        let mut error = self.error("0020", span);
+        error.add_label(Some(&label));
+        error.add_secondary_label_in(
+            &definition.file,
+            definition.span.clone(),
+            "first defined here",
+        );
+        error.build_and_emit();
+    }
+
+    fn error<'a>(&'a self, code: &'a str, span: Range<usize>) -> ErrorClient<'a> {
+        let mut error = ErrorClient::new(code, crate::errors::MessageKind::ERROR);
+        error.end_process(true);
+        error.set_file(&self.file, &self.file);
+        error.set_span(span);
+        error
+    }
+}
@@ -0,0 +1,974 @@
+//! Checks that every expression is used with the right types, after names have been resolved
+//! and before codegen runs.
+
+use std::{collections::HashMap, ops::Range};
+
+use crate::{
+    errors::ErrorClient,
+    node::{
+        Access, ArithmeticOperators, BinaryOperators, DefId, Expr, Field, FunctionCall, Index,
+        LiteralExpr, Stmt, Store, VariableType, MAX_DIGITS,
+    },
+};
+
+#[derive(Default)]
+pub struct TypeChecker {
+    /// The type of every variable, keyed by its definition. Set by the `SET` that defines it.
+    types: HashMap<DefId, VariableType>,
+    file: String,
+}
+
+/// Integers can be used wherever a decimal is expected and any number can be stored in a
+/// `NUMBER`, which drops the decimals it has no room for. Any characters fit in a `TEXT` once
+/// they are padded or cut off. Anything else has to match exactly.
+pub fn assignable(from: &VariableType, to: &VariableType) -> bool {
+    match (from, to) {
+        (VariableType::Integer, VariableType::Decimal) => true,
+        (VariableType::Fixed { .. }, VariableType::Decimal) => true,
+        (from, VariableType::Fixed { .. }) => from.is_numeric(),
+        (from, VariableType::Text { .. }) => from.is_text(),
+        (VariableType::Text { .. }, VariableType::String) => true,
+        (from, to) => from == to,
+    }
+}
+
+/// `MOVE` also converts between any two kinds of number, truncating like COBOL does.
+pub fn movable(from: &VariableType, to: &VariableType) -> bool {
+    assignable(from, to) || (from.is_numeric() && to.is_numeric())
+}
+
+/// Whether values of these types can be tested for being equal, as `IS EQUAL TO` and `SEARCH`
+/// do.
+fn equatable(l: &VariableType, r: &VariableType) -> bool {
+    (l == r && !matches!(l, VariableType::Record(_)))
+        || (l.is_numeric() && r.is_numeric())
+        || (l.is_text() && r.is_text())
+}
+
+/// Whether values of this type have an order `SORT` can put them in, like `IS LESS THAN`.
+fn ordered(ty: &VariableType) -> bool {
+    ty.is_numeric() || ty.is_text()
+}
+
+/// The type with its article, for labels.
+fn a(ty: &VariableType) -> String {
+    match ty {
+        VariableType::Integer => format!("an {ty}"),
+        _ => format!("a {ty}"),
+    }
+}
+
+/// `"a"`, `"a" or "b"`, `"a", "b" or "c"` and so on, for labels.
+fn either(names: &[&String]) -> String {
+    let names = names
+        .iter()
+        .map(|name| format!("\"{name}\""))
+        .collect::<Vec<_>>();
+    match names.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} or {last}", rest.join(", ")),
+        None => String::new(),
+    }
+}
+
+/// What a value is, for labels. A figurative constant is named rather than given the type it
+/// has when it can't stand for the one expected.
+fn what(value: &Expr, ty: &VariableType) -> String {
+    match value {
+        Expr::Literal(LiteralExpr::Figurative(figurative)) => figurative.to_string(),
+        _ => a(ty),
+    }
+}
+
+impl TypeChecker {
+    /// Records the types of every function and its arguments. This runs over every file
+    /// before `declare`, as a module-level variable can hold any of them.
+    pub fn declare_functions(&mut self, nodes: &[Stmt]) {
+        for node in nodes {
+            match node {
+                Stmt::Module {
+                    name: LiteralExpr::String(module),
+                    nodes,
+                    ..
+                } => {
+                    let Stmt::Block(contents) = &**nodes else {
+                        continue;
+                    };
+                    for content in contents {
+                        self.declare_function(module, content);
+                    }
+                }
+                node => self.declare_function("main", node),
+            }
+        }
+    }
+
+    /// Records the types of module-level variables and constants, so functions can use them no
+    /// matter where they are defined.
+    pub fn declare(&mut self, file: &str, nodes: &[Stmt]) {
+        self.file = file.to_string();
+        for node in nodes {
+            let contents = match node {
+                Stmt::Module { nodes, .. } => match &**nodes {
+                    Stmt::Block(contents) => contents,
+                    _ => continue,
+                },
+                Stmt::Constant(_) => {
+                    self.check_stmt(node);
+                    continue;
+                }
+                _ => continue,
+            };
+            for content in contents {
+                if let Stmt::Variable { .. } | Stmt::Constant(_) = **content {
+                    self.check_stmt(content);
+                }
+            }
+        }
+    }
+
+    /// `main` of module `main` runs the program, so it gets no type and can't be used as a value.
+    fn declare_function(&mut self, module: &str, function: &Stmt) {
+        let Stmt::Function {
+            name: LiteralExpr::String(name),
+            parameters,
+            id: Some(id),
+            ..
+        } = function
+        else {
+            return;
+        };
+        for parameter in parameters {
+            if let Some(id) = parameter.id {
+                self.types.insert(id, parameter.ty.clone());
+            }
+        }
+        if module == "main" && name == "main" {
+            return;
+        }
+        let parameters = parameters.iter().map(|p| p.ty.clone()).collect();
+        self.types
+            .insert(*id, VariableType::Function { parameters });
+    }
+    pub fn check(&mut self, file: &str, nodes: &[Stmt]) {
+        self.file = file.to_string();
+        for node in nodes {
+            match node {
+                Stmt::Module { nodes, .. } => {
+                    let Stmt::Block(contents) = &**nodes else {
+                        continue;
+                    };
+                    for content in contents {
+                        if let Stmt::Function { .. } = **content {
+                            self.check_stmt(content);
+                        }
+                    }
+                }
+                Stmt::Constant(_) => {}
+                node => self.check_stmt(node),
+            }
+        }
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(nodes) => {
+                for node in nodes {
+                    self.check_stmt(node);
+                }
+            }
+            Stmt::Function {
+                parameters, nodes, ..
+            } => {
+                // Like with `MOVE`, the caller's copy and the function's would share the items.
+                for parameter in parameters {
+                    if parameter.ty.any(&|ty| {
+                        matches!(
+                            ty,
+                            VariableType::List { length: None, .. } | VariableType::Table { .. }
+                        )
+                    }) {
+                        let label = format!(
+                            "\"{}\" would hold a list that grows or a table, which can't be passed to a function",
+                            parameter.name
+                        );
+                        self.error(parameter.span.clone(), &label);
+                    }
+                    self.check_sorted(&parameter.ty);
+                }
+                self.check_stmt(nodes);
+            }
+            Stmt::Variable {
+                name,
+                ty,
+                value,
+                id: Some(id),
+                span,
+                conditions,
+                ..
+            } => {
+                // The first `SET` of a variable decides its type, later ones have to fit it.
+                let ty = self.types.entry(*id).or_insert_with(|| ty.clone()).clone();
+                let value_ty = match value {
+                    Some(value) => self.type_as(value, &ty, span),
+                    None => ty.clone(),
+                };
+                if !assignable(&value_ty, &ty) {
+                    let value = value
+                        .as_ref()
+                        .map_or_else(|| a(&value_ty), |value| what(value, &value_ty));
+                    let label = format!("\"{name}\" is {}, but this is {value}", a(&ty));
+                    self.error(span.clone(), &label);
+                }
+                if let Some(value) = value {
+                    self.check_truncation(value, &ty, span);
+                }
+                if let VariableType::Record(fields) = &ty {
+                    self.check_overlays(fields);
+                }
+                self.check_sorted(&ty);
+                let keys = |ty: &VariableType| matches!(ty, VariableType::Table { key, .. } if **key != VariableType::String);
+                if ty.any(&keys) {
+                    let label = "The keys of a table are STRINGs, written as `FROM TEXT`";
+                    self.error(span.clone(), label);
+                }
+                // Each value of a condition name has to be one the variable can hold.
+                for condition in conditions {
+                    for value in &condition.values {
+                        let value_ty = self.type_as(value, &ty, &condition.span);
+                        if !assignable(&value_ty, &ty) {
+                            let label = format!(
+                                "\"{name}\" is {}, so it can't be {}",
+                                a(&ty),
+                                what(value, &value_ty)
+                            );
+                            self.error(condition.span.clone(), &label);
+                        }
+                        self.check_truncation(value, &ty, &condition.span);
+                    }
+                }
+                if let (VariableType::Fixed { digits, scale }, Some(value)) = (&ty, value) {
+                    let fits = value
+                        .scaled_literal(*scale)
+                        .is_none_or(|value| value.abs() < 10i128.pow(*digits));
+                    if !fits {
+                        let label = format!("\"{name}\" only has room for {digits} digits");
+                        self.report("0024", span.clone(), &label);
+                    }
+                }
+            }
+            Stmt::Constant(constant) => {
+                let ty = self.type_of(&constant.value, &constant.span);
+                if !constant.value.is_literal() {
+                    let label = "This can't be worked out while compiling";
+                    self.report("0038", constant.span.clone(), label);
+                }
+                if let Some(id) = constant.id {
+                    self.types.insert(id, ty);
+                }
+            }
+            Stmt::Compute(compute) => {
+                for store in &compute.stores {
+                    let value_ty = self.type_of(&store.value, &store.span);
+                    let Some(ty) = &self.target_type(store) else {
+                        continue;
+                    };
+                    if !ty.is_numeric() {
+                        let label = format!(
+                            "\"{}\" is {}, so it can't hold the result of arithmetic",
+                            store.target,
+                            a(ty)
+                        );
+                        self.error(store.span.clone(), &label);
+                    }
+                    if !value_ty.is_numeric() {
+                        let label = format!("Expected a number, but this is {}", a(&value_ty));
+                        self.error(store.span.clone(), &label);
+                    }
+                }
+                if let Some(handler) = &compute.on_size_error {
+                    self.check_stmt(handler);
+                }
+            }
+            Stmt::Move(store) => {
+                let Some(ty) = self.target_type(store) else {
+                    self.type_of(&store.value, &store.span);
+                    return;
+                };
+                // Both copies would share the items, and growing one would lose them for the
+                // other.
+                if ty.any(&|ty| {
+                    matches!(
+                        ty,
+                        VariableType::List { length: None, .. } | VariableType::Table { .. }
+                    )
+                }) {
+                    let label = format!(
+                        "\"{}\" holds a list that grows or a table, which can't be copied by `MOVE`",
+                        store.target
+                    );
+                    self.error(store.span.clone(), &label);
+                }
+                let value_ty = self.type_as(&store.value, &ty, &store.span);
+                if !movable(&value_ty, &ty) {
+                    let label = format!(
+                        "\"{}\" is {}, but this is {}",
+                        store.target,
+                        a(&ty),
+                        what(&store.value, &value_ty)
+                    );
+                    self.error(store.span.clone(), &label);
+                }
+                self.check_truncation(&store.value, &ty, &store.span);
+            }
+            Stmt::Condition(condition) => {
+                let ty = self.type_of(&condition.condition, &condition.span);
+                if ty != VariableType::Boolean {
+                    let label = format!("Expected a condition, but this is {}", a(&ty));
+                    self.error(condition.span.clone(), &label);
+                }
+                self.check_stmt(&condition.then);
+                if let Some(el) = &condition.el {
+                    self.check_stmt(el);
+                }
+            }
+            Stmt::Expr(Expr::Call(call)) => self.check_call(call),
+            Stmt::Display { exprs, span } => {
+                for expr in exprs {
+                    let ty = self.type_of(expr, span);
+                    if ty.any(&|ty| {
+                        matches!(ty, VariableType::List { .. } | VariableType::Table { .. })
+                    }) {
+                        let label = format!(
+                            "This is {}, lists and tables are displayed one item at a time",
+                            a(&ty)
+                        );
+                        self.error(span.clone(), &label);
+                    }
+                    if ty.any(&|ty| matches!(ty, VariableType::Function { .. })) {
+                        let label = format!(
+                            "This is {}, functions can be called but not displayed",
+                            a(&ty)
+                        );
+                        self.error(span.clone(), &label);
+                    }
+                }
+            }
+            Stmt::Evaluate(evaluate) => {
+                let ty = self.type_of(&evaluate.subject, &evaluate.span);
+                let mut handled = Vec::new();
+                for when in &evaluate.branches {
+                    for value in &when.values {
+                        let value_ty = self.type_as(value, &ty, &when.span);
+                        if !equatable(&ty, &value_ty) {
+                            let label = format!(
+                                "This is {}, which can't be equal to {}",
+                                what(value, &value_ty),
+                                a(&ty)
+                            );
+                            self.error(when.span.clone(), &label);
+                        }
+                        if let Expr::Literal(LiteralExpr::String(value)) = value {
+                            handled.push(value);
+                        }
+                    }
+                    self.check_stmt(&when.body);
+                }
+                match (&evaluate.other, &ty) {
+                    (Some(other), _) => self.check_stmt(other),
+                    (None, VariableType::Choice { name, values }) => {
+                        let missing = values
+                            .iter()
+                            .filter(|value| !handled.contains(value))
+                            .collect::<Vec<_>>();
+                        if !missing.is_empty() {
+                            let label = format!("\"{name}\" can also be {}", either(&missing));
+                            let note = "Add a `WHEN` for every value, or a `WHEN OTHER`.";
+                            self.report_with_note("0035", evaluate.span.clone(), &label, note);
+                        }
+                    }
+                    (None, _) => {}
+                }
+            }
+            Stmt::ForEach(each) => {
+                let collection = self.type_of(&each.collection, &each.span);
+                let ty = match collection {
+                    VariableType::List { item, .. } => *item,
+                    VariableType::Table { key, .. } => *key,
+                    ty => {
+                        let label = format!(
+                            "`FOR EACH` goes through a list or a table, this is {}",
+                            a(&ty)
+                        );
+                        self.error(each.span.clone(), &label);
+                        return;
+                    }
+                };
+                if let Some(id) = each.id {
+                    self.types.insert(id, ty);
+                }
+                self.check_stmt(&each.body);
+            }
+            Stmt::Sort(sort) => {
+                let collection = self.type_of(&sort.collection, &sort.span);
+                for key in &sort.keys {
+                    let element = match (&collection, &key.field) {
+                        (VariableType::List { item, .. }, _) => item,
+                        (VariableType::Table { key, .. }, None) => key,
+                        (VariableType::Table { value, .. }, Some(_)) => value,
+                        (ty, _) => {
+                            let label = format!(
+                                "`SORT` puts a list or a table in order, this is {}",
+                                a(ty)
+                            );
+                            self.error(sort.span.clone(), &label);
+                            return;
+                        }
+                    };
+                    let span = key.field.as_ref().map_or(&sort.span, |_| &key.span);
+                    self.sort_operand(element, key.field.as_deref(), span);
+                }
+            }
+            Stmt::Search(search) => {
+                let collection = self.type_of(&search.collection, &search.span);
+                let VariableType::List { item, .. } = &collection else {
+                    let label =
+                        format!("`SEARCH` looks through a list, this is {}", a(&collection));
+                    self.error(search.span.clone(), &label);
+                    return;
+                };
+                let span = search.field.as_ref().map_or(&search.span, |(_, span)| span);
+                let element = self.sort_operand(item, search.key(&collection), span);
+                let store = &search.giving;
+                let value_ty = self.type_as(&store.value, &element, &store.span);
+                if !equatable(&element, &value_ty) {
+                    let label = format!(
+                        "The items are compared as {}, this is {}",
+                        a(&element),
+                        what(&store.value, &value_ty)
+                    );
+                    self.error(search.span.clone(), &label);
+                }
+                if let Some(ty) = self.target_type(store) {
+                    if !ty.is_numeric() {
+                        let label = format!(
+                            "\"{}\" is {}, so it can't hold the number of an item",
+                            store.target,
+                            a(&ty)
+                        );
+                        self.error(store.span.clone(), &label);
+                    }
+                }
+            }
+            Stmt::Append(store) => {
+                let Some(ty) = self.target_type(store) else {
+                    self.type_of(&store.value, &store.span);
+                    return;
+                };
+                let item = match &ty {
+                    VariableType::List {
+                        item, length: None, ..
+                    } => (**item).clone(),
+                    VariableType::List {
+                        length: Some(length),
+                        ..
+                    } => {
+                        let label = format!(
+                            "\"{}\" always has {length} item{}, only a list defined without a length can grow",
+                            store.target,
+                            if *length == 1 { "" } else { "s" }
+                        );
+                        self.report("0032", store.span.clone(), &label);
+                        return;
+                    }
+                    ty => {
+                        let label = format!("Only lists can grow, this is {}", a(ty));
+                        self.error(store.span.clone(), &label);
+                        return;
+                    }
+                };
+                let value_ty = self.type_as(&store.value, &item, &store.span);
+                if !movable(&value_ty, &item) {
+                    let label = format!(
+                        "\"{}\" is {}, but this is {}",
+                        store.target,
+                        a(&ty),
+                        what(&store.value, &value_ty)
+                    );
+                    self.error(store.span.clone(), &label);
+                }
+                self.check_truncation(&store.value, &item, &store.span);
+            }
+            _ => {}
+        }
+    }
+
+    /// Works out the type of `expr`. Errors in parts of it without a span of their own are
+    /// reported at `span`, the closest enclosing one.
+    fn type_of(&self, expr: &Expr, span: &Range<usize>) -> VariableType {
+        match expr {
+            Expr::Literal(LiteralExpr::String(_)) | Expr::Interpolation(_) => VariableType::String,
+            Expr::Literal(LiteralExpr::Integer(_)) => VariableType::Integer,
+            Expr::Literal(LiteralExpr::Decimal(_)) => VariableType::Decimal,
+            Expr::Literal(LiteralExpr::Boolean(_)) => VariableType::Boolean,
+            Expr::Literal(LiteralExpr::Figurative(figurative)) => figurative.default_type(),
+            Expr::Variable(reference) => match self.types.get(&reference.id) {
+                Some(ty) => ty.clone(),
+                None => unreachable!(
+                    "\"{}\" was resolved to a definition that wasn't checked before it",
+                    reference.name
+                ),
+            },
+            Expr::Negate(inner) => {
+                let ty = self.type_of(inner, span);
+                if !ty.is_numeric() {
+                    let label = format!("Only numbers can be negated, not {}", a(&ty));
+                    self.error(span.clone(), &label);
+                }
+                ty
+            }
+            Expr::Arithmetic(arithmetic) => {
+                let (l, r) = self.operand_types(&arithmetic.l, &arithmetic.r, &arithmetic.span);
+                if !l.is_numeric() || !r.is_numeric() {
+                    let verb = match arithmetic.op {
+                        ArithmeticOperators::Add => "add",
+                        ArithmeticOperators::Subtract => "subtract",
+                        ArithmeticOperators::Multiply => "multiply",
+                        ArithmeticOperators::Divide | ArithmeticOperators::Remainder => "divide",
+                    };
+                    let label = format!("Cannot {verb} {} and {}", a(&l), a(&r));
+                    self.error(arithmetic.span.clone(), &label);
+                }
+                VariableType::arithmetic(&l, &r, &arithmetic.op)
+            }
+            Expr::BinaryOp(binary) => {
+                let l = self.type_of(&binary.l, &binary.span);
+                // What a list or a table contains is one of its items or keys.
+                let member = match (&binary.op, &l) {
+                    (BinaryOperators::Contains, VariableType::List { item, .. }) => item,
+                    (
+                        BinaryOperators::Contains | BinaryOperators::ContainsKey,
+                        VariableType::Table { key, .. },
+                    ) => key,
+                    _ => &l,
+                };
+                let r = self.type_as(&binary.r, member, &binary.span);
+                let l = self.type_as(&binary.l, &r, &binary.span);
+                let valid = match binary.op {
+                    BinaryOperators::Contains => match &l {
+                        VariableType::List { item, .. } => equatable(item, &r),
+                        VariableType::Table { key, .. } => assignable(&r, key),
+                        l => l.is_text() && r.is_text(),
+                    },
+                    BinaryOperators::ContainsKey => {
+                        matches!(&l, VariableType::Table { key, .. } if assignable(&r, key))
+                    }
+                    BinaryOperators::EqualTo | BinaryOperators::NotEqualTo => equatable(&l, &r),
+                    _ => (l.is_text() && r.is_text()) || (l.is_numeric() && r.is_numeric()),
+                };
+                if !valid {
+                    let label = format!("Cannot compare {} with {}", a(&l), a(&r));
+                    self.error(binary.span.clone(), &label);
+                }
+                VariableType::Boolean
+            }
+            Expr::Logical(logical) => {
+                for side in [&logical.l, &logical.r] {
+                    let ty = self.type_of(side, &logical.span);
+                    if ty != VariableType::Boolean {
+                        let label = format!("Expected a condition on both sides, not {}", a(&ty));
+                        self.error(logical.span.clone(), &label);
+                    }
+                }
+                VariableType::Boolean
+            }
+            // Anything that `DISPLAY` shows as a single value can be joined, as it would be shown.
+            Expr::Concat(concat) => {
+                let span = &concat.span;
+                for side in [&concat.l, &concat.r] {
+                    let ty = self.type_of(side, span);
+                    let shown = ty.is_text()
+                        || ty.is_numeric()
+                        || matches!(ty, VariableType::Boolean | VariableType::Choice { .. });
+                    if !shown {
+                        let label = format!("Cannot join {} into a string", a(&ty));
+                        self.error(span.clone(), &label);
+                    }
+                }
+                VariableType::String
+            }
+            Expr::Characters(characters) => {
+                for index in [&characters.from, &characters.through] {
+                    let ty = self.type_of(&index.value, &index.span);
+                    if ty != VariableType::Integer {
+                        let label =
+                            format!("Characters are numbered by an INTEGER, this is {}", a(&ty));
+                        self.error(index.span.clone(), &label);
+                    }
+                }
+                let span = &characters.from.span;
+                let ty = self.type_of(&characters.text, span);
+                if !ty.is_text() {
+                    let label = format!("Only strings have characters, this is {}", a(&ty));
+                    self.error(span.clone(), &label);
+                }
+                VariableType::String
+            }
+            Expr::Length(length) => {
+                let ty = self.type_of(&length.text, &length.span);
+                if !ty.is_text() {
+                    let label = format!("Only strings have a length, this is {}", a(&ty));
+                    self.error(length.span.clone(), &label);
+                }
+                VariableType::Integer
+            }
+            Expr::Not(inner) => {
+                let ty = self.type_of(inner, span);
+                if ty != VariableType::Boolean {
+                    let label =
+                        format!("Only conditions can be negated with `NOT`, not {}", a(&ty));
+                    self.error(span.clone(), &label);
+                }
+                VariableType::Boolean
+            }
+            Expr::Edited(edited) => {
+                let ty = self.type_of(&edited.value, &edited.span);
+                if !ty.is_numeric() {
+                    let label = format!("Only numbers can be formatted, not {}", a(&ty));
+                    self.error(edited.span.clone(), &label);
+                }
+                VariableType::String
+            }
+            Expr::Member(member) => {
+                let record = self.type_of(&member.record, &member.span);
+                self.field_type(&record, &member.field, &member.span)
+            }
+            Expr::Item(item) => {
+                let list = self.type_of(&item.list, &item.index.span);
+                self.item_type(&list, &item.index)
+            }
+            Expr::Entry(entry) => {
+                let table = self.type_of(&entry.table, &entry.key.span);
+                self.entry_type(&table, &entry.key)
+            }
+            Expr::Function(function) => match function.id.and_then(|id| self.types.get(&id)) {
+                Some(ty) => ty.clone(),
+                None => {
+                    let label = "`main` runs the program, it can't be used as a value";
+                    self.error(function.span.clone(), label);
+                    unreachable!()
+                }
+            },
+            // Only left for the value of a `SET`, which the resolver doesn't look into.
+            Expr::ModuleVariable(variable) => {
+                let label = format!(
+                    "A `SET` stores a literal, not the variable \"{}\"",
+                    variable.name
+                );
+                self.error(variable.span.clone(), &label);
+                unreachable!()
+            }
+            Expr::Call(call) => {
+                let label = "Functions don't return values, so a `CALL` can't be used as one";
+                self.error(call.span.clone(), label);
+                unreachable!()
+            }
+        }
+    }
+
+    /// The arguments of a call have to fit what the function expects, in number and in type.
+    /// Calls of C functions like `printf` aren't checked.
+    fn check_call(&self, call: &FunctionCall) {
+        let args = call.args.iter().flatten().collect::<Vec<_>>();
+        let (name, ty) = match (&*call.func, call.id) {
+            (Expr::Variable(reference), _) => {
+                (&reference.name, self.type_of(&call.func, &call.span))
+            }
+            (Expr::Literal(LiteralExpr::String(name)), Some(id)) => match self.types.get(&id) {
+                Some(ty) => (name, ty.clone()),
+                None => return,
+            },
+            _ => {
+                for arg in args {
+                    self.type_of(arg, &call.span);
+                }
+                return;
+            }
+        };
+        let VariableType::Function { parameters } = &ty else {
+            let label = format!("\"{name}\" is {}, not a FUNCTION to call", a(&ty));
+            self.error(call.span.clone(), &label);
+            return;
+        };
+        if args.len() != parameters.len() {
+            let count = |n: usize| format!("{n} argument{}", if n == 1 { "" } else { "s" });
+            let label = format!(
+                "\"{name}\" expects {}, but this passes {}",
+                count(parameters.len()),
+                args.len()
+            );
+            let note = format!("\"{name}\" is {}.", a(&ty));
+            self.report_with_note("0036", call.span.clone(), &label, &note);
+        }
+        for (number, (arg, parameter)) in args.iter().zip(parameters).enumerate() {
+            let arg_ty = self.type_as(arg, parameter, &call.span);
+            if !assignable(&arg_ty, parameter) {
+                let label = format!(
+                    "Argument {} of \"{name}\" is {}, but this is {}",
+                    number + 1,
+                    a(parameter),
+                    what(arg, &arg_ty)
+                );
+                self.report("0036", call.span.clone(), &label);
+            }
+            self.check_truncation(arg, parameter, &call.span);
+        }
+    }
+
+    /// The type of what `store` stores into, following its fields. `None` if the target is
+    /// unknown, which the resolver has already reported.
+    fn target_type(&self, store: &Store) -> Option<VariableType> {
+        let mut ty = store.id.and_then(|id| self.types.get(&id)).cloned()?;
+        for access in &store.path {
+            ty = match access {
+                Access::Field(field) => self.field_type(&ty, field, &store.span),
+                Access::Item(index) => self.item_type(&ty, index),
+                Access::Entry(key) => self.entry_type(&ty, key),
+            };
+        }
+        Some(ty)
+    }
+
+    /// The type of the items of a list of type `list`, which are picked by an `INTEGER`.
+    fn item_type(&self, list: &VariableType, index: &Index) -> VariableType {
+        let index_ty = self.type_of(&index.value, &index.span);
+        if index_ty != VariableType::Integer {
+            let label = format!("Items are numbered by an INTEGER, this is {}", a(&index_ty));
+            self.error(index.span.clone(), &label);
+        }
+        match list {
+            VariableType::List { item, .. } => (**item).clone(),
+            list => {
+                let label = format!("Only lists have items, this is {}", a(list));
+                self.error(index.span.clone(), &label);
+                unreachable!()
+            }
+        }
+    }
+
+    /// The type of the values of a table of type `table`, which are looked up by its key.
+    fn entry_type(&self, table: &VariableType, key: &Index) -> VariableType {
+        let VariableType::Table { key: key_ty, value } = table else {
+            let label = format!("Only tables have entries, this is {}", a(table));
+            self.error(key.span.clone(), &label);
+            unreachable!()
+        };
+        let ty = self.type_of(&key.value, &key.span);
+        if !assignable(&ty, key_ty) {
+            let label = format!("The keys of this table are {key_ty}S, this is {}", a(&ty));
+            self.error(key.span.clone(), &label);
+        }
+        (**value).clone()
+    }
+
+    /// What is compared when items of type `element` are sorted or searched by `field`, or by
+    /// themselves without one.
+    fn sort_operand(
+        &self,
+        element: &VariableType,
+        field: Option<&str>,
+        span: &Range<usize>,
+    ) -> VariableType {
+        let ty = match field {
+            Some(field) => self.field_type(element, field, span),
+            None if matches!(element, VariableType::Record(_)) => {
+                let label = "Records are compared by their fields, say which with `BY`";
+                self.report("0033", span.clone(), label);
+                unreachable!()
+            }
+            None => element.clone(),
+        };
+        if !ordered(&ty) {
+            let label = match field {
+                Some(field) => format!("\"{field}\" is {}, which has no order", a(&ty)),
+                None => format!("The items are {ty}S, which have no order"),
+            };
+            self.report("0033", span.clone(), &label);
+        }
+        ty
+    }
+
+    /// The key of every `SORTED` list in `ty` has to be something items can be ordered by.
+    fn check_sorted(&self, ty: &VariableType) {
+        match ty {
+            VariableType::Record(fields) => {
+                for field in fields {
+                    self.check_sorted(&field.ty);
+                }
+            }
+            VariableType::List { item, sorted, .. } => {
+                if let Some(key) = sorted {
+                    self.sort_operand(item, key.field.as_deref(), &key.span);
+                }
+                self.check_sorted(item);
+            }
+            VariableType::Table { value, .. } => self.check_sorted(value),
+            _ => {}
+        }
+    }
+
+    /// Every overlay in a record, nested ones included, has to fit in the field it redefines.
+    fn check_overlays(&self, fields: &[Field]) {
+        for field in fields {
+            let mut inner = &field.ty;
+            while let VariableType::List { item, .. } = inner {
+                inner = item;
+            }
+            if let VariableType::Record(fields) = inner {
+                self.check_overlays(fields);
+            }
+            let Some(redefines) = &field.redefines else {
+                continue;
+            };
+            let Some(original) = fields.iter().find(|other| &other.name == redefines) else {
+                continue;
+            };
+            match (field.ty.size(), original.ty.size()) {
+                (Some(size), Some(available)) if size > available => {
+                    let label = format!(
+                        "\"{}\" takes {size} byte{}, but \"{redefines}\" only has {available}",
+                        field.name,
+                        if size == 1 { "" } else { "s" }
+                    );
+                    self.report("0030", field.span.clone(), &label);
+                }
+                (Some(_), Some(_)) => {}
+                (_, None) => {
+                    let label = format!(
+                        "\"{redefines}\" is {}, which has no fixed size to overlay",
+                        a(&original.ty)
+                    );
+                    self.report("0030", field.span.clone(), &label);
+                }
+                (None, _) => {
+                    let label = format!(
+                        "\"{}\" is {}, which has no fixed size to overlay",
+                        field.name,
+                        a(&field.ty)
+                    );
+                    self.report("0030", field.span.clone(), &label);
+                }
+            }
+        }
+    }
+
+    /// The type of the field `name` of a record of type `record`.
+    fn field_type(&self, record: &VariableType, name: &str, span: &Range<usize>) -> VariableType {
+        match (record.field(name), record) {
+            (Some(field), _) => field.ty.clone(),
+            (None, VariableType::Record(fields)) => {
+                let names = fields
+                    .iter()
+                    .map(|field| format!("\"{}\"", field.name))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let label = format!("There is no field \"{name}\" in this record");
+                let note = format!("Its fields are {names}.");
+                self.report_with_note("0029", span.clone(), &label, &note);
+                unreachable!()
+            }
+            (None, record) => {
+                let label = format!("Only records have fields, this is {}", a(record));
+                self.error(span.clone(), &label);
+                unreachable!()
+            }
+        }
+    }
+
+    /// The type of `expr` where a value of type `ty` is expected. A figurative constant takes
+    /// that type if it can stand for one, like codegen fills it in, and a string is a value of
+    /// a choice.
+    fn type_as(&self, expr: &Expr, ty: &VariableType, span: &Range<usize>) -> VariableType {
+        match (expr, ty) {
+            (Expr::Literal(LiteralExpr::Figurative(figurative)), ty) if figurative.fits(ty) => {
+                ty.clone()
+            }
+            (Expr::Literal(LiteralExpr::String(value)), VariableType::Choice { name, values }) => {
+                if !values.contains(value) {
+                    let label = format!("\"{value}\" is not a value of \"{name}\"");
+                    let values = values.iter().collect::<Vec<_>>();
+                    let note = format!("A \"{name}\" is {}.", either(&values));
+                    self.report_with_note("0034", span.clone(), &label, &note);
+                }
+                ty.clone()
+            }
+            (expr, _) => self.type_of(expr, span),
+        }
+    }
+
+    /// The types of the two sides of an operator. A decimal literal next to a `NUMBER` is an
+    /// exact `NUMBER` itself, like codegen treats it.
+    fn operand_types(
+        &self,
+        l: &Expr,
+        r: &Expr,
+        span: &Range<usize>,
+    ) -> (VariableType, VariableType) {
+        let l_ty = self.type_of(l, span);
+        let r_ty = self.type_of(r, span);
+        let exact = |expr: &Expr, ty: VariableType, other: &VariableType| match (
+            expr.fixed_literal(),
+            other,
+        ) {
+            (Some((_, scale)), VariableType::Fixed { .. }) => VariableType::Fixed {
+                digits: MAX_DIGITS,
+                scale,
+            },
+            _ => ty,
+        };
+        (exact(l, l_ty.clone(), &r_ty), exact(r, r_ty, &l_ty))
+    }
+
+    /// Warns when a string literal is longer than the `TEXT` it is stored in, as the end of it
+    /// will be lost.
+    fn check_truncation(&self, value: &Expr, ty: &VariableType, span: &Range<usize>) {
+        let (Expr::Literal(LiteralExpr::String(value)), VariableType::Text { length }) =
+            (value, ty)
+        else {
+            return;
+        };
+        let count = value.chars().count();
+        if count <= *length as usize {
+            return;
+        }
+        let label = format!("Only {length} of these {count} characters fit");
+        let mut warning = ErrorClient::new("0027", crate::errors::MessageKind::WARNING);
+        warning.set_file(&self.file, &self.file);
+        warning.set_span(span.clone());
+        warning.add_label(Some(&label));
+        warning.build_and_emit();
+    }
+
+    fn error(&self, span: Range<usize>, label: &str) {
+        self.report("0022", span, label);
+    }
+
+    fn report_with_note(&self, code: &str, span: Range<usize>, label: &str, note: &str) {
+        let mut error = ErrorClient::new(code, crate::errors::MessageKind::ERROR);
+        error.end_process(true);
+        error.set_file(&self.file, &self.file);
+        error.set_span(span);
+        error.add_label(Some(label));
+        error.add_note(note);
+        error.build_and_emit();
+    }
+
+    fn report(&self, code: &str, span: Range<usize>, label: &str) {
+        let mut error = ErrorClient::new(code, crate::errors::MessageKind::ERROR);
+        error.end_process(true);
+        error.set_file(&self.file, &self.file);
+        error.set_span(span);
+        error.add_label(Some(label));
+        error.build_and_emit();
+    }
+}